@@ -0,0 +1,66 @@
+//! How many instructions per second the interpreter runs, on roms that keep running
+//! without any input:
+//!
+//! ```sh
+//! cargo bench --target x86_64-unknown-linux-gnu --no-default-features --features native
+//! ```
+//!
+//! Add `-- splash` to only run the roms with `splash` in their name.
+
+use std::{fs, time::Instant};
+
+use chip_8::{emulator::Program, host::NativeHost, quirks::Preset};
+
+const ROMS: &[(&str, Preset)] = &[
+    ("splash-screen.ch8", Preset::Octo),
+    ("tests/3-corax+.ch8", Preset::Octo),
+    ("octojam1title.ch8", Preset::Octo),
+    ("superpong.ch8", Preset::SuperChip),
+    ("spacejam.ch8", Preset::Octo),
+];
+const FRAMES: usize = 600;
+const INSTRUCTIONS_PER_FRAME: usize = 10_000;
+
+fn main() {
+    // cargo passes --bench
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let mut total_instructions = 0;
+    let mut total_seconds = 0.0;
+    for &(name, preset) in ROMS {
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !name.contains(filter.as_str()))
+        {
+            continue;
+        }
+        let path = format!("{}/roms/{name}", env!("CARGO_MANIFEST_DIR"));
+        let rom = fs::read(&path).unwrap_or_else(|e| panic!("Could not read {path}: {e}"));
+        let mut program = Program::new(Box::new(NativeHost::new()));
+        program.quirks = preset.quirks();
+        // waiting for the display would measure the wait
+        program.quirks.display_wait = false;
+        program.set_platform(preset.platform());
+        program.load_rom(&rom).expect("The rom doesn't fit");
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            for _ in 0..INSTRUCTIONS_PER_FRAME {
+                program.tick().expect("The rom crashed");
+            }
+            program.timer_tick();
+        }
+        let seconds = start.elapsed().as_secs_f64();
+        let instructions = FRAMES * INSTRUCTIONS_PER_FRAME;
+        println!(
+            "{name:<24} {:>8.1} million instructions per second",
+            instructions as f64 / seconds / 1e6
+        );
+        total_instructions += instructions;
+        total_seconds += seconds;
+    }
+    println!(
+        "{:<24} {:>8.1} million instructions per second",
+        "all",
+        total_instructions as f64 / total_seconds / 1e6
+    );
+}
@@ -0,0 +1,209 @@
+//! Guesses what a rom was written for from the code the [disassembler](crate::disassembler)
+//! can reach, so an unknown `.ch8` can start with the right platform and quirks.
+//!
+//! The instructions tell the platform for sure. The quirks can only be guessed: the analysis
+//! finds the code that behaves differently between the interpreters and reports it, but it
+//! can't know which behavior the author tested with.
+
+use std::fmt::Display;
+
+use crate::{
+    disassembler::{disassemble, Disassembly},
+    emulator::Platform,
+    instruction::Instruction,
+    quirks::Preset,
+};
+
+// the program starts at 0x200 and the 4KB of the older platforms end at 0x1000
+const MAX_CHIP_8_SIZE: usize = 0x1000 - 0x200;
+// how far the code after FX55 and FX65 is searched for a use of I
+const INDEX_LOOKAHEAD: usize = 8;
+
+/// What the code of a rom needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub size: usize,
+    /// The instructions that CHIP-8 doesn't have, by address
+    pub super_chip_instructions: Vec<(u16, Instruction)>,
+    /// The instructions that only XO-CHIP has, by address
+    pub xo_chip_instructions: Vec<(u16, Instruction)>,
+    /// The 8XY6 and 8XYE where X isn't Y, they depend on the shifting quirk
+    pub shifts: Vec<u16>,
+    /// The FX55 and FX65 whose I is used again before it's set, they depend on the memory
+    /// increment quirk
+    pub index_reuses: Vec<u16>,
+    /// The BNNN, they depend on the jumping quirk
+    pub jump_offsets: Vec<u16>,
+    /// The 0NNN calls to machine code of the COSMAC VIP, with their opcode
+    pub machine_calls: Vec<(u16, u16)>,
+}
+
+/// Analyzes a rom that is loaded at 0x200
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let disassembly = disassemble(rom);
+    let mut analysis = Analysis {
+        size: rom.len(),
+        super_chip_instructions: Vec::new(),
+        xo_chip_instructions: Vec::new(),
+        shifts: Vec::new(),
+        index_reuses: Vec::new(),
+        jump_offsets: Vec::new(),
+        machine_calls: Vec::new(),
+    };
+    for (address, instruction) in disassembly.instructions() {
+        if !instruction.is_supported_on(Platform::SuperChip) {
+            analysis.xo_chip_instructions.push((address, instruction));
+        } else if is_super_chip(instruction) {
+            analysis
+                .super_chip_instructions
+                .push((address, instruction));
+        }
+        match instruction {
+            Instruction::ShiftRight { x, y } | Instruction::ShiftLeft { x, y } if x != y => {
+                analysis.shifts.push(address)
+            }
+            Instruction::StoreRegisters { .. } | Instruction::LoadRegisters { .. }
+                if reuses_index(&disassembly, address + instruction.size()) =>
+            {
+                analysis.index_reuses.push(address)
+            }
+            Instruction::JumpOffset { .. } => analysis.jump_offsets.push(address),
+            _ => {}
+        }
+    }
+    // 0000 is a placeholder that self-modifying code writes over, not a call to 0x000
+    analysis.machine_calls = disassembly
+        .unknown_opcodes()
+        .filter(|&(_, opcode)| opcode & 0xF000 == 0 && opcode != 0)
+        .collect();
+    analysis
+}
+
+/// The instructions that SUPER-CHIP added, the emulator runs them on every platform
+fn is_super_chip(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::LowResolution
+            | Instruction::HighResolution
+            | Instruction::Draw { rows: 0, .. }
+            | Instruction::LoadBigFont { .. }
+            | Instruction::SaveFlags { .. }
+            | Instruction::LoadFlags { .. }
+    )
+}
+
+/// Whether the straight line of code from the address uses I before setting it
+fn reuses_index(disassembly: &Disassembly, mut address: u16) -> bool {
+    for _ in 0..INDEX_LOOKAHEAD {
+        let Some(instruction) = disassembly.instruction_at(address) else {
+            return false;
+        };
+        match instruction {
+            Instruction::LoadIndex { .. }
+            | Instruction::LoadLongIndex
+            | Instruction::LoadFont { .. }
+            | Instruction::LoadBigFont { .. } => return false,
+            Instruction::Draw { .. }
+            | Instruction::SaveRange { .. }
+            | Instruction::LoadRange { .. }
+            | Instruction::AddIndex { .. }
+            | Instruction::StoreDigits { .. }
+            | Instruction::StoreRegisters { .. }
+            | Instruction::LoadRegisters { .. }
+            | Instruction::LoadAudio => return true,
+            // I could be set on the other side
+            Instruction::Jump { .. }
+            | Instruction::JumpOffset { .. }
+            | Instruction::Call { .. }
+            | Instruction::Return
+            | Instruction::Exit => return false,
+            _ => address += instruction.size(),
+        }
+    }
+    false
+}
+
+impl Analysis {
+    /// The oldest platform that has every instruction and fits the rom
+    pub fn platform(&self) -> Platform {
+        if !self.xo_chip_instructions.is_empty() || self.size > MAX_CHIP_8_SIZE {
+            Platform::XoChip
+        } else if !self.super_chip_instructions.is_empty() {
+            Platform::SuperChip
+        } else {
+            Platform::Chip8
+        }
+    }
+
+    /// The preset to start with, the one of the platform. None when the rom calls machine
+    /// code, which this emulator can't run.
+    pub fn preset(&self) -> Option<Preset> {
+        if !self.machine_calls.is_empty() {
+            return None;
+        }
+        Some(Preset::for_platform(self.platform()))
+    }
+
+    /// One sentence for everything that was found
+    pub fn findings(&self) -> Vec<String> {
+        let mut findings = vec![format!("{} bytes", self.size)];
+        if self.size > MAX_CHIP_8_SIZE {
+            findings.push("Too large for the 4KB of CHIP-8 and SUPER-CHIP".to_string());
+        }
+        let mut list = |addresses: Vec<u16>, text: &str| {
+            if let Some(&first) = addresses.first() {
+                findings.push(format!(
+                    "{} {text}, the first at {first:#06x}",
+                    addresses.len()
+                ));
+            }
+        };
+        list(
+            addresses(&self.xo_chip_instructions),
+            "XO-CHIP instructions",
+        );
+        list(
+            addresses(&self.super_chip_instructions),
+            "SUPER-CHIP instructions",
+        );
+        list(addresses(&self.machine_calls), "machine code calls (0NNN)");
+        list(
+            self.shifts.clone(),
+            "shifts of another register (8XY6/8XYE), check the shifting quirk",
+        );
+        list(
+            self.index_reuses.clone(),
+            "uses of I after FX55/FX65, check the memory increment quirk",
+        );
+        list(
+            self.jump_offsets.clone(),
+            "jumps with an offset (BNNN), check the jumping quirk",
+        );
+        findings
+    }
+}
+
+fn addresses<T>(found: &[(u16, T)]) -> Vec<u16> {
+    found.iter().map(|&(address, _)| address).collect()
+}
+
+impl Display for Analysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.preset() {
+            Some(preset) => write!(
+                f,
+                "Looks like {}, the {preset} quirks should fit",
+                self.platform().name()
+            ),
+            None => write!(
+                f,
+                "Looks like {} with machine code, which isn't supported",
+                self.platform().name()
+            ),
+        }
+    }
+}
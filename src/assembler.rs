@@ -0,0 +1,927 @@
+//! An assembler for [Octo](https://johnearnest.github.io/Octo/docs/Manual.html) programs,
+//! the `.8o` sources like `splash-screen/splash-screen.8o`.
+//!
+//! It covers the instructions the emulator runs, labels, `:alias`, `:const`, `:calc`,
+//! `:org`, `:macro`, `:call`, `:byte`, `:pointer`, `:unpack` and the control flow sugar:
+//! `if ... then`, `if ... begin ... else ... end`, `loop ... again` and `while`. The output
+//! is the same as Octo's, so the bundled roms can be rebuilt from their sources.
+
+mod calc;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::{error::AssemblyError, instruction::Instruction};
+
+const START_ADDRESS: usize = 0x200;
+const MEMORY_SIZE: usize = 0x10000;
+
+/// An assembled program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    /// The bytes that are loaded at 0x200
+    pub rom: Vec<u8>,
+    /// The address of every label
+    pub labels: BTreeMap<String, u16>,
+}
+
+impl Assembly {
+    /// One label per line in address order, like `0x0246 move-block-right-vB`
+    pub fn symbol_table(&self) -> String {
+        let mut labels: Vec<_> = self.labels.iter().collect();
+        labels.sort_by_key(|&(name, &address)| (address, name));
+        labels
+            .into_iter()
+            .map(|(name, address)| format!("{address:#06x} {name}\n"))
+            .collect()
+    }
+}
+
+/// Assembles an Octo program
+pub fn assemble(source: &str) -> Result<Assembly, AssemblyError> {
+    let mut assembler = Assembler::new(source);
+    while let Some(token) = assembler.tokens.pop_front() {
+        assembler.line = token.line;
+        assembler
+            .statement(&token.text)
+            .map_err(|message| assembler.error(message))?;
+    }
+    assembler.finish()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// Whitespace separates the tokens and `#` starts a comment
+fn tokenize(source: &str) -> VecDeque<Token> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |text| Token {
+                text: text.to_string(),
+                line: index + 1,
+            })
+        })
+        .collect()
+}
+
+/// Decimal, `0x` hexadecimal and `0b` binary numbers, with an optional minus
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+    match digit.len() {
+        1 => u8::from_str_radix(digit, 16).ok(),
+        _ => None,
+    }
+}
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Token>,
+    calls: usize,
+}
+
+/// A value that may only be known at the end, when all the labels are defined
+enum Address {
+    Known(u16),
+    Label(String),
+}
+
+/// Which bits of the instruction an address goes in
+#[derive(Clone, Copy)]
+enum AddressSize {
+    Twelve,
+    Sixteen,
+}
+
+struct Fixup {
+    position: usize,
+    size: AddressSize,
+    label: String,
+    line: usize,
+}
+
+/// The blocks that are waiting for their end
+enum Block {
+    // the jump over the block when the condition fails
+    If {
+        jump: usize,
+        line: usize,
+    },
+    // the jump over the else part
+    Else {
+        jump: usize,
+        line: usize,
+    },
+    // the jumps of the whiles go after the again
+    Loop {
+        start: u16,
+        exits: Vec<usize>,
+        line: usize,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+    Key,
+    NotKey,
+}
+
+impl Comparison {
+    fn negated(self) -> Self {
+        match self {
+            Comparison::Equal => Comparison::NotEqual,
+            Comparison::NotEqual => Comparison::Equal,
+            Comparison::Less => Comparison::GreaterOrEqual,
+            Comparison::Greater => Comparison::LessOrEqual,
+            Comparison::LessOrEqual => Comparison::Greater,
+            Comparison::GreaterOrEqual => Comparison::Less,
+            Comparison::Key => Comparison::NotKey,
+            Comparison::NotKey => Comparison::Key,
+        }
+    }
+}
+
+/// The right side of a comparison or an assignment
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+struct Condition {
+    x: u8,
+    comparison: Comparison,
+    operand: Operand,
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    line: usize,
+    rom: Vec<u8>,
+    here: usize,
+    labels: BTreeMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+    // 0x200 holds a jump to main unless main is the first thing in the program
+    jumps_to_main: bool,
+}
+
+impl Assembler {
+    fn new(source: &str) -> Self {
+        Self {
+            tokens: tokenize(source),
+            line: 1,
+            rom: vec![0; 2],
+            here: START_ADDRESS + 2,
+            labels: BTreeMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            jumps_to_main: true,
+        }
+    }
+
+    fn error(&self, message: String) -> AssemblyError {
+        AssemblyError {
+            line: self.line,
+            message,
+        }
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let token = self
+            .tokens
+            .pop_front()
+            .ok_or("The program ends in the middle of a statement")?;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("Expected '{expected}' but found '{token}'")),
+        }
+    }
+
+    fn statement(&mut self, token: &str) -> Result<(), String> {
+        if let Some(x) = self.register_of(token) {
+            return self.register_statement(x);
+        }
+        match token {
+            ":" => {
+                let name = self.next()?;
+                self.define_label(name)?;
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+            }
+            ":org" => {
+                let address = self.value()? as i64;
+                if !(START_ADDRESS as i64..MEMORY_SIZE as i64).contains(&address) {
+                    return Err(format!("Can't place code at {address:#x}"));
+                }
+                self.here = address as usize;
+            }
+            ":macro" => self.define_macro()?,
+            ":call" => {
+                let address = self.address()?;
+                self.address_instruction(0x2000, address)?;
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit(byte)?;
+            }
+            ":pointer" => {
+                let address = self.address()?;
+                self.address_of_size(AddressSize::Sixteen, 0x0000, address)?;
+            }
+            ":unpack" => {
+                // v0 gets the nibble and the high bits of the address, v1 the low byte
+                let nibble = self.value()? as u16 & 0xF;
+                let address = self.resolved_address()?;
+                self.instruction(Instruction::LoadByte {
+                    x: 0,
+                    byte: (nibble << 4 | address >> 8) as u8,
+                })?;
+                self.instruction(Instruction::LoadByte {
+                    x: 1,
+                    byte: address as u8,
+                })?;
+            }
+            "clear" => self.instruction(Instruction::ClearScreen)?,
+            "return" | ";" => self.instruction(Instruction::Return)?,
+            "scroll-down" => {
+                let rows = self.nibble()?;
+                self.instruction(Instruction::ScrollDown { rows })?;
+            }
+            "scroll-right" => self.instruction(Instruction::ScrollRight)?,
+            "scroll-left" => self.instruction(Instruction::ScrollLeft)?,
+            "exit" => self.instruction(Instruction::Exit)?,
+            "lores" => self.instruction(Instruction::LowResolution)?,
+            "hires" => self.instruction(Instruction::HighResolution)?,
+            "jump" => {
+                let address = self.address()?;
+                self.address_instruction(0x1000, address)?;
+            }
+            "jump0" => {
+                let address = self.address()?;
+                self.address_instruction(0xB000, address)?;
+            }
+            "native" => {
+                let address = self.address()?;
+                self.address_instruction(0x0000, address)?;
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let rows = self.nibble()?;
+                self.instruction(Instruction::Draw { x, y, rows })?;
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.instruction(Instruction::StoreDigits { x })?;
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                let save = token == "save";
+                let instruction = if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    if save {
+                        Instruction::SaveRange { x, y }
+                    } else {
+                        Instruction::LoadRange { x, y }
+                    }
+                } else if save {
+                    Instruction::StoreRegisters { x }
+                } else {
+                    Instruction::LoadRegisters { x }
+                };
+                self.instruction(instruction)?;
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.instruction(Instruction::SaveFlags { x })?;
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.instruction(Instruction::LoadFlags { x })?;
+            }
+            "plane" => {
+                let planes = self.nibble()?;
+                self.instruction(Instruction::SelectPlanes { planes })?;
+            }
+            "audio" => self.instruction(Instruction::LoadAudio)?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.instruction(match token {
+                    "delay" => Instruction::SetDelay { x },
+                    "buzzer" => Instruction::SetSound { x },
+                    _ => Instruction::SetPitch { x },
+                })?;
+            }
+            "i" => self.index_statement()?,
+            "if" => self.if_statement()?,
+            "else" => {
+                let Some(Block::If { jump, .. }) = self.blocks.pop() else {
+                    return Err("'else' without 'if ... begin'".to_string());
+                };
+                let line = self.line;
+                let else_jump = self.placeholder_jump()?;
+                self.patch_jump(jump, self.here)?;
+                self.blocks.push(Block::Else {
+                    jump: else_jump,
+                    line,
+                });
+            }
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) | Some(Block::Else { jump, .. }) => {
+                    self.patch_jump(jump, self.here)?;
+                }
+                _ => return Err("'end' without 'begin'".to_string()),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here as u16,
+                exits: Vec::new(),
+                line: self.line,
+            }),
+            "while" => {
+                let condition = self.condition()?;
+                // skip the jump out while the condition holds
+                self.skip_unless(Condition {
+                    comparison: condition.comparison.negated(),
+                    ..condition
+                })?;
+                let jump = self.placeholder_jump()?;
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { exits, .. } => Some(exits),
+                    _ => None,
+                }) {
+                    Some(exits) => exits.push(jump),
+                    None => return Err("'while' outside of a loop".to_string()),
+                }
+            }
+            "again" => {
+                let Some(Block::Loop { start, exits, .. }) = self.blocks.pop() else {
+                    return Err("'again' without 'loop'".to_string());
+                };
+                self.address_instruction(0x1000, Address::Known(start))?;
+                for exit in exits {
+                    self.patch_jump(exit, self.here)?;
+                }
+            }
+            _ => {
+                if let Some(value) = parse_number(token) {
+                    let byte = Self::to_byte(value)?;
+                    return self.emit(byte);
+                }
+                if self.macros.contains_key(token) {
+                    return self.expand_macro(token);
+                }
+                if let Some(&value) = self.constants.get(token) {
+                    let byte = Self::to_byte(value as i64)?;
+                    return self.emit(byte);
+                }
+                if token.starts_with(':') {
+                    return Err(format!("Unknown directive '{token}'"));
+                }
+                // a label on its own calls the subroutine
+                let address = self.label_address(token);
+                self.address_instruction(0x2000, address)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), String> {
+        let operator = self.next()?;
+        let instruction = match operator.as_str() {
+            ":=" => match self.peek() {
+                Some("key") => {
+                    self.next()?;
+                    Instruction::WaitKey { x }
+                }
+                Some("delay") => {
+                    self.next()?;
+                    Instruction::LoadDelay { x }
+                }
+                Some("random") => {
+                    self.next()?;
+                    let mask = self.byte()?;
+                    Instruction::Random { x, mask }
+                }
+                _ => match self.operand()? {
+                    Operand::Register(y) => Instruction::Load { x, y },
+                    Operand::Byte(byte) => Instruction::LoadByte { x, byte },
+                },
+            },
+            "+=" => match self.operand()? {
+                Operand::Register(y) => Instruction::Add { x, y },
+                Operand::Byte(byte) => Instruction::AddByte { x, byte },
+            },
+            "-=" => match self.operand()? {
+                Operand::Register(y) => Instruction::Sub { x, y },
+                Operand::Byte(byte) => Instruction::AddByte {
+                    x,
+                    byte: byte.wrapping_neg(),
+                },
+            },
+            "=-" => Instruction::SubReverse {
+                x,
+                y: self.register()?,
+            },
+            "|=" => Instruction::Or {
+                x,
+                y: self.register()?,
+            },
+            "&=" => Instruction::And {
+                x,
+                y: self.register()?,
+            },
+            "^=" => Instruction::Xor {
+                x,
+                y: self.register()?,
+            },
+            ">>=" => Instruction::ShiftRight {
+                x,
+                y: self.register()?,
+            },
+            "<<=" => Instruction::ShiftLeft {
+                x,
+                y: self.register()?,
+            },
+            _ => return Err(format!("Unknown register operation '{operator}'")),
+        };
+        self.instruction(instruction)
+    }
+
+    fn index_statement(&mut self) -> Result<(), String> {
+        match self.next()?.as_str() {
+            "+=" => {
+                let x = self.register()?;
+                self.instruction(Instruction::AddIndex { x })
+            }
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.instruction(Instruction::LoadFont { x })
+                }
+                Some("bighex") => {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.instruction(Instruction::LoadBigFont { x })
+                }
+                Some("long") => {
+                    self.next()?;
+                    let address = self.address()?;
+                    self.instruction(Instruction::LoadLongIndex)?;
+                    self.address_of_size(AddressSize::Sixteen, 0x0000, address)
+                }
+                _ => {
+                    let address = self.address()?;
+                    self.address_instruction(0xA000, address)
+                }
+            },
+            operator => Err(format!("Unknown index operation '{operator}'")),
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<(), String> {
+        let line = self.line;
+        let condition = self.condition()?;
+        match self.next()?.as_str() {
+            "then" => self.skip_unless(condition),
+            "begin" => {
+                // skip the jump over the block when the condition holds
+                self.skip_unless(Condition {
+                    comparison: condition.comparison.negated(),
+                    ..condition
+                })?;
+                let jump = self.placeholder_jump()?;
+                self.blocks.push(Block::If { jump, line });
+                Ok(())
+            }
+            token => Err(format!("Expected 'then' or 'begin' but found '{token}'")),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let x = self.register()?;
+        let comparison = match self.next()?.as_str() {
+            "key" => Comparison::Key,
+            "-key" => Comparison::NotKey,
+            "==" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            "<" => Comparison::Less,
+            ">" => Comparison::Greater,
+            "<=" => Comparison::LessOrEqual,
+            ">=" => Comparison::GreaterOrEqual,
+            token => return Err(format!("Unknown comparison '{token}'")),
+        };
+        let operand = match comparison {
+            Comparison::Key | Comparison::NotKey => Operand::Byte(0),
+            _ => self.operand()?,
+        };
+        Ok(Condition {
+            x,
+            comparison,
+            operand,
+        })
+    }
+
+    /// Emits the instructions that skip the next one unless the condition holds. The
+    /// ordered comparisons subtract into VF and check the borrow.
+    fn skip_unless(&mut self, condition: Condition) -> Result<(), String> {
+        let Condition {
+            x,
+            comparison,
+            operand,
+        } = condition;
+        let instruction = match (comparison, operand) {
+            (Comparison::Key, _) => Instruction::SkipIfNotPressed { x },
+            (Comparison::NotKey, _) => Instruction::SkipIfPressed { x },
+            (Comparison::Equal, Operand::Byte(byte)) => Instruction::SkipIfNotEqualByte { x, byte },
+            (Comparison::Equal, Operand::Register(y)) => Instruction::SkipIfNotEqual { x, y },
+            (Comparison::NotEqual, Operand::Byte(byte)) => Instruction::SkipIfEqualByte { x, byte },
+            (Comparison::NotEqual, Operand::Register(y)) => Instruction::SkipIfEqual { x, y },
+            (comparison, operand) => {
+                self.instruction(match operand {
+                    Operand::Register(y) => Instruction::Load { x: 0xF, y },
+                    Operand::Byte(byte) => Instruction::LoadByte { x: 0xF, byte },
+                })?;
+                // VF is 1 when there was no borrow
+                let (subtraction, skip) = match comparison {
+                    Comparison::Less => (Instruction::SubReverse { x: 0xF, y: x }, 0x4),
+                    Comparison::Greater => (Instruction::Sub { x: 0xF, y: x }, 0x4),
+                    Comparison::LessOrEqual => (Instruction::Sub { x: 0xF, y: x }, 0x3),
+                    _ => (Instruction::SubReverse { x: 0xF, y: x }, 0x3),
+                };
+                self.instruction(subtraction)?;
+                if skip == 0x4 {
+                    Instruction::SkipIfNotEqualByte { x: 0xF, byte: 0 }
+                } else {
+                    Instruction::SkipIfEqualByte { x: 0xF, byte: 0 }
+                }
+            }
+        };
+        self.instruction(instruction)
+    }
+
+    fn define_label(&mut self, name: String) -> Result<(), String> {
+        if self.labels.contains_key(&name) {
+            return Err(format!("The label '{name}' is already defined"));
+        }
+        if name == "main" && self.here == START_ADDRESS + 2 && self.rom.len() == 2 {
+            // nothing comes before main, so there is no need to jump to it
+            self.jumps_to_main = false;
+            self.rom.clear();
+            self.here = START_ADDRESS;
+        }
+        self.labels.insert(name, self.here as u16);
+        Ok(())
+    }
+
+    fn define_macro(&mut self) -> Result<(), String> {
+        let name = self.next()?;
+        let mut parameters = Vec::new();
+        loop {
+            match self.next()? {
+                brace if brace == "{" => break,
+                parameter => parameters.push(parameter),
+            }
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self
+                .tokens
+                .pop_front()
+                .ok_or(format!("The macro '{name}' has no end"))?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(token);
+        }
+        self.macros.insert(
+            name,
+            Macro {
+                parameters,
+                body,
+                calls: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Replaces the call with the body of the macro, where the parameters are the tokens
+    /// that follow and `CALLS` is how many times the macro was called before
+    fn expand_macro(&mut self, name: &str) -> Result<(), String> {
+        let parameter_count = self.macros[name].parameters.len();
+        let mut arguments = Vec::with_capacity(parameter_count);
+        for _ in 0..parameter_count {
+            arguments.push(self.next()?);
+        }
+        let line = self.line;
+        let macro_ = self.macros.get_mut(name).expect("The macro was just found");
+        let calls = macro_.calls.to_string();
+        macro_.calls += 1;
+        let expansion: Vec<Token> = macro_
+            .body
+            .iter()
+            .map(|token| {
+                let text = match macro_.parameters.iter().position(|p| *p == token.text) {
+                    Some(index) => arguments[index].clone(),
+                    None if token.text == "CALLS" => calls.clone(),
+                    None => token.text.clone(),
+                };
+                Token { text, line }
+            })
+            .collect();
+        for token in expansion.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    /// The tokens up to the closing brace, the opening one was already read
+    fn calc(&mut self) -> Result<f64, String> {
+        let mut tokens = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self
+                .tokens
+                .pop_front()
+                .ok_or("The expression has no closing brace")?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            tokens.push(token);
+        }
+        calc::evaluate(&tokens, &|name| self.value_of(name))
+    }
+
+    fn value_of(&self, name: &str) -> Option<f64> {
+        match name {
+            "HERE" => Some(self.here as f64),
+            "PI" => Some(std::f64::consts::PI),
+            "E" => Some(std::f64::consts::E),
+            _ => self
+                .constants
+                .get(name)
+                .copied()
+                .or_else(|| self.labels.get(name).map(|&address| address as f64)),
+        }
+    }
+
+    /// A number, a constant, a label that is already defined or an expression in braces
+    fn value(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        if token == "{" {
+            return self.calc();
+        }
+        if let Some(value) = parse_number(&token) {
+            return Ok(value as f64);
+        }
+        self.value_of(&token)
+            .ok_or_else(|| format!("'{token}' has no value"))
+    }
+
+    fn to_byte(value: i64) -> Result<u8, String> {
+        if !(-128..=255).contains(&value) {
+            return Err(format!("{value} doesn't fit in a byte"));
+        }
+        Ok(value as u8)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let value = self.value()?;
+        Self::to_byte(value as i64)
+    }
+
+    fn nibble(&mut self) -> Result<u8, String> {
+        let value = self.value()? as i64;
+        if !(0..=0xF).contains(&value) {
+            return Err(format!("{value} doesn't fit in 4 bits"));
+        }
+        Ok(value as u8)
+    }
+
+    fn register_of(&self, token: &str) -> Option<u8> {
+        parse_register(token).or_else(|| self.aliases.get(token).copied())
+    }
+
+    fn register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        self.register_of(&token)
+            .ok_or_else(|| format!("'{token}' is not a register"))
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        if let Some(register) = self.peek().and_then(|token| self.register_of(token)) {
+            self.next()?;
+            return Ok(Operand::Register(register));
+        }
+        Ok(Operand::Byte(self.byte()?))
+    }
+
+    /// Labels can be used before they are defined, anything else needs a value now
+    fn address(&mut self) -> Result<Address, String> {
+        let token = match self.peek() {
+            Some(token) => token.to_string(),
+            None => return Err("The program ends in the middle of a statement".to_string()),
+        };
+        if token == "{" || parse_number(&token).is_some() || self.value_of(&token).is_some() {
+            return Ok(Address::Known(self.value()? as u16));
+        }
+        self.next()?;
+        Ok(self.label_address(&token))
+    }
+
+    fn resolved_address(&mut self) -> Result<u16, String> {
+        match self.address()? {
+            Address::Known(address) => Ok(address),
+            Address::Label(label) => Err(format!("The label '{label}' isn't defined yet")),
+        }
+    }
+
+    fn label_address(&self, name: &str) -> Address {
+        match self.labels.get(name) {
+            Some(&address) => Address::Known(address),
+            None => Address::Label(name.to_string()),
+        }
+    }
+
+    fn instruction(&mut self, instruction: Instruction) -> Result<(), String> {
+        for byte in instruction.encode().to_be_bytes() {
+            self.emit(byte)?;
+        }
+        Ok(())
+    }
+
+    /// An instruction with the address in its lower 12 bits
+    fn address_instruction(&mut self, opcode: u16, address: Address) -> Result<(), String> {
+        self.address_of_size(AddressSize::Twelve, opcode, address)
+    }
+
+    fn address_of_size(
+        &mut self,
+        size: AddressSize,
+        opcode: u16,
+        address: Address,
+    ) -> Result<(), String> {
+        let position = self.here;
+        let value = match address {
+            Address::Known(address) => address,
+            Address::Label(label) => {
+                self.fixups.push(Fixup {
+                    position,
+                    size,
+                    label,
+                    line: self.line,
+                });
+                0
+            }
+        };
+        for byte in opcode.to_be_bytes() {
+            self.emit(byte)?;
+        }
+        self.patch(position, size, value)
+    }
+
+    /// A jump to an address that isn't known yet, returns where it is
+    fn placeholder_jump(&mut self) -> Result<usize, String> {
+        let position = self.here;
+        self.instruction(Instruction::Jump { address: 0 })?;
+        Ok(position)
+    }
+
+    fn patch_jump(&mut self, position: usize, target: usize) -> Result<(), String> {
+        self.patch(position, AddressSize::Twelve, target as u16)
+    }
+
+    fn patch(&mut self, position: usize, size: AddressSize, address: u16) -> Result<(), String> {
+        let index = position - START_ADDRESS;
+        match size {
+            AddressSize::Twelve => {
+                if address > 0xFFF {
+                    return Err(format!("The address {address:#x} doesn't fit in 12 bits"));
+                }
+                self.rom[index] = (self.rom[index] & 0xF0) | (address >> 8) as u8;
+                self.rom[index + 1] = address as u8;
+            }
+            AddressSize::Sixteen => {
+                self.rom[index..index + 2].copy_from_slice(&address.to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), String> {
+        if self.here >= MEMORY_SIZE {
+            return Err("The program doesn't fit in memory".to_string());
+        }
+        let index = self.here - START_ADDRESS;
+        if index >= self.rom.len() {
+            self.rom.resize(index + 1, 0);
+        }
+        self.rom[index] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Assembly, AssemblyError> {
+        if let Some(block) = self.blocks.last() {
+            let (line, message) = match *block {
+                Block::If { line, .. } | Block::Else { line, .. } => {
+                    (line, "'begin' without 'end'")
+                }
+                Block::Loop { line, .. } => (line, "'loop' without 'again'"),
+            };
+            return Err(AssemblyError {
+                line,
+                message: message.to_string(),
+            });
+        }
+        if self.jumps_to_main {
+            let fixup = Fixup {
+                position: START_ADDRESS,
+                size: AddressSize::Twelve,
+                label: "main".to_string(),
+                line: 1,
+            };
+            self.rom[0] = 0x10;
+            self.fixups.push(fixup);
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.line = fixup.line;
+            let address = *self.labels.get(&fixup.label).ok_or_else(|| {
+                self.error(format!("The label '{}' is never defined", fixup.label))
+            })?;
+            self.patch(fixup.position, fixup.size, address)
+                .map_err(|message| self.error(message))?;
+        }
+        Ok(Assembly {
+            rom: self.rom,
+            labels: self.labels,
+        })
+    }
+}
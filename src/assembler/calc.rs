@@ -0,0 +1,122 @@
+//! The expressions of `:calc`.
+//!
+//! Like in Octo, every binary operator has the same precedence and they are evaluated from
+//! right to left, so `2 * 3 + 1` is 8. Parentheses group. The values are floating point
+//! until they are stored.
+
+use super::Token;
+
+const BINARY: &[&str] = &[
+    "+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>", "pow", "min", "max", "<", "<=", "==", "!=",
+    ">=", ">",
+];
+const UNARY: &[&str] = &[
+    "-", "~", "!", "abs", "sqrt", "sin", "cos", "tan", "exp", "log", "sign", "ceil", "floor",
+];
+
+/// Evaluates the tokens of an expression, `lookup` finds the value of the names
+pub(super) fn evaluate(
+    tokens: &[Token],
+    lookup: &dyn Fn(&str) -> Option<f64>,
+) -> Result<f64, String> {
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        lookup,
+    };
+    let value = parser.expression()?;
+    match parser.tokens.get(parser.position) {
+        Some(token) => Err(format!("Unexpected '{}' in the expression", token.text)),
+        None => Ok(value),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    lookup: &'a dyn Fn(&str) -> Option<f64>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Result<&str, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or("The expression ends too early")?;
+        self.position += 1;
+        Ok(&token.text)
+    }
+
+    fn expression(&mut self) -> Result<f64, String> {
+        let left = self.term()?;
+        let operator = match self.tokens.get(self.position) {
+            Some(token) if BINARY.contains(&token.text.as_str()) => token.text.clone(),
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.expression()?;
+        Ok(binary(&operator, left, right))
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let token = self.next()?.to_string();
+        if token == "(" {
+            let value = self.expression()?;
+            return match self.next()? {
+                ")" => Ok(value),
+                other => Err(format!("Expected ')' but found '{other}'")),
+            };
+        }
+        if UNARY.contains(&token.as_str()) {
+            return Ok(unary(&token, self.term()?));
+        }
+        if let Some(value) = super::parse_number(&token) {
+            return Ok(value as f64);
+        }
+        (self.lookup)(&token).ok_or_else(|| format!("'{token}' has no value yet"))
+    }
+}
+
+fn binary(operator: &str, left: f64, right: f64) -> f64 {
+    let bits = |operation: fn(i64, i64) -> i64| operation(left as i64, right as i64) as f64;
+    let truth = |value: bool| if value { 1.0 } else { 0.0 };
+    match operator {
+        "+" => left + right,
+        "-" => left - right,
+        "*" => left * right,
+        "/" => left / right,
+        "%" => left % right,
+        "&" => bits(|a, b| a & b),
+        "|" => bits(|a, b| a | b),
+        "^" => bits(|a, b| a ^ b),
+        "<<" => bits(|a, b| a.wrapping_shl(b as u32)),
+        ">>" => bits(|a, b| a.wrapping_shr(b as u32)),
+        "pow" => left.powf(right),
+        "min" => left.min(right),
+        "max" => left.max(right),
+        "<" => truth(left < right),
+        "<=" => truth(left <= right),
+        "==" => truth(left == right),
+        "!=" => truth(left != right),
+        ">=" => truth(left >= right),
+        _ => truth(left > right),
+    }
+}
+
+fn unary(operator: &str, value: f64) -> f64 {
+    match operator {
+        "-" => -value,
+        "~" => !(value as i64) as f64,
+        "!" => (value == 0.0) as u8 as f64,
+        "abs" => value.abs(),
+        "sqrt" => value.sqrt(),
+        "sin" => value.sin(),
+        "cos" => value.cos(),
+        "tan" => value.tan(),
+        "exp" => value.exp(),
+        "log" => value.ln(),
+        "sign" => value.signum(),
+        "ceil" => value.ceil(),
+        _ => value.floor(),
+    }
+}
@@ -0,0 +1,60 @@
+//! Copies the Chip-8 Archive `programs.json` into `roms/metadata.json`:
+//!
+//! ```sh
+//! cargo run --target x86_64-unknown-linux-gnu --no-default-features --features native \
+//!     --bin archive -- path/to/programs.json
+//! ```
+//!
+//! The roms of the archive are matched by file name, see [`Database::import_archive`]. The
+//! bundled roms the archive doesn't have are listed so they can be filled in by hand.
+
+use std::{env, fs, path::PathBuf, process::ExitCode};
+
+use chip_8::metadata::Database;
+
+fn main() -> ExitCode {
+    let Some(programs_path) = env::args().nth(1) else {
+        eprintln!("Usage: archive path/to/programs.json");
+        return ExitCode::FAILURE;
+    };
+    let metadata_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("roms/metadata.json");
+    let (programs, metadata) = match (
+        fs::read_to_string(&programs_path),
+        fs::read_to_string(&metadata_path),
+    ) {
+        (Ok(programs), Ok(metadata)) => (programs, metadata),
+        (Err(e), _) => {
+            eprintln!("Could not read {programs_path}: {e}");
+            return ExitCode::FAILURE;
+        }
+        (_, Err(e)) => {
+            eprintln!("Could not read {}: {e}", metadata_path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut database = match Database::parse(&metadata) {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let imported = match database.import_archive(&programs) {
+        Ok(imported) => imported,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    for metadata in database.catalog() {
+        if !imported.contains(&metadata.file) {
+            println!("Not in the archive: {}", metadata.file);
+        }
+    }
+    if let Err(e) = fs::write(&metadata_path, database.to_json()) {
+        eprintln!("Could not write {}: {e}", metadata_path.display());
+        return ExitCode::FAILURE;
+    }
+    println!("Imported {} roms", imported.len());
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,102 @@
+//! Adds the keys every rom of `roms/metadata.json` reads to its controls:
+//!
+//! ```sh
+//! cargo run --target x86_64-unknown-linux-gnu --no-default-features --features native \
+//!     --bin controls
+//! ```
+//!
+//! Every rom runs for a while with its settings, holding each key in turn so menus and key
+//! waits move on, and the keys `EX9E` and `EXA1` check are kept. Keys the game reads only
+//! through `FX0A` can't be told apart and are left to be filled in by hand.
+
+use std::{collections::BTreeSet, fs, path::PathBuf, process::ExitCode};
+
+use chip_8::{
+    emulator::Program,
+    host::NativeHost,
+    instruction::Instruction,
+    metadata::{Database, Metadata},
+};
+
+const FRAMES: usize = 60 * 60;
+// how long each key is held, then released for as long
+const HOLD_FRAMES: usize = 8;
+// the default speed of the page
+const INSTRUCTIONS_PER_FRAME: u32 = 16;
+
+fn main() -> ExitCode {
+    let roms = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("roms");
+    let metadata_path = roms.join("metadata.json");
+    let mut database = match fs::read_to_string(&metadata_path)
+        .map_err(|e| format!("Could not read {}: {e}", metadata_path.display()))
+        .and_then(|json| Database::parse(&json))
+    {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut result = ExitCode::SUCCESS;
+    for metadata in database.roms_mut() {
+        let rom_path = roms.join(&metadata.file);
+        let rom = match fs::read(&rom_path) {
+            Ok(rom) => rom,
+            Err(e) => {
+                eprintln!("Could not read {}: {e}", rom_path.display());
+                result = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        match read_keys(metadata, &rom) {
+            Ok(keys) => {
+                let keys: BTreeSet<u8> = metadata.keys.iter().copied().chain(keys).collect();
+                metadata.keys = keys.into_iter().collect();
+            }
+            Err(e) => {
+                eprintln!("{}: {e}", metadata.file);
+                result = ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Err(e) = fs::write(&metadata_path, database.to_json()) {
+        eprintln!("Could not write {}: {e}", metadata_path.display());
+        return ExitCode::FAILURE;
+    }
+    result
+}
+
+fn read_keys(metadata: &Metadata, rom: &[u8]) -> Result<BTreeSet<u8>, String> {
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.set_platform(metadata.preset().platform());
+    program.quirks = metadata.quirks();
+    program.load_rom(rom).map_err(|e| e.to_string())?;
+    let instructions = metadata.options.tickrate.unwrap_or(INSTRUCTIONS_PER_FRAME);
+    let mut keys = BTreeSet::new();
+    for frame in 0..FRAMES {
+        let step = frame / HOLD_FRAMES;
+        program.pressed_keys = if step.is_multiple_of(2) {
+            1 << (step / 2 % 16)
+        } else {
+            0
+        };
+        for _ in 0..instructions {
+            let address = program.program_counter as usize;
+            let opcode = u16::from_be_bytes([
+                program.memory[address],
+                program.memory[(address + 1) % program.memory.len()],
+            ]);
+            if let Ok(Instruction::SkipIfPressed { x } | Instruction::SkipIfNotPressed { x }) =
+                Instruction::decode(opcode)
+            {
+                keys.insert(program.variable_regsiters[x as usize] & 0xF);
+            }
+            // the keys read before a rom stops are still its controls
+            if program.tick().is_err() {
+                return Ok(keys);
+            }
+        }
+        program.timer_tick();
+    }
+    Ok(keys)
+}
@@ -0,0 +1,53 @@
+//! Assembles an Octo program into a rom:
+//!
+//! ```sh
+//! cargo run --target x86_64-unknown-linux-gnu --no-default-features --features native \
+//!     --bin octo -- splash-screen/splash-screen.8o roms/splash-screen.ch8
+//! ```
+//!
+//! The symbol table is written next to the rom with the `.sym` extension when a third
+//! argument `--symbols` is given.
+
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use chip_8::assembler::assemble;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (source_path, rom_path) = match args.as_slice() {
+        [source, rom] | [source, rom, _] => (PathBuf::from(source), PathBuf::from(rom)),
+        _ => {
+            eprintln!("Usage: octo <source.8o> <rom.ch8> [--symbols]");
+            return ExitCode::FAILURE;
+        }
+    };
+    let with_symbols = args.get(2).is_some_and(|arg| arg == "--symbols");
+
+    let source = match fs::read_to_string(&source_path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Could not read {}: {e}", source_path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let assembly = match assemble(&source) {
+        Ok(assembly) => assembly,
+        Err(e) => {
+            eprintln!("{}:{e}", source_path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = fs::write(&rom_path, &assembly.rom) {
+        eprintln!("Could not write {}: {e}", rom_path.display());
+        return ExitCode::FAILURE;
+    }
+    if with_symbols {
+        let symbols_path = rom_path.with_extension("sym");
+        if let Err(e) = fs::write(&symbols_path, assembly.symbol_table()) {
+            eprintln!("Could not write {}: {e}", symbols_path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    println!("{} bytes", assembly.rom.len());
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,112 @@
+//! Draws the thumbnails of the roms in `roms/metadata.json` that have one:
+//!
+//! ```sh
+//! cargo run --target x86_64-unknown-linux-gnu --no-default-features --features native \
+//!     --bin thumbnails
+//! ```
+//!
+//! Every rom runs for a few seconds with its settings and without keys, the screen is then
+//! written as an SVG with one path per color and one rectangle per run of pixels.
+
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use chip_8::{
+    emulator::Program,
+    host::NativeHost,
+    metadata::{Database, Metadata},
+    palette::Palette,
+};
+
+const FRAMES: usize = 180;
+// the default speed of the page
+const INSTRUCTIONS_PER_FRAME: u32 = 16;
+
+fn main() -> ExitCode {
+    let roms = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("roms");
+    let mut result = ExitCode::SUCCESS;
+    for metadata in Database::bundled().catalog() {
+        let Some(thumbnail) = &metadata.thumbnail else {
+            continue;
+        };
+        let rom_path = roms.join(&metadata.file);
+        let rom = match fs::read(&rom_path) {
+            Ok(rom) => rom,
+            Err(e) => {
+                eprintln!("Could not read {}: {e}", rom_path.display());
+                result = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        let svg = match run(metadata, &rom) {
+            Ok(program) => draw(&program, metadata.palette().unwrap_or(Palette::OCTO)),
+            Err(e) => {
+                eprintln!("{}: {e}", metadata.file);
+                result = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        let thumbnail_path = roms.join(thumbnail);
+        let written = thumbnail_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&thumbnail_path, svg));
+        if let Err(e) = written {
+            eprintln!("Could not write {}: {e}", thumbnail_path.display());
+            result = ExitCode::FAILURE;
+        }
+    }
+    result
+}
+
+fn run(metadata: &Metadata, rom: &[u8]) -> Result<Program, String> {
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.set_platform(metadata.preset().platform());
+    program.quirks = metadata.quirks();
+    program.load_rom(rom).map_err(|e| e.to_string())?;
+    let instructions = metadata.options.tickrate.unwrap_or(INSTRUCTIONS_PER_FRAME);
+    for _ in 0..FRAMES {
+        for _ in 0..instructions {
+            // the screen of a rom that stopped is still a thumbnail
+            if program.tick().is_err() {
+                return Ok(program);
+            }
+        }
+        program.timer_tick();
+    }
+    Ok(program)
+}
+
+fn draw(program: &Program, palette: Palette) -> String {
+    let width = program.width() as usize;
+    let height = program.height() as usize;
+    let hex = |planes: u8| {
+        let [r, g, b, _] = palette.color(planes);
+        format!("#{r:02x}{g:02x}{b:02x}")
+    };
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width} {height}\" \
+         shape-rendering=\"crispEdges\">\n<rect width=\"{width}\" height=\"{height}\" fill=\"{}\"/>\n",
+        hex(0)
+    );
+    for planes in 1..4 {
+        let mut path = String::new();
+        for (y, row) in program.get_display().chunks(width).take(height).enumerate() {
+            let mut x = 0;
+            while x < width {
+                let run = row[x..]
+                    .iter()
+                    .take_while(|&&pixel| pixel & 0b11 == planes)
+                    .count();
+                if run > 0 {
+                    path.push_str(&format!("M{x} {y}h{run}v1h-{run}z"));
+                }
+                x += run.max(1);
+            }
+        }
+        if !path.is_empty() {
+            svg.push_str(&format!("<path fill=\"{}\" d=\"{path}\"/>\n", hex(planes)));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
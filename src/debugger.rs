@@ -1,28 +1,60 @@
-use std::sync::Mutex;
+use std::{collections::BTreeMap, sync::Mutex};
 
 use wasm_bindgen::JsCast;
-use web_sys::{HtmlTableElement, HtmlTableRowElement, Node};
+use web_sys::{Element, HtmlTableElement, HtmlTableRowElement, Node};
 
 use crate::{
-    emulator::Program,
+    disassembler::Disassembly,
+    emulator::{Access, Breakpoint, Program, Watchpoint, WatchpointHit},
+    expression::Expression,
+    instruction::Instruction,
     ui::{add_class_name, document, get_element, remove_class_name, to_number},
 };
 
 pub static INTERVAL_HANDLE: Mutex<Option<i32>> = Mutex::new(None);
 pub static RENDER_DEBUGGER: Mutex<bool> = Mutex::new(false);
-pub static BREAKPOINTS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+/// The code and data of the loaded rom
+pub static DISASSEMBLY: Mutex<Option<Disassembly>> = Mutex::new(None);
+/// The expressions that are shown next to the registers
+pub static WATCHES: Mutex<Vec<Expression>> = Mutex::new(Vec::new());
 
 pub fn render_debugger(program: &Program) {
     render_registers(
         &program.variable_regsiters,
         &get_element(&document(), "#registers-table"),
     );
+    render_watches(program);
 
     render_memory(
         &program.memory,
         program.program_counter as usize,
+        &program.breakpoints,
         &get_element(&document(), "#memory-table"),
     );
+    render_breakpoints(&program.breakpoints);
+    render_watchpoints(&program.watchpoints);
+    render_watchpoint_hit(program.watchpoint_hit.as_ref());
+}
+
+/// Shows the debugger and renders it from now on
+pub fn show_debugger() {
+    let document = document();
+    add_class_name(&get_element(&document, "#show-debugger"), "checked");
+    remove_class_name(&get_element(&document, "#debugger"), "off");
+    *RENDER_DEBUGGER.lock().unwrap() = true;
+}
+
+/// Shows where a breakpoint or watchpoint stopped the program, or hides it
+pub fn show_stop(stop: Option<&str>) {
+    let element: Element = get_element(&document(), "#debugger-stop");
+    element.set_text_content(stop);
+}
+
+pub fn hide_debugger() {
+    let document = document();
+    remove_class_name(&get_element(&document, "#show-debugger"), "checked");
+    add_class_name(&get_element(&document, "#debugger"), "off");
+    *RENDER_DEBUGGER.lock().unwrap() = false;
 }
 
 fn render_registers(registers: &[u8], table: &HtmlTableElement) {
@@ -47,7 +79,12 @@ fn render_registers(registers: &[u8], table: &HtmlTableElement) {
     });
 }
 
-fn render_memory(memory: &[u8], program_counter: usize, table: &HtmlTableElement) {
+fn render_memory(
+    memory: &[u8],
+    program_counter: usize,
+    breakpoints: &BTreeMap<u16, Breakpoint>,
+    table: &HtmlTableElement,
+) {
     let tbody = table
         .query_selector("tbody")
         .expect("bad query for tbody")
@@ -98,11 +135,7 @@ fn render_memory(memory: &[u8], program_counter: usize, table: &HtmlTableElement
         } else {
             remove_class_name(&row, "current-instruction");
         }
-        let is_breakpoint = BREAKPOINTS
-            .lock()
-            .unwrap()
-            .iter()
-            .any(|&breakpoint| breakpoint == address);
+        let is_breakpoint = breakpoints.contains_key(&(address as u16));
         if is_breakpoint {
             add_class_name(&row, "breakpoint");
         } else {
@@ -111,6 +144,149 @@ fn render_memory(memory: &[u8], program_counter: usize, table: &HtmlTableElement
     }
 }
 
+/// Shows the value of every watch, or why it has none. A click on one removes it.
+fn render_watches(program: &Program) {
+    let shown: Vec<(String, String)> = WATCHES
+        .lock()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .map(|(i, watch)| {
+            let text = match watch.evaluate(program) {
+                Ok(value) => format!("{watch} = {value} ({value:#x})"),
+                Err(e) => format!("{watch}: {e}"),
+            };
+            (i.to_string(), text)
+        })
+        .collect();
+    render_list(
+        &get_element(&document(), "#watch-list"),
+        "data-index",
+        &shown,
+    );
+}
+
+/// Lists every breakpoint, the ones outside of the memory page too. A click on one
+/// removes it.
+fn render_breakpoints(breakpoints: &BTreeMap<u16, Breakpoint>) {
+    let shown: Vec<(String, String)> = breakpoints
+        .iter()
+        .map(|(address, breakpoint)| {
+            let mut text = format!("{address:#05x}");
+            if let Some(condition) = &breakpoint.condition {
+                text.push_str(&format!(" if {condition}"));
+            }
+            if let Some(hit_count) = breakpoint.hit_count {
+                text.push_str(&format!(" from hit {hit_count}"));
+            }
+            text.push_str(&format!(", {} hits", breakpoint.hits));
+            (address.to_string(), text)
+        })
+        .collect();
+    render_list(
+        &get_element(&document(), "#breakpoint-list"),
+        "data-address",
+        &shown,
+    );
+}
+
+/// Lists the watchpoints in the order they were added. A click on one removes it.
+fn render_watchpoints(watchpoints: &[Watchpoint]) {
+    let shown: Vec<(String, String)> = watchpoints
+        .iter()
+        .enumerate()
+        .map(|(i, watchpoint)| {
+            let mut text = format!("{:#05x}", watchpoint.start);
+            if watchpoint.end != watchpoint.start {
+                text.push_str(&format!("..={:#05x}", watchpoint.end));
+            }
+            let accesses = [
+                (watchpoint.read, "read"),
+                (watchpoint.write, "write"),
+                (watchpoint.execute, "execute"),
+            ];
+            let accesses: Vec<&str> = accesses
+                .iter()
+                .filter(|(watched, _)| *watched)
+                .map(|(_, access)| *access)
+                .collect();
+            text.push_str(&format!(" on {}", accesses.join(", ")));
+            (i.to_string(), text)
+        })
+        .collect();
+    render_list(
+        &get_element(&document(), "#watchpoint-list"),
+        "data-index",
+        &shown,
+    );
+}
+
+/// Shows the instruction that hit a watchpoint last, with the memory before and after it
+fn render_watchpoint_hit(hit: Option<&WatchpointHit>) {
+    let text = hit.map(|hit| {
+        let instruction = Instruction::decode(hit.opcode)
+            .map_or_else(|e| e.to_string(), |instruction| instruction.to_string());
+        let bytes = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let location = format!("{} at {:#05x}", instruction, hit.instruction_address);
+        match hit.access {
+            Access::Read => format!("{location} read {:#05x}: {}", hit.address, bytes(&hit.new)),
+            Access::Write => format!(
+                "{location} wrote {:#05x}: {} -> {}",
+                hit.address,
+                bytes(&hit.old),
+                bytes(&hit.new)
+            ),
+            Access::Execute => format!("{location} is executed"),
+        }
+    });
+    let element = get_element::<Element>(&document(), "#watchpoint-hit");
+    if element.text_content() != text {
+        element.set_text_content(text.as_deref());
+    }
+}
+
+/// Replaces the items of the list when their text changed, the attribute keeps what each
+/// item is for
+fn render_list(list: &Element, attribute: &str, shown: &[(String, String)]) {
+    let document = document();
+    let children = list.child_nodes();
+    let unchanged = children.length() as usize == shown.len()
+        && (0..children.length()).zip(shown).all(|(i, (_, text))| {
+            children
+                .item(i)
+                .and_then(|item| item.text_content())
+                .as_ref()
+                == Some(text)
+        });
+    if unchanged {
+        return;
+    }
+    list.set_inner_html("");
+    for (value, text) in shown {
+        let item = document.create_element("li").unwrap();
+        item.set_text_content(Some(text));
+        item.set_attribute(attribute, value).unwrap();
+        list.append_child(&item).unwrap();
+    }
+}
+
+/// Shows why the text of an input can't be used under it, or hides it
+pub fn show_input_error(selector: &str, error: Option<&str>) {
+    let element: Element = get_element(&document(), selector);
+    element.set_text_content(error);
+    if error.is_some() {
+        remove_class_name(&element, "off");
+    } else if !element.class_name().contains("off") {
+        add_class_name(&element, "off");
+    }
+}
+
 fn render_address(
     address_node: &Node,
     mnemonic_node: &Node,
@@ -120,52 +296,16 @@ fn render_address(
 ) {
     address_node.set_text_content(Some(format!("{address}").as_str()));
     value_node.set_text_content(Some(format!("{:#04x}", value).as_str()));
-    mnemonic_node.set_text_content(Some(interpret_instruction(value).as_str()));
-}
-
-fn interpret_instruction(instruction: u16) -> String {
-    let first = (instruction & 0xF000) >> 12;
-    let second = (instruction & 0x0F00) >> 8;
-    let third = (instruction & 0x00F0) >> 4;
-    let fourth = instruction & 0x000F;
-    let nnn = instruction & 0x0FFF;
-    let kk = instruction & 0x00FF;
-    match (first, second, third, fourth) {
-        (0x0, 0x0, 0xE, 0x0) => "CLS".to_string().to_string(),
-        (0x0, 0x0, 0xE, 0xE) => "RET".to_string().to_string(),
-        (0x0, _, _, _) => format!("SYS {nnn:x}").to_string(),
-        (0x1, _, _, _) => format!("JP {nnn:x}").to_string(),
-        (0x2, _, _, _) => format!("CALL {nnn:x}").to_string(),
-        (0x3, _, _, _) => format!("SE V[{second:x}], {kk:x}").to_string(),
-        (0x4, _, _, _) => format!("SNE V[{second:x}], {kk:x}").to_string(),
-        (0x5, _, _, 0x0) => format!("SE V[{second:x}], V[{third:x}]").to_string(),
-        (0x6, _, _, _) => format!("LD V[{second:x}], {kk:x}").to_string(),
-        (0x7, _, _, _) => format!("ADD V[{second:x}], {kk:x}").to_string(),
-        (0x8, _, _, 0x0) => format!("LD V[{second:x}], V[{third:x}]").to_string(),
-        (0x8, _, _, 0x1) => format!("OR V[{second:x}], V[{third:x}]").to_string(),
-        (0x8, _, _, 0x2) => format!("AND V[{second:x}], V[{third:x}]").to_string(),
-        (0x8, _, _, 0x3) => format!("XOR V[{second:x}], V[{third:x}]").to_string(),
-        (0x8, _, _, 0x4) => format!("ADD V[{second:x}], V[{third:x}]").to_string(),
-        (0x8, _, _, 0x5) => format!("SUB V[{second:x}], V[{third:x}]").to_string(),
-        (0x8, _, _, 0x6) => format!("SHR V[{second:x}]").to_string(),
-        (0x8, _, _, 0x7) => format!("SUBR V[{second:x}], V[{third:x}]").to_string(),
-        (0x8, _, _, 0xE) => format!("SHL V[{second:x}]").to_string(),
-        (0x9, _, _, 0x0) => format!("SNE V[{second:x}], V[{third:x}]").to_string(),
-        (0xA, _, _, _) => format!("LD I, {nnn:x}").to_string(),
-        (0xB, _, _, _) => format!("JP V0, {nnn:x}").to_string(),
-        (0xC, _, _, _) => format!("RND V[{second:x}], {kk:x}").to_string(),
-        (0xD, _, _, _) => format!("DRW V[{second:x}], V[{third:x}], {fourth}").to_string(),
-        (0xE, _, 0x9, 0xE) => format!("SKP V[{second:x}]").to_string(),
-        (0xE, _, 0xA, 0x1) => format!("SKNP V[{second:x}]").to_string(),
-        (0xF, _, 0x0, 0x7) => format!("LD V[{second:x}], DT").to_string(),
-        (0xF, _, 0x0, 0xA) => format!("LD V[{second:x}], K").to_string(),
-        (0xF, _, 0x1, 0x5) => format!("LD DT, V[{second:x}]").to_string(),
-        (0xF, _, 0x1, 0x8) => format!("LD ST, V[{second:x}]").to_string(),
-        (0xF, _, 0x1, 0xE) => format!("ADD I, V[{second:x}]").to_string(),
-        (0xF, _, 0x2, 0x9) => format!("LD F, V[{second:x}]").to_string(),
-        (0xF, _, 0x3, 0x3) => format!("LD B, V[{second:x}]").to_string(),
-        (0xF, _, 0x5, 0x5) => format!("LD [I], V[{second:x}]").to_string(),
-        (0xF, _, 0x6, 0x5) => format!("LD V[{second:x}], [I]").to_string(),
-        _ => "".to_string(),
-    }
+    let is_data = DISASSEMBLY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|disassembly| disassembly.is_data(address as u16));
+    let mnemonic = match Instruction::decode(value) {
+        // sprites and tables decode to nonsense
+        _ if is_data => "data".to_string(),
+        Ok(instruction) => instruction.to_string(),
+        Err(e) => e.to_string(),
+    };
+    mnemonic_node.set_text_content(Some(mnemonic.as_str()));
 }
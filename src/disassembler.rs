@@ -0,0 +1,322 @@
+//! Separates the code of a rom from its data by following the control flow from 0x200,
+//! and turns the result back into an [Octo](https://johnearnest.github.io/Octo/) program
+//! that [`assemble`](crate::assembler::assemble) turns into the same rom.
+//!
+//! Jumps, calls and skips are followed. `jump0` depends on V0 and `return` on the call
+//! stack, so they end a path. Everything that is never reached is data, and is exported
+//! one byte per line with its sprite row in a comment.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::instruction::Instruction;
+
+const START_ADDRESS: usize = 0x200;
+
+/// A rom split into instructions and data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    rom: Vec<u8>,
+    // the instructions that can be reached, by address
+    code: BTreeMap<u16, Instruction>,
+    // the words that are reached but aren't instructions, like the 0NNN machine code calls
+    unknown: BTreeMap<u16, u16>,
+    labels: HashMap<u16, String>,
+}
+
+/// The kinds of labels, from the least to the most important. An address that is
+/// referenced in different ways is named after the most important one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    Data,
+    Table,
+    Label,
+    Loop,
+    Subroutine,
+    Main,
+}
+
+impl LabelKind {
+    fn name(self, address: u16) -> String {
+        let prefix = match self {
+            LabelKind::Data => "data",
+            LabelKind::Table => "table",
+            LabelKind::Label => "label",
+            LabelKind::Loop => "loop",
+            LabelKind::Subroutine => "sub",
+            LabelKind::Main => return "main".to_string(),
+        };
+        format!("{prefix}_{address:03X}")
+    }
+}
+
+/// Disassembles a rom that is loaded at 0x200
+pub fn disassemble(rom: &[u8]) -> Disassembly {
+    let mut disassembly = Disassembly {
+        rom: rom.to_vec(),
+        code: BTreeMap::new(),
+        unknown: BTreeMap::new(),
+        labels: HashMap::new(),
+    };
+    disassembly.follow(START_ADDRESS as u16);
+    disassembly.name_labels();
+    disassembly
+}
+
+impl Disassembly {
+    /// The instruction that starts at the address, if the control flow reaches it
+    pub fn instruction_at(&self, address: u16) -> Option<Instruction> {
+        self.code.get(&address).copied()
+    }
+
+    /// Whether the address is part of the rom but never runs
+    pub fn is_data(&self, address: u16) -> bool {
+        let in_rom = (START_ADDRESS..self.end()).contains(&(address as usize));
+        in_rom && !self.is_code(address)
+    }
+
+    /// Whether an instruction that can be reached covers the address
+    pub fn is_code(&self, address: u16) -> bool {
+        self.code
+            .range(..=address)
+            .next_back()
+            .is_some_and(|(&start, instruction)| address < start + instruction.size())
+    }
+
+    /// The instructions that can be reached, in the order of their addresses
+    pub fn instructions(&self) -> impl Iterator<Item = (u16, Instruction)> + '_ {
+        self.code
+            .iter()
+            .map(|(&address, &instruction)| (address, instruction))
+    }
+
+    /// The opcodes that can be reached but aren't instructions, by address
+    pub fn unknown_opcodes(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.unknown
+            .iter()
+            .map(|(&address, &opcode)| (address, opcode))
+    }
+
+    /// The generated name of the address, like `sub_2A4` or `loop_31C`
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    fn end(&self) -> usize {
+        START_ADDRESS + self.rom.len()
+    }
+
+    fn word(&self, address: usize) -> Option<u16> {
+        let index = address.checked_sub(START_ADDRESS)?;
+        match self.rom.get(index..index + 2)? {
+            &[high, low] => Some(u16::from_be_bytes([high, low])),
+            _ => None,
+        }
+    }
+
+    fn decode(&self, address: usize) -> Option<Instruction> {
+        Instruction::decode(self.word(address)?).ok()
+    }
+
+    /// Marks everything that can run from `entry` as code
+    fn follow(&mut self, entry: u16) {
+        let mut pending = vec![entry as usize];
+        while let Some(address) = pending.pop() {
+            if self.code.contains_key(&(address as u16)) {
+                continue;
+            }
+            let Some(word) = self.word(address) else {
+                continue;
+            };
+            let Ok(instruction) = Instruction::decode(word) else {
+                self.unknown.insert(address as u16, word);
+                continue;
+            };
+            // the address of F000 is in the next two bytes
+            if address + instruction.size() as usize > self.end() {
+                continue;
+            }
+            self.code.insert(address as u16, instruction);
+            let next = address + instruction.size() as usize;
+            match instruction {
+                Instruction::Jump { address } => pending.push(address as usize),
+                Instruction::Call { address } => {
+                    pending.push(address as usize);
+                    pending.push(next);
+                }
+                Instruction::Return | Instruction::Exit | Instruction::JumpOffset { .. } => {}
+                instruction if is_skip(instruction) => {
+                    let skipped = self.decode(next).map_or(2, Instruction::size);
+                    pending.push(next);
+                    pending.push(next + skipped as usize);
+                }
+                _ => pending.push(next),
+            }
+        }
+    }
+
+    fn name_labels(&mut self) {
+        let mut kinds: HashMap<u16, LabelKind> = HashMap::new();
+        let mut reference = |address: u16, kind: LabelKind| {
+            let best = kinds.entry(address).or_insert(kind);
+            *best = (*best).max(kind);
+        };
+        reference(START_ADDRESS as u16, LabelKind::Main);
+        for (&address, &instruction) in &self.code {
+            match instruction {
+                Instruction::Jump { address: target } if target <= address => {
+                    reference(target, LabelKind::Loop)
+                }
+                Instruction::Jump { address: target } => reference(target, LabelKind::Label),
+                Instruction::Call { address: target } => reference(target, LabelKind::Subroutine),
+                Instruction::JumpOffset { address: target } => reference(target, LabelKind::Table),
+                Instruction::LoadIndex { address: target } => {
+                    let kind = if self.code.contains_key(&target) {
+                        LabelKind::Label
+                    } else {
+                        LabelKind::Data
+                    };
+                    reference(target, kind);
+                }
+                Instruction::LoadLongIndex => {
+                    if let Some(target) = self.word(address as usize + 2) {
+                        reference(target, LabelKind::Data);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let end = self.end();
+        self.labels = kinds
+            .into_iter()
+            .filter(|&(address, _)| (START_ADDRESS..end).contains(&(address as usize)))
+            .map(|(address, kind)| (address, kind.name(address)))
+            .collect();
+    }
+
+    /// The rom as an Octo program, it assembles into the same bytes
+    pub fn to_octo(&self) -> String {
+        let mut octo = format!(
+            "# Disassembled from a {} byte rom, the data is shown as sprite rows\n",
+            self.rom.len()
+        );
+        let mut address = START_ADDRESS;
+        let mut after_skip = false;
+        while address < self.end() {
+            if let Some(label) = self.label(address as u16) {
+                octo.push_str(&format!("\n: {label}\n"));
+            }
+            let instruction = self
+                .instruction_at(address as u16)
+                .filter(|instruction| !self.has_label_inside(address, instruction.size()));
+            match instruction {
+                Some(instruction) => {
+                    let indent = if after_skip { "\t\t" } else { "\t" };
+                    let text = self.octo_instruction(address, instruction);
+                    octo.push_str(&format!("{indent}{text}\n"));
+                    after_skip = is_skip(instruction);
+                    address += instruction.size() as usize;
+                }
+                None => {
+                    // an instruction that something jumps into the middle of can't be
+                    // written as one, so its bytes are written one by one like data
+                    let byte = self.rom[address - START_ADDRESS];
+                    let row: String = (0..8)
+                        .map(|bit| if byte << bit & 0x80 != 0 { '#' } else { '.' })
+                        .collect();
+                    octo.push_str(&format!("\t{byte:#04x} # {row}\n"));
+                    after_skip = false;
+                    address += 1;
+                }
+            }
+        }
+        octo
+    }
+
+    fn has_label_inside(&self, address: usize, size: u16) -> bool {
+        (address + 1..address + size as usize).any(|inside| {
+            self.labels.contains_key(&(inside as u16)) || self.code.contains_key(&(inside as u16))
+        })
+    }
+
+    fn target(&self, address: u16) -> String {
+        match self.label(address) {
+            Some(label) => label.to_string(),
+            None => format!("{address:#05x}"),
+        }
+    }
+
+    fn octo_instruction(&self, address: usize, instruction: Instruction) -> String {
+        match instruction {
+            Instruction::ClearScreen => "clear".to_string(),
+            Instruction::Return => "return".to_string(),
+            Instruction::ScrollDown { rows } => format!("scroll-down {rows}"),
+            Instruction::ScrollRight => "scroll-right".to_string(),
+            Instruction::ScrollLeft => "scroll-left".to_string(),
+            Instruction::Exit => "exit".to_string(),
+            Instruction::LowResolution => "lores".to_string(),
+            Instruction::HighResolution => "hires".to_string(),
+            Instruction::Jump { address } => format!("jump {}", self.target(address)),
+            Instruction::Call { address } => format!(":call {}", self.target(address)),
+            // the skips run the next instruction when the opposite is true
+            Instruction::SkipIfEqualByte { x, byte } => format!("if v{x:x} != {byte:#04x} then"),
+            Instruction::SkipIfNotEqualByte { x, byte } => {
+                format!("if v{x:x} == {byte:#04x} then")
+            }
+            Instruction::SkipIfEqual { x, y } => format!("if v{x:x} != v{y:x} then"),
+            Instruction::SaveRange { x, y } => format!("save v{x:x} - v{y:x}"),
+            Instruction::LoadRange { x, y } => format!("load v{x:x} - v{y:x}"),
+            Instruction::LoadByte { x, byte } => format!("v{x:x} := {byte:#04x}"),
+            Instruction::AddByte { x, byte } => format!("v{x:x} += {byte:#04x}"),
+            Instruction::Load { x, y } => format!("v{x:x} := v{y:x}"),
+            Instruction::Or { x, y } => format!("v{x:x} |= v{y:x}"),
+            Instruction::And { x, y } => format!("v{x:x} &= v{y:x}"),
+            Instruction::Xor { x, y } => format!("v{x:x} ^= v{y:x}"),
+            Instruction::Add { x, y } => format!("v{x:x} += v{y:x}"),
+            Instruction::Sub { x, y } => format!("v{x:x} -= v{y:x}"),
+            Instruction::ShiftRight { x, y } => format!("v{x:x} >>= v{y:x}"),
+            Instruction::SubReverse { x, y } => format!("v{x:x} =- v{y:x}"),
+            Instruction::ShiftLeft { x, y } => format!("v{x:x} <<= v{y:x}"),
+            Instruction::SkipIfNotEqual { x, y } => format!("if v{x:x} == v{y:x} then"),
+            Instruction::LoadIndex { address } => format!("i := {}", self.target(address)),
+            Instruction::JumpOffset { address } => format!("jump0 {}", self.target(address)),
+            Instruction::Random { x, mask } => format!("v{x:x} := random {mask:#04x}"),
+            Instruction::Draw { x, y, rows } => format!("sprite v{x:x} v{y:x} {rows}"),
+            Instruction::SkipIfPressed { x } => format!("if v{x:x} -key then"),
+            Instruction::SkipIfNotPressed { x } => format!("if v{x:x} key then"),
+            Instruction::LoadLongIndex => {
+                let target = self.word(address + 2).unwrap_or_default();
+                match self.label(target) {
+                    Some(label) => format!("i := long {label}"),
+                    None => format!("i := long {target:#06x}"),
+                }
+            }
+            Instruction::SelectPlanes { planes } => format!("plane {planes}"),
+            Instruction::LoadAudio => "audio".to_string(),
+            Instruction::LoadDelay { x } => format!("v{x:x} := delay"),
+            Instruction::WaitKey { x } => format!("v{x:x} := key"),
+            Instruction::SetDelay { x } => format!("delay := v{x:x}"),
+            Instruction::SetSound { x } => format!("buzzer := v{x:x}"),
+            Instruction::AddIndex { x } => format!("i += v{x:x}"),
+            Instruction::LoadFont { x } => format!("i := hex v{x:x}"),
+            Instruction::LoadBigFont { x } => format!("i := bighex v{x:x}"),
+            Instruction::StoreDigits { x } => format!("bcd v{x:x}"),
+            Instruction::SetPitch { x } => format!("pitch := v{x:x}"),
+            Instruction::StoreRegisters { x } => format!("save v{x:x}"),
+            Instruction::LoadRegisters { x } => format!("load v{x:x}"),
+            Instruction::SaveFlags { x } => format!("saveflags v{x:x}"),
+            Instruction::LoadFlags { x } => format!("loadflags v{x:x}"),
+        }
+    }
+}
+
+fn is_skip(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::SkipIfEqualByte { .. }
+            | Instruction::SkipIfNotEqualByte { .. }
+            | Instruction::SkipIfEqual { .. }
+            | Instruction::SkipIfNotEqual { .. }
+            | Instruction::SkipIfPressed { .. }
+            | Instruction::SkipIfNotPressed { .. }
+    )
+}
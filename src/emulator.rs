@@ -1,64 +1,176 @@
+mod breakpoint;
+mod jit;
+mod save_state;
+mod timing;
+
+pub use breakpoint::{Access, Breakpoint, Watchpoint, WatchpointHit};
+pub use jit::{Block, MAX_MODULE_SIZE};
+pub use timing::{Timing, FETCH_CYCLES, VIP_CYCLES_PER_FRAME};
+
+use std::collections::BTreeMap;
+#[cfg(feature = "web")]
 use std::sync::{Mutex, OnceLock};
 
-use log::error;
-use web_sys::js_sys::Math::random;
+use sha1_smol::Sha1;
 
-use crate::ui;
+use crate::{
+    error::EmulatorError,
+    host::Host,
+    instruction::Instruction,
+    quirks::Quirks,
+    rng::{Rng, RngKind},
+};
 
 const DISPLAY_WIDTH: u8 = 64;
 const DISPLAY_HEIGHT: u8 = 32;
-const ON_COLOR: [u8; 4] = Program::hex_to_rgba(0x1d2021ff);
-const OFF_COLOR: [u8; 4] = Program::hex_to_rgba(0xfabd2fff);
-const RGBA: u8 = 4;
+// SUPER-CHIP doubles both sides in high resolution mode
+const HIRES_DISPLAY_WIDTH: u8 = 128;
+const HIRES_DISPLAY_HEIGHT: u8 = 64;
 
+#[cfg(feature = "web")]
 pub fn get_program() -> &'static Mutex<Program> {
     // this is some rust crazyness
     // This can only be written once so it's static
     static PROGRAM: OnceLock<Mutex<Program>> = OnceLock::new();
-    PROGRAM.get_or_init(|| Mutex::new(Program::new()))
+    PROGRAM.get_or_init(|| Mutex::new(Program::new(Box::new(crate::host::WebHost))))
+}
+
+/// The interpreter family a ROM was written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    /// 64KB of memory, two bitplanes and an audio pattern buffer
+    #[default]
+    XoChip,
+}
+
+impl Platform {
+    /// Guesses the platform from the conventional file extensions of the Chip-8 Archive.
+    /// `.ch8` is used by every platform so it tells us nothing.
+    pub fn from_file_name(name: &str) -> Option<Platform> {
+        let (_, extension) = name.rsplit_once('.')?;
+        match extension.to_lowercase().as_str() {
+            "sc8" => Some(Platform::SuperChip),
+            "xo8" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => 0x1000,
+            Platform::XoChip => 0x10000,
+        }
+    }
 }
 
 pub struct Program {
-    pub memory: [u8; 4096],
-    pub display: [u8; DISPLAY_WIDTH as usize * DISPLAY_HEIGHT as usize * RGBA as usize],
+    // only the first 4KB are addressable outside of XO-CHIP.
+    // call invalidate_decoded after changing code in here directly
+    pub memory: [u8; 0x10000],
+    // one byte per pixel with the planes that are on, bit 0 is the first plane and bit 1 the second.
+    // only the start of the display is used in low resolution mode
+    pub display: [u8; HIRES_DISPLAY_WIDTH as usize * HIRES_DISPLAY_HEIGHT as usize],
+    pub high_resolution: bool,
+    pub selected_planes: u8, // the XO-CHIP bitplanes that are drawn on, one bit per plane
     pub program_counter: u16,
     pub index_register: u16,
     pub call_stack: Vec<u16>,
     pub delay_timer: u8,
     pub sound_timer: u8,
     pub variable_regsiters: [u8; 16],
-    pub op_table: [OpCodeFn; 0xF + 1],
-    pub f_op_table: [OpCodeFn; 0x65 + 1],
     pub pressed_keys: u16, // each bit tells if the key is pressed
+    pub host: Box<dyn Host + Send>,
+    pub rng: Rng, // draws the numbers of CXNN
+    pub quirks: Quirks,
+    pub rpl_flags: [u8; 16], // the HP48 "RPL user flags" of FX75 and FX85
+    pub platform: Platform,
+    pub audio_pattern: Option<[u8; 16]>, // 128 1-bit samples, loaded by F002
+    pub pitch: u8,                       // the playback rate of the audio pattern
+    pub rom_hash: [u8; 20],              // SHA-1 of the loaded rom
+    /// The breakpoints [`tick`](Self::tick) stops at by address, they stay after a reset
+    pub breakpoints: BTreeMap<u16, Breakpoint>,
+    /// The memory ranges [`tick`](Self::tick) stops at when an instruction uses them
+    pub watchpoints: Vec<Watchpoint>,
+    /// The last access that a watchpoint stopped at
+    pub watchpoint_hit: Option<WatchpointHit>,
+    /// The instructions of a fixed speed frame that ran before a breakpoint stopped it
+    pub frame_progress: u32,
+    at_breakpoint: bool, // stopped at a breakpoint, its instruction runs the next time
+    watchpoint_pending: bool, // the last instruction hit a watchpoint, the next one stops
+    vertical_blank: bool, // set by the timer so the display wait quirk can draw
+    vip_cycle_debt: u32, // the cycles the last VIP frame went over, see timing.rs
+    vip_instructions: u32, // the instructions of a VIP frame a breakpoint stopped
+    key_to_release: Option<u8>, // FX0A got this key and waits for it to be released
+    // the instruction that starts at every address, decoded the first time it runs.
+    // writes to memory throw away the instructions they overlap
+    decoded: Box<[Option<Instruction>; 0x10000]>,
+    // the instruction that is executing, for the errors
+    instruction_address: u16,
+    instruction: Instruction,
 }
 
-type OpCodeFn = fn(program: &mut Program, instruction: u16);
-
 impl Program {
     const START_ADDRESS: u16 = 0x200;
     const FONT_START_ADDR: usize = 0x050;
-    fn new() -> Self {
-        const NULL_OP: OpCodeFn = |_, __| {};
+    const BIG_FONT_START_ADDR: usize = 0x0A0;
+    const DEFAULT_PITCH: u8 = 64; // plays the pattern at 4000 samples per second
+    const STACK_SIZE: usize = 16;
+    pub fn new(mut host: Box<dyn Host + Send>) -> Self {
+        let seed = host.entropy();
         let mut p = Self {
-            memory: [0; 4096],
-            display: [0; 8192],
+            memory: [0; 0x10000],
+            display: [0; HIRES_DISPLAY_WIDTH as usize * HIRES_DISPLAY_HEIGHT as usize],
+            high_resolution: false,
+            selected_planes: 0b01,
             program_counter: Self::START_ADDRESS,
             index_register: 0,
             call_stack: Vec::new(),
             delay_timer: 0,
             sound_timer: 0,
             variable_regsiters: [0; 16],
-            op_table: [NULL_OP; 0xF + 1],
-            f_op_table: [NULL_OP; 0x65 + 1],
             pressed_keys: 0,
+            host,
+            rng: Rng::new(seed, RngKind::default()),
+            quirks: Quirks::default(),
+            rpl_flags: [0; 16],
+            platform: Platform::default(),
+            audio_pattern: None,
+            pitch: Self::DEFAULT_PITCH,
+            rom_hash: [0; 20],
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            watchpoint_hit: None,
+            at_breakpoint: false,
+            watchpoint_pending: false,
+            vertical_blank: false,
+            frame_progress: 0,
+            vip_cycle_debt: 0,
+            vip_instructions: 0,
+            key_to_release: None,
+            decoded: vec![None; 0x10000]
+                .try_into()
+                .expect("The decoded instructions cover the memory"),
+            instruction_address: Self::START_ADDRESS,
+            instruction: Instruction::ClearScreen,
         };
         p.clear_display();
         p.set_font();
-        p.set_instruction_table();
         p
     }
 
     pub fn reset(&mut self) {
+        self.high_resolution = false;
+        self.selected_planes = 0b01;
         self.clear_display();
         self.program_counter = Self::START_ADDRESS;
         self.index_register = 0;
@@ -67,32 +179,131 @@ impl Program {
         self.sound_timer = 0;
         self.variable_regsiters = [0; 16];
         self.pressed_keys = 0;
-    }
-
-    pub fn load_rom(&mut self, rom: &[u8]) {
-        self.reset();
-        for (i, value) in rom.iter().enumerate() {
-            self.memory[i + Self::START_ADDRESS as usize] = *value;
+        self.watchpoint_hit = None;
+        self.at_breakpoint = false;
+        self.watchpoint_pending = false;
+        self.vertical_blank = false;
+        self.frame_progress = 0;
+        self.vip_cycle_debt = 0;
+        self.vip_instructions = 0;
+        self.key_to_release = None;
+        // the same seed plays the same game again
+        self.rng.restart();
+        self.audio_pattern = None;
+        self.pitch = Self::DEFAULT_PITCH;
+        self.host.set_audio_pattern(self.audio_pattern, self.pitch);
+    }
+
+    /// Changes the platform, some instructions are only available on some platforms
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        self.invalidate_decoded();
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), EmulatorError> {
+        let max_size = self.platform.memory_size() - Self::START_ADDRESS as usize;
+        if rom.len() > max_size {
+            return Err(EmulatorError::RomTooLarge {
+                size: rom.len(),
+                max_size,
+            });
         }
+        self.reset();
+        self.write_memory(Self::START_ADDRESS as usize, rom);
+        self.rom_hash = Sha1::from(rom).digest().bytes();
+        Ok(())
     }
 
     pub fn timer_tick(&mut self) {
         self.delay_timer = self.delay_timer.saturating_sub(1);
         self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.vertical_blank = true;
+        self.rng.timer_tick();
         if self.sound_timer != 0 {
-            ui::beep();
+            self.host.beep();
         } else {
-            ui::stop_beep();
+            self.host.stop_beep();
+        }
+    }
+
+    /// Runs the next instruction, or stops with [`EmulatorError::Breakpoint`] before an
+    /// instruction at a breakpoint. It runs on the next call. A watchpoint stops with
+    /// [`EmulatorError::Watchpoint`] before the instruction after the one that hit it.
+    pub fn tick(&mut self) -> Result<(), EmulatorError> {
+        self.check_breakpoint()?;
+        self.step()
+    }
+
+    fn step(&mut self) -> Result<(), EmulatorError> {
+        let instruction = self.fetch(self.program_counter)?;
+        self.instruction_address = self.program_counter;
+        self.instruction = instruction;
+        self.program_counter = self.program_counter.wrapping_add(2);
+        self.execute(instruction)
+    }
+
+    /// The instruction at the address, decoded only the first time. Only the instructions
+    /// of the current platform are decoded.
+    #[inline]
+    fn fetch(&mut self, address: u16) -> Result<Instruction, EmulatorError> {
+        // only addresses inside memory are ever decoded
+        match self.decoded[address as usize] {
+            Some(instruction) => Ok(instruction),
+            None => self.decode(address),
+        }
+    }
+
+    fn decode(&mut self, address: u16) -> Result<Instruction, EmulatorError> {
+        let address = address as usize;
+        if address + 1 >= self.platform.memory_size() {
+            return Err(EmulatorError::PcOutOfBounds {
+                program_counter: address as u16,
+            });
+        }
+        let opcode = u16::from_be_bytes([self.memory[address], self.memory[address + 1]]);
+        let instruction = Instruction::decode(opcode)
+            .ok()
+            .filter(|instruction| instruction.is_supported_on(self.platform))
+            .ok_or(EmulatorError::UnknownOpcode {
+                program_counter: address as u16,
+                opcode,
+            })?;
+        self.decoded[address] = Some(instruction);
+        Ok(instruction)
+    }
+
+    /// Copies memory into the buffer for an instruction, the watchpoints see every read of
+    /// the instructions
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) {
+        buffer.copy_from_slice(&self.memory[address..address + buffer.len()]);
+        if !self.watchpoints.is_empty() {
+            self.watch(Access::Read, address, buffer, buffer);
+        }
+    }
+
+    /// Writes memory for an instruction, the watchpoints see every write of the
+    /// instructions with the bytes it replaces
+    fn store_memory(&mut self, address: usize, bytes: &[u8]) {
+        if self.watchpoints.is_empty() {
+            return self.write_memory(address, bytes);
         }
+        let old = self.memory[address..address + bytes.len()].to_vec();
+        self.write_memory(address, bytes);
+        self.watch(Access::Write, address, &old, bytes);
+    }
+
+    /// Copies the bytes to memory and forgets the instructions they change
+    fn write_memory(&mut self, address: usize, bytes: &[u8]) {
+        self.memory[address..address + bytes.len()].copy_from_slice(bytes);
+        // the instruction that starts one byte before also has a byte in the range
+        let first = address.saturating_sub(1);
+        self.decoded[first..address + bytes.len()].fill(None);
     }
 
-    pub fn tick(&mut self) {
-        let instr_first_byte = self.memory[self.program_counter as usize];
-        let instr_second_byte = self.memory[(self.program_counter + 1) as usize];
-        let entire_instruction = ((instr_first_byte as u16) << 8) | instr_second_byte as u16;
-        let first_nible = instr_first_byte >> 4;
-        self.program_counter += 2;
-        self.op_table[first_nible as usize](self, entire_instruction);
+    /// Forgets every decoded instruction, needed after changing [`memory`](Self::memory)
+    /// directly
+    pub fn invalidate_decoded(&mut self) {
+        self.decoded.fill(None);
     }
 
     pub fn set_key_down(&mut self, key: u8) {
@@ -100,387 +311,528 @@ impl Program {
     }
 
     pub fn set_key_up(&mut self, key: u8) {
-        self.pressed_keys &= 0 << key;
+        self.pressed_keys &= !(0b1 << key);
     }
 
     fn key_is_pressed(&self, key: u8) -> bool {
-        ((self.pressed_keys >> key) & 0b1) == 1
+        // only the low nibble selects one of the 16 keys
+        ((self.pressed_keys >> (key & 0xF)) & 0b1) == 1
     }
 
-    pub fn get_display(&self) -> Vec<u8> {
-        self.display.into()
+    /// The pixels of the current resolution, row by row, see [`Palette`](crate::palette::Palette)
+    /// to turn them into colors
+    pub fn get_display(&self) -> &[u8] {
+        let size = self.width() as usize * self.height() as usize;
+        &self.display[..size]
     }
 
-    pub fn width() -> u8 {
-        DISPLAY_WIDTH
+    pub fn width(&self) -> u8 {
+        if self.high_resolution {
+            HIRES_DISPLAY_WIDTH
+        } else {
+            DISPLAY_WIDTH
+        }
+    }
+
+    pub fn height(&self) -> u8 {
+        if self.high_resolution {
+            HIRES_DISPLAY_HEIGHT
+        } else {
+            DISPLAY_HEIGHT
+        }
     }
 
     #[inline]
-    fn pixel_location(x: u8, y: u8) -> usize {
-        ((y * RGBA) as usize * DISPLAY_WIDTH as usize) + (x * RGBA) as usize
+    fn pixel_location(&self, x: u8, y: u8) -> usize {
+        y as usize * self.width() as usize + x as usize
     }
 
     fn set_font(&mut self) {
         // each character is 5 tall by 4 wide.
-        // every bytes is a new character
+        // every bytes is a new row, sprites are drawn from the most significant bit
         const CHARACTER_FONTS: [u8; 80] = [
-            0xF, 0x9, 0x9, 0x9, 0xF, // 0
-            0x2, 0x6, 0x2, 0x2, 0x7, // 1
-            0xF, 0x1, 0xF, 0x8, 0xF, // 2
-            0xF, 0x1, 0xF, 0x1, 0xF, // 3
-            0x9, 0x9, 0xF, 0x1, 0x1, // 4
-            0xF, 0x8, 0xF, 0x1, 0xF, // 5
-            0xF, 0x8, 0xF, 0x9, 0xF, // 6
-            0xF, 0x1, 0x2, 0x4, 0x4, // 7
-            0xF, 0x9, 0xF, 0x9, 0xF, // 8
-            0xF, 0x9, 0xF, 0x1, 0xF, // 9
-            0xF, 0x9, 0xF, 0x9, 0x9, // A
-            0xE, 0x9, 0xE, 0x9, 0xE, // B
-            0xF, 0x8, 0x8, 0x8, 0xF, // C
-            0xE, 0x9, 0x9, 0x9, 0xE, // D
-            0xF, 0x8, 0xF, 0x8, 0xF, // E
-            0xF, 0x8, 0xF, 0x8, 0x8, // F
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+            0x20, 0x60, 0x20, 0x20, 0x70, // 1
+            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+        ];
+        // the SUPER-CHIP font is 10 tall by 8 wide
+        const BIG_CHARACTER_FONTS: [u8; 160] = [
+            0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+            0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+            0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+            0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+            0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+            0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+            0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+            0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
         ];
         for (i, value) in CHARACTER_FONTS.iter().enumerate() {
             self.memory[i + Self::FONT_START_ADDR] = *value;
         }
+        for (i, value) in BIG_CHARACTER_FONTS.iter().enumerate() {
+            self.memory[i + Self::BIG_FONT_START_ADDR] = *value;
+        }
     }
 
     fn clear_display(&mut self) {
-        // there must be a better way of filling this
-        for i in 0..self.display.len() {
-            self.display[i] = OFF_COLOR[i % 4];
-        }
+        self.display.fill(0);
     }
 
-    const fn hex_to_rgba(hex: u32) -> [u8; 4] {
-        [
-            ((hex & 0xFF000000) >> 24) as u8,
-            ((hex & 0x00FF0000) >> 16) as u8,
-            ((hex & 0x0000FF00) >> 8) as u8,
-            (hex & 0x000000FF) as u8,
-        ]
+    /// Turns off the pixels of the given planes only
+    fn clear_planes(&mut self, planes: u8) {
+        for pixel_planes in self.display.iter_mut() {
+            *pixel_planes &= !planes;
+        }
     }
 
-    fn pixel_is_on(&mut self, location: usize) -> bool {
-        if ON_COLOR[0] == self.display[location] {
-            return true;
-        }
-        if OFF_COLOR[0] == self.display[location] {
-            return false;
+    /// Moves the selected planes by `dx` and `dy` pixels, the uncovered pixels are turned off
+    fn scroll_display(&mut self, dx: i16, dy: i16) {
+        let width = self.width() as i16;
+        let height = self.height() as i16;
+        let planes = self.selected_planes;
+        let old_display = self.display;
+        for y in 0..height {
+            for x in 0..width {
+                let location = self.pixel_location(x as u8, y as u8);
+                let (old_x, old_y) = (x - dx, y - dy);
+                let moved_planes = if (0..width).contains(&old_x) && (0..height).contains(&old_y) {
+                    old_display[self.pixel_location(old_x as u8, old_y as u8)]
+                } else {
+                    0
+                };
+                let kept_planes = self.pixel_planes(location) & !planes;
+                self.set_pixel_planes(location, kept_planes | (moved_planes & planes));
+            }
         }
-        panic!("Couldn't determine if pixel was on or off");
     }
 
-    fn invert_pixel(&mut self, location: usize) {
-        let color = if Self::pixel_is_on(self, location) {
-            OFF_COLOR
-        } else {
-            ON_COLOR
-        };
-        self.display[location] = color[0];
-        self.display[location + 1] = color[1];
-        self.display[location + 2] = color[2];
-        self.display[location + 3] = color[3];
-    }
-
-    pub fn set_instruction_table(&mut self) {
-        self.op_table[0x0] = Program::op_0;
-        self.op_table[0x1] = Program::op_1;
-        self.op_table[0x2] = Program::op_2;
-        self.op_table[0x3] = Program::op_3;
-        self.op_table[0x4] = Program::op_4;
-        self.op_table[0x5] = Program::op_5;
-        self.op_table[0x6] = Program::op_6;
-        self.op_table[0x7] = Program::op_7;
-        self.op_table[0x8] = Program::op_8;
-        self.op_table[0x9] = Program::op_9;
-        self.op_table[0xA] = Program::op_A;
-        self.op_table[0xB] = Program::op_B;
-        self.op_table[0xC] = Program::op_C;
-        self.op_table[0xD] = Program::op_D;
-        self.op_table[0xE] = Program::op_E;
-        self.op_table[0xF] = Program::op_F;
-
-        self.f_op_table[0x7] = Program::op_FX07;
-        self.f_op_table[0xA] = Program::op_FX0A;
-        self.f_op_table[0x15] = Program::op_FX15;
-        self.f_op_table[0x18] = Program::op_FX18;
-        self.f_op_table[0x1E] = Program::op_FX1E;
-        self.f_op_table[0x29] = Program::op_FX29;
-        self.f_op_table[0x33] = Program::op_FX33;
-        self.f_op_table[0x55] = Program::op_FX55;
-        self.f_op_table[0x65] = Program::op_FX65;
+    fn set_high_resolution(&mut self, high_resolution: bool) {
+        self.high_resolution = high_resolution;
+        self.clear_display();
     }
 
-    // Instructions bellow
-    fn op_0(program: &mut Program, instruction: u16) {
-        match instruction {
-            // clear screen
-            0x00E0 => {
-                program.clear_display();
-            },
-            // return from function
-            0x00EE => {
-                let pointer = program.call_stack.pop().expect("returned from a function without a return address");
-                program.program_counter = pointer;
-            },
-            _ => error!("Encountered an execute machine language routine instruction. This isn't implemented")
-        }
+    fn pixel_planes(&self, location: usize) -> u8 {
+        self.display[location]
     }
-    fn op_1(program: &mut Program, instruction: u16) {
-        let jump_location = instruction & 0x0FFF;
-        program.program_counter = jump_location;
-    }
-    fn op_2(program: &mut Program, instruction: u16) {
-        // call the function
-        let pointer = instruction & 0x0FFF;
-        program.call_stack.push(program.program_counter);
-        program.program_counter = pointer;
-    }
-    fn op_3(program: &mut Program, instruction: u16) {
-        let register_name = ((instruction & 0x0F00) >> 8) as usize;
-        let value = (instruction & 0x00FF) as u8;
-        if program.variable_regsiters[register_name] == value {
-            program.program_counter += 2;
-        }
+
+    fn set_pixel_planes(&mut self, location: usize, planes: u8) {
+        self.display[location] = planes;
+    }
+
+    /// Skips the next instruction, which is twice as long if it's the XO-CHIP long load
+    fn skip_instruction(&mut self) {
+        let next_instruction = self.program_counter as usize;
+        let is_long_load = self.platform == Platform::XoChip
+            && self.memory.get(next_instruction) == Some(&0xF0)
+            && self.memory.get(next_instruction + 1) == Some(&0x00);
+        let length = if is_long_load { 4 } else { 2 };
+        self.program_counter = self.program_counter.wrapping_add(length);
     }
-    fn op_4(program: &mut Program, instruction: u16) {
-        let register_name = ((instruction & 0x0F00) >> 8) as usize;
-        let value = (instruction & 0x00FF) as u8;
-        if program.variable_regsiters[register_name] != value {
-            program.program_counter += 2;
+
+    /// Makes sure that `length` bytes starting at `address` are in memory
+    fn check_memory(&self, address: usize, length: usize) -> Result<(), EmulatorError> {
+        let memory_size = self.platform.memory_size();
+        match (address..address + length).find(|&address| address >= memory_size) {
+            Some(address) => Err(EmulatorError::AddressOutOfBounds {
+                program_counter: self.instruction_address,
+                opcode: self.instruction.encode(),
+                address,
+            }),
+            None => Ok(()),
         }
     }
-    fn op_5(program: &mut Program, instruction: u16) {
-        let x_register_name = ((instruction & 0x0F00) >> 8) as usize;
-        let y_register_name = ((instruction & 0x00F0) >> 4) as usize;
-        if program.variable_regsiters[x_register_name]
-            == program.variable_regsiters[y_register_name]
-        {
-            program.program_counter += 2;
+
+    // Instructions bellow
+    fn execute(&mut self, instruction: Instruction) -> Result<(), EmulatorError> {
+        match instruction {
+            Instruction::ClearScreen => self.clear_planes(self.selected_planes),
+            Instruction::Return => {
+                self.program_counter =
+                    self.call_stack.pop().ok_or(EmulatorError::StackUnderflow {
+                        program_counter: self.instruction_address,
+                        opcode: self.instruction.encode(),
+                    })?;
+            }
+            Instruction::ScrollDown { rows } => self.scroll_display(0, rows as i16),
+            Instruction::ScrollRight => self.scroll_display(4, 0),
+            Instruction::ScrollLeft => self.scroll_display(-4, 0),
+            // we stay on this instruction forever
+            Instruction::Exit => self.repeat_instruction(),
+            Instruction::LowResolution => self.set_high_resolution(false),
+            Instruction::HighResolution => self.set_high_resolution(true),
+            Instruction::Jump { address } => self.program_counter = address,
+            Instruction::Call { address } => {
+                if self.call_stack.len() >= Self::STACK_SIZE {
+                    return Err(EmulatorError::StackOverflow {
+                        program_counter: self.instruction_address,
+                        opcode: self.instruction.encode(),
+                    });
+                }
+                self.call_stack.push(self.program_counter);
+                self.program_counter = address;
+            }
+            Instruction::SkipIfEqualByte { x, byte } => {
+                if self.variable_regsiters[x as usize] == byte {
+                    self.skip_instruction();
+                }
+            }
+            Instruction::SkipIfNotEqualByte { x, byte } => {
+                if self.variable_regsiters[x as usize] != byte {
+                    self.skip_instruction();
+                }
+            }
+            Instruction::SkipIfEqual { x, y } => {
+                if self.variable_regsiters[x as usize] == self.variable_regsiters[y as usize] {
+                    self.skip_instruction();
+                }
+            }
+            // VY can be smaller than VX
+            Instruction::SaveRange { x, y } => {
+                let index = self.index_register as usize;
+                self.check_memory(index, x.abs_diff(y) as usize + 1)?;
+                for (offset, register) in Self::register_range(x as usize, y as usize) {
+                    let value = self.variable_regsiters[register];
+                    self.store_memory(index + offset, &[value]);
+                }
+            }
+            Instruction::LoadRange { x, y } => {
+                let index = self.index_register as usize;
+                self.check_memory(index, x.abs_diff(y) as usize + 1)?;
+                for (offset, register) in Self::register_range(x as usize, y as usize) {
+                    let mut value = [0];
+                    self.read_memory(index + offset, &mut value);
+                    self.variable_regsiters[register] = value[0];
+                }
+            }
+            Instruction::LoadByte { x, byte } => self.variable_regsiters[x as usize] = byte,
+            Instruction::AddByte { x, byte } => {
+                let register = &mut self.variable_regsiters[x as usize];
+                *register = register.wrapping_add(byte);
+            }
+            Instruction::Load { .. }
+            | Instruction::Or { .. }
+            | Instruction::And { .. }
+            | Instruction::Xor { .. }
+            | Instruction::Add { .. }
+            | Instruction::Sub { .. }
+            | Instruction::ShiftRight { .. }
+            | Instruction::SubReverse { .. }
+            | Instruction::ShiftLeft { .. } => self.arithmetic(instruction),
+            Instruction::SkipIfNotEqual { x, y } => {
+                if self.variable_regsiters[x as usize] != self.variable_regsiters[y as usize] {
+                    self.skip_instruction();
+                }
+            }
+            Instruction::LoadIndex { address } => self.index_register = address,
+            Instruction::JumpOffset { address } => {
+                // with the jumping quirk, BXNN uses VX instead of V0
+                let offset_register = if self.quirks.jumping {
+                    (address >> 8) as usize
+                } else {
+                    0
+                };
+                self.program_counter = address + self.variable_regsiters[offset_register] as u16;
+            }
+            Instruction::Random { x, mask } => {
+                self.variable_regsiters[x as usize] = mask & self.rng.next_byte(&self.memory);
+            }
+            Instruction::Draw { x, y, rows } => self.draw(x, y, rows)?,
+            Instruction::SkipIfPressed { x } => {
+                if self.key_is_pressed(self.variable_regsiters[x as usize]) {
+                    self.skip_instruction();
+                }
+            }
+            Instruction::SkipIfNotPressed { x } => {
+                if !self.key_is_pressed(self.variable_regsiters[x as usize]) {
+                    self.skip_instruction();
+                }
+            }
+            Instruction::LoadLongIndex => {
+                // the address is the next 16 bits
+                let address_location = self.program_counter as usize;
+                self.check_memory(address_location, 2)?;
+                let mut address = [0; 2];
+                self.read_memory(address_location, &mut address);
+                self.index_register = u16::from_be_bytes(address);
+                let mask = (self.platform.memory_size() - 1) as u16;
+                self.program_counter = self.program_counter.wrapping_add(2) & mask;
+            }
+            Instruction::SelectPlanes { planes } => self.selected_planes = planes & 0b11,
+            Instruction::LoadAudio => {
+                let index = self.index_register as usize;
+                self.check_memory(index, 16)?;
+                let mut pattern = [0; 16];
+                self.read_memory(index, &mut pattern);
+                self.audio_pattern = Some(pattern);
+                self.host.set_audio_pattern(self.audio_pattern, self.pitch);
+            }
+            Instruction::LoadDelay { x } => self.variable_regsiters[x as usize] = self.delay_timer,
+            Instruction::WaitKey { x } => self.wait_key(x),
+            Instruction::SetDelay { x } => self.delay_timer = self.variable_regsiters[x as usize],
+            Instruction::SetSound { x } => self.sound_timer = self.variable_regsiters[x as usize],
+            Instruction::AddIndex { x } => {
+                self.index_register = self
+                    .index_register
+                    .wrapping_add(self.variable_regsiters[x as usize] as u16);
+                // going past the 4KB of memory counts as an "overflow" on some interpreters,
+                // XO-CHIP has the whole 16 bits
+                if self.index_register as usize >= self.platform.memory_size() {
+                    self.variable_regsiters[0xF_usize] = 1;
+                }
+            }
+            Instruction::LoadFont { x } => {
+                let hex = self.variable_regsiters[x as usize] & 0x0F;
+                self.index_register = (Self::FONT_START_ADDR + (hex as usize * 5)) as u16;
+            }
+            Instruction::LoadBigFont { x } => {
+                let hex = self.variable_regsiters[x as usize] & 0x0F;
+                self.index_register = (Self::BIG_FONT_START_ADDR + (hex as usize * 10)) as u16;
+            }
+            Instruction::StoreDigits { x } => {
+                let register_value = self.variable_regsiters[x as usize];
+                let index = self.index_register as usize;
+                self.check_memory(index, 3)?;
+                let digits = [
+                    register_value / 100,
+                    (register_value / 10) % 10,
+                    register_value % 10,
+                ];
+                self.store_memory(index, &digits);
+            }
+            Instruction::SetPitch { x } => {
+                self.pitch = self.variable_regsiters[x as usize];
+                self.host.set_audio_pattern(self.audio_pattern, self.pitch);
+            }
+            Instruction::StoreRegisters { x } => {
+                // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#fx55-and-fx65-store-and-load-memory
+                let start = self.index_register as usize;
+                let count = x as usize + 1;
+                self.check_memory(start, count)?;
+                let registers = self.variable_regsiters;
+                self.store_memory(start, &registers[..count]);
+                self.increment_index(count);
+            }
+            Instruction::LoadRegisters { x } => {
+                let start = self.index_register as usize;
+                let count = x as usize + 1;
+                self.check_memory(start, count)?;
+                let mut values = [0; 16];
+                self.read_memory(start, &mut values[..count]);
+                self.variable_regsiters[..count].copy_from_slice(&values[..count]);
+                self.increment_index(count);
+            }
+            Instruction::SaveFlags { x } => {
+                let count = x as usize + 1;
+                self.rpl_flags[..count].copy_from_slice(&self.variable_regsiters[..count]);
+            }
+            Instruction::LoadFlags { x } => {
+                let count = x as usize + 1;
+                self.variable_regsiters[..count].copy_from_slice(&self.rpl_flags[..count]);
+            }
         }
+        Ok(())
     }
-    fn op_6(program: &mut Program, instruction: u16) {
-        let register_name = ((instruction & 0x0F00) >> 8) as usize;
-        let value = (instruction & 0x00FF) as u8;
-        program.variable_regsiters[register_name] = value;
+
+    /// The registers from x to y (in either direction), paired with their offset from I
+    fn register_range(x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+        let registers: Vec<usize> = if x <= y {
+            (x..=y).collect()
+        } else {
+            (y..=x).rev().collect()
+        };
+        registers.into_iter().enumerate()
     }
-    fn op_7(program: &mut Program, instruction: u16) {
-        let register_name = ((instruction & 0x0F00) >> 8) as usize;
-        let value = (instruction & 0x00FF) as u8;
-        let register = &mut program.variable_regsiters[register_name];
-        *register = register.wrapping_add(value);
+
+    /// FX55 and FX65 move I past the registers on the older interpreters
+    fn increment_index(&mut self, count: usize) {
+        if self.quirks.memory_increment {
+            self.index_register = self.index_register.wrapping_add(count as u16);
+        }
     }
-    fn op_8(program: &mut Program, instruction: u16) {
-        // TODO: make these configurable for more modern interpreter
+
+    /// The 8XYN instructions
+    fn arithmetic(&mut self, instruction: Instruction) {
         // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#8xy6-and-8xye-shift
-        // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#fx55-and-fx65-store-and-load-memory
-        let x_register_name = ((instruction & 0x0F00) >> 8) as usize;
-        let y_register_name = ((instruction & 0x00F0) >> 4) as usize;
-        let y_register = program.variable_regsiters[y_register_name];
-
-        // this depends on the target platform: we default to 0 instead of VF
-        // since we choose to suport Chip 8 only, it defaults the flag to 0.
-        // UPDATE: this doesn't work with the Octo compiler since it expects no reset of the flag
-        let x_register = &mut program.variable_regsiters[x_register_name];
-
-        let op_type = instruction & 0x000F;
-        match op_type {
-            0x0 => {
+        let (x, y) = match instruction {
+            Instruction::Load { x, y }
+            | Instruction::Or { x, y }
+            | Instruction::And { x, y }
+            | Instruction::Xor { x, y }
+            | Instruction::Add { x, y }
+            | Instruction::Sub { x, y }
+            | Instruction::ShiftRight { x, y }
+            | Instruction::SubReverse { x, y }
+            | Instruction::ShiftLeft { x, y } => (x as usize, y as usize),
+            _ => unreachable!("{instruction} is not an arithmetic instruction"),
+        };
+        let y_register = self.variable_regsiters[y];
+        let quirks = self.quirks;
+
+        // the shift source depends on the target platform
+        let shift_source = if quirks.shifting {
+            self.variable_regsiters[x]
+        } else {
+            y_register
+        };
+        let x_register = &mut self.variable_regsiters[x];
+
+        match instruction {
+            Instruction::Load { .. } => {
                 *x_register = y_register;
             }
-            0x1 => {
+            Instruction::Or { .. } => {
                 *x_register |= y_register;
+                if quirks.vf_reset {
+                    self.variable_regsiters[0xF] = 0;
+                }
             }
-            0x2 => {
+            Instruction::And { .. } => {
                 *x_register &= y_register;
+                if quirks.vf_reset {
+                    self.variable_regsiters[0xF] = 0;
+                }
             }
-            0x3 => {
+            Instruction::Xor { .. } => {
                 *x_register ^= y_register;
+                if quirks.vf_reset {
+                    self.variable_regsiters[0xF] = 0;
+                }
             }
-            0x4 => {
+            Instruction::Add { .. } => {
                 let (new_value, overflow) = x_register.overflowing_add(y_register);
                 *x_register = new_value;
-                let overflow_value = if overflow { 1 } else { 0 };
-                program.variable_regsiters[0xF] = overflow_value;
+                self.variable_regsiters[0xF] = overflow as u8;
             }
-            0x5 => {
-                let f_flag_value = if *x_register >= y_register { 1 } else { 0 };
+            Instruction::Sub { .. } => {
+                let f_flag_value = (*x_register >= y_register) as u8;
                 *x_register = x_register.wrapping_sub(y_register);
-                program.variable_regsiters[0xF] = f_flag_value;
+                self.variable_regsiters[0xF] = f_flag_value;
             }
-            0x6 => {
-                let shifted_out = y_register & 0b1;
-                let new_value = y_register >> 1;
-                *x_register = new_value;
-                program.variable_regsiters[0xF] = shifted_out;
+            Instruction::ShiftRight { .. } => {
+                *x_register = shift_source >> 1;
+                self.variable_regsiters[0xF] = shift_source & 0b1;
             }
-            0x7 => {
-                let f_flag_value = if y_register >= *x_register { 1 } else { 0 };
+            Instruction::SubReverse { .. } => {
+                let f_flag_value = (y_register >= *x_register) as u8;
                 *x_register = y_register.wrapping_sub(*x_register);
-                program.variable_regsiters[0xF] = f_flag_value;
+                self.variable_regsiters[0xF] = f_flag_value;
             }
-            0xE => {
-                let shifted_out = (y_register & 0b10000000) >> 7;
-                let new_value = y_register << 1;
-                *x_register = new_value;
-                program.variable_regsiters[0xF] = shifted_out;
+            _ => {
+                *x_register = shift_source << 1;
+                self.variable_regsiters[0xF] = (shift_source & 0b10000000) >> 7;
             }
-            _ => error!("This arithmetic operation is not supported"),
         }
-
-        // program.variable_regsiters[0xF] = f_flag_value;
     }
-    fn op_9(program: &mut Program, instruction: u16) {
-        let x_register_name = ((instruction & 0x0F00) >> 8) as usize;
-        let y_register_name = ((instruction & 0x00F0) >> 4) as usize;
-        if program.variable_regsiters[x_register_name]
-            != program.variable_regsiters[y_register_name]
-        {
-            program.program_counter += 2;
+
+    fn draw(&mut self, x: u8, y: u8, rows: u8) -> Result<(), EmulatorError> {
+        if self.quirks.display_wait {
+            if !self.vertical_blank {
+                // try again until the next frame
+                self.repeat_instruction();
+                return Ok(());
+            }
+            self.vertical_blank = false;
         }
-    }
-    #[allow(non_snake_case)]
-    fn op_A(program: &mut Program, instruction: u16) {
-        let location = instruction & 0x0FFF;
-        program.index_register = location;
-    }
-    #[allow(non_snake_case)]
-    fn op_B(program: &mut Program, instruction: u16) {
-        // TODO: make this configurable with SUPER-CHIP
-        let jump_pointer = instruction & 0x0FFF;
-        let offset = program.variable_regsiters[0] as u16;
-        program.program_counter = jump_pointer + offset;
-    }
-    #[allow(non_snake_case)]
-    fn op_C(program: &mut Program, instruction: u16) {
-        let r = (random() * (u8::MAX as f64)) as u8;
-        let register_name = ((instruction & 0x0F00) >> 8) as usize;
-        let value = (instruction & 0x00FF) as u8;
-        program.variable_regsiters[register_name] = value & r;
-    }
-    #[allow(non_snake_case)]
-    fn op_D(program: &mut Program, instruction: u16) {
-        let x_register = (instruction & 0x0F00) >> 8;
-        let y_register = (instruction & 0x00F0) >> 4;
+        let clipping = self.quirks.clipping;
+        let width = self.width();
+        let height = self.height();
 
         // we use modulo in case the variable goes off screen
-        let x_start = program.variable_regsiters[x_register as usize] % DISPLAY_WIDTH;
-        let y_start = program.variable_regsiters[y_register as usize] % DISPLAY_HEIGHT;
-        let rows = (instruction & 0x000F) as u8;
-
-        program.variable_regsiters[0xF_usize] = 0;
-        for y in 0..rows {
-            let y_location = y_start + y;
-            if y_location >= DISPLAY_HEIGHT {
-                break;
+        let x_start = self.variable_regsiters[x as usize] % width;
+        let y_start = self.variable_regsiters[y as usize] % height;
+        // DXY0 draws a 16x16 sprite on SUPER-CHIP, 2 bytes per row
+        let (rows, columns) = if rows == 0 { (16, 16) } else { (rows, 8) };
+        let bytes_per_row = columns / 8;
+
+        self.variable_regsiters[0xF_usize] = 0;
+        // XO-CHIP draws the sprite for every selected plane, one after the other in memory
+        let plane_count = self.selected_planes.count_ones() as usize;
+        let sprite_size = (rows * bytes_per_row) as usize * plane_count;
+        self.check_memory(self.index_register as usize, sprite_size)?;
+        // at most 16 rows of 2 bytes for both planes
+        let mut sprite = [0; 64];
+        self.read_memory(self.index_register as usize, &mut sprite[..sprite_size]);
+        let mut sprite_offset = 0;
+        for plane in [0b01, 0b10] {
+            if self.selected_planes & plane == 0 {
+                continue;
             }
-
-            let sprite_row = program.memory[(program.index_register + y as u16) as usize];
-
-            for x in 0_u8..8 {
-                let x_location = x_start + x;
-                if x_location >= DISPLAY_WIDTH {
-                    break;
+            for y in 0..rows {
+                let mut y_location = y_start + y;
+                if y_location >= height {
+                    if clipping {
+                        break;
+                    }
+                    y_location %= height;
                 }
-                if ((sprite_row >> (7 - x)) & 0b1) == 1 {
-                    let pixel_location = Program::pixel_location(x_location, y_location);
-                    if program.pixel_is_on(pixel_location) {
-                        program.variable_regsiters[0xF_usize] = 1;
+
+                let row_offset = sprite_offset + (y * bytes_per_row) as usize;
+                let sprite_row = sprite[row_offset..row_offset + bytes_per_row as usize]
+                    .iter()
+                    .fold(0_u16, |row, &byte| (row << 8) | byte as u16);
+
+                for x in 0_u8..columns {
+                    let mut x_location = x_start + x;
+                    if x_location >= width {
+                        if clipping {
+                            break;
+                        }
+                        x_location %= width;
+                    }
+                    if ((sprite_row >> (columns - 1 - x)) & 0b1) == 1 {
+                        let pixel_location = self.pixel_location(x_location, y_location);
+                        let pixel_planes = self.pixel_planes(pixel_location);
+                        if pixel_planes & plane != 0 {
+                            self.variable_regsiters[0xF_usize] = 1;
+                        }
+                        self.set_pixel_planes(pixel_location, pixel_planes ^ plane);
                     }
-                    program.invert_pixel(pixel_location);
                 }
             }
+            sprite_offset += (rows * bytes_per_row) as usize;
         }
+        Ok(())
     }
-    #[allow(non_snake_case)]
-    fn op_E(program: &mut Program, instruction: u16) {
-        let register_name = ((instruction & 0x0F00) >> 8) as usize;
-        let key = program.variable_regsiters[register_name];
-        let is_pressed = program.key_is_pressed(key);
-        let instr_type = instruction & 0x00FF;
-        match (instr_type, is_pressed) {
-            (0x9E, true) => {
-                program.program_counter += 2;
-            }
-            (0x9E, false) => {}
-            (0xA1, false) => {
-                program.program_counter += 2;
-            }
-            (0xA1, true) => {}
-            _ => error!("This isntruction type shouldn't exist"),
-        }
-    }
-    #[allow(non_snake_case)]
-    fn op_F(program: &mut Program, instruction: u16) {
-        let instr_type = (instruction & 0x00FF) as usize;
-        let register_name = (instruction & 0x0F00) >> 8;
-        program.f_op_table[instr_type](program, register_name);
-    }
-    #[allow(non_snake_case)]
-    fn op_FX07(program: &mut Program, register_name: u16) {
-        program.variable_regsiters[register_name as usize] = program.delay_timer;
-    }
-    #[allow(non_snake_case)]
-    fn op_FX0A(program: &mut Program, register_name: u16) {
-        for key in 0..0xF_u8 {
-            if program.key_is_pressed(key) {
-                program.variable_regsiters[register_name as usize] = key;
+
+    fn wait_key(&mut self, x: u8) {
+        // like on the VIP, the key only counts once it's released
+        match self.key_to_release {
+            Some(key) if !self.key_is_pressed(key) => {
+                self.variable_regsiters[x as usize] = key;
+                self.key_to_release = None;
                 return;
             }
+            Some(_) => {}
+            None => self.key_to_release = (0..=0xF).find(|&key| self.key_is_pressed(key)),
         }
-        program.program_counter -= 2;
-    }
-    #[allow(non_snake_case)]
-    fn op_FX15(program: &mut Program, register_name: u16) {
-        program.delay_timer = program.variable_regsiters[register_name as usize];
-    }
-    #[allow(non_snake_case)]
-    fn op_FX18(program: &mut Program, register_name: u16) {
-        program.sound_timer = program.variable_regsiters[register_name as usize];
-    }
-    #[allow(non_snake_case)]
-    fn op_FX1E(program: &mut Program, register_name: u16) {
-        program.index_register += program.variable_regsiters[register_name as usize] as u16;
-        // this counts as an "overflow" on some interpreters
-        if program.index_register > 0xFFF {
-            program.variable_regsiters[0xF_usize] = 1;
-        }
+        self.repeat_instruction();
     }
-    #[allow(non_snake_case)]
-    fn op_FX29(program: &mut Program, register_name: u16) {
-        let hex = program.variable_regsiters[register_name as usize] & 0x0F;
-        program.index_register =
-            program.memory[Self::FONT_START_ADDR + ((hex * 5) as usize)] as u16;
-    }
-    #[allow(non_snake_case)]
-    fn op_FX33(program: &mut Program, register_name: u16) {
-        let register_value = program.variable_regsiters[register_name as usize];
-        let d1 = register_value / 100;
-        let d2 = (register_value / 10) % 10;
-        let d3 = register_value % 10;
-        program.memory[program.index_register as usize] = d1;
-        program.memory[(program.index_register + 1) as usize] = d2;
-        program.memory[(program.index_register + 2) as usize] = d3;
-    }
-    #[allow(non_snake_case)]
-    fn op_FX55(program: &mut Program, register_name: u16) {
-        // Since we are targeting the original chip 8,
-        // we also increment the i register
-
-        for i in 0..(register_name + 1) {
-            program.memory[program.index_register as usize] =
-                program.variable_regsiters[i as usize];
-            program.index_register += 1;
-        }
-    }
-    #[allow(non_snake_case)]
-    fn op_FX65(program: &mut Program, register_name: u16) {
-        for i in 0..(register_name + 1) {
-            program.variable_regsiters[i as usize] =
-                program.memory[program.index_register as usize];
-            program.index_register += 1;
-        }
+
+    /// Goes back to the instruction that is executing so it runs again. An instruction at
+    /// the end of memory left the program counter wrapped around to 0.
+    fn repeat_instruction(&mut self) {
+        let mask = (self.platform.memory_size() - 1) as u16;
+        self.program_counter = self.program_counter.wrapping_sub(2) & mask;
     }
 }
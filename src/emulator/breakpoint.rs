@@ -0,0 +1,166 @@
+//! Where and when the debugger stops the program.
+
+use crate::{error::EmulatorError, expression::Expression};
+
+use super::Program;
+
+/// Stops the program before the instruction at its address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// Only stops when the expression isn't 0, or can't be evaluated
+    pub condition: Option<Expression>,
+    /// Only stops from this hit on
+    pub hit_count: Option<u32>,
+    /// How many times the instruction was reached with the condition true
+    pub hits: u32,
+}
+
+impl Breakpoint {
+    pub fn new(condition: Option<Expression>, hit_count: Option<u32>) -> Self {
+        Self {
+            condition,
+            hit_count,
+            hits: 0,
+        }
+    }
+}
+
+/// How an instruction uses memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// The instruction itself is in the range, it stops before running
+    Execute,
+}
+
+/// Stops the program when an instruction uses memory in its range the way it watches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    /// Inclusive, so a watchpoint can end at the last byte of memory
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Watchpoint {
+    fn watches(&self, access: Access, address: usize, length: usize) -> bool {
+        let watched = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Execute => self.execute,
+        };
+        watched && address <= self.end as usize && address + length > self.start as usize
+    }
+}
+
+/// The access a watchpoint stopped at, for the debugger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchpointHit {
+    pub access: Access,
+    pub instruction_address: u16,
+    pub opcode: u16,
+    /// Where the access starts, it can start before the watched range
+    pub address: u16,
+    /// The bytes before the access, the same as `new` unless it writes
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+impl Program {
+    /// Stops where a watchpoint or a breakpoint says so. The instruction at the program
+    /// counter runs on the next call.
+    pub(super) fn check_breakpoint(&mut self) -> Result<(), EmulatorError> {
+        // the access happened in the last instruction, the program stops after it
+        if std::mem::take(&mut self.watchpoint_pending) {
+            return Err(self.watchpoint_error());
+        }
+        if std::mem::take(&mut self.at_breakpoint) {
+            return Ok(());
+        }
+        if !self.watchpoints.is_empty() && self.check_execute_watchpoint() {
+            self.at_breakpoint = true;
+            return Err(self.watchpoint_error());
+        }
+        self.check_address_breakpoint()
+    }
+
+    fn check_execute_watchpoint(&mut self) -> bool {
+        let address = self.program_counter;
+        let watched = self
+            .watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.watches(Access::Execute, address as usize, 2));
+        if watched {
+            let opcode = [address, address.wrapping_add(1)].map(|i| self.memory[i as usize]);
+            self.watchpoint_hit = Some(WatchpointHit {
+                access: Access::Execute,
+                instruction_address: address,
+                opcode: u16::from_be_bytes(opcode),
+                address,
+                old: opcode.to_vec(),
+                new: opcode.to_vec(),
+            });
+        }
+        watched
+    }
+
+    /// Remembers the first access of the instruction that a watchpoint watches, the
+    /// program stops before the next instruction
+    pub(super) fn watch(&mut self, access: Access, address: usize, old: &[u8], new: &[u8]) {
+        let watched = self
+            .watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.watches(access, address, new.len()));
+        if !watched || self.watchpoint_pending {
+            return;
+        }
+        self.watchpoint_hit = Some(WatchpointHit {
+            access,
+            instruction_address: self.instruction_address,
+            opcode: self.instruction.encode(),
+            address: address as u16,
+            old: old.to_vec(),
+            new: new.to_vec(),
+        });
+        self.watchpoint_pending = true;
+    }
+
+    fn watchpoint_error(&self) -> EmulatorError {
+        let hit = self
+            .watchpoint_hit
+            .as_ref()
+            .expect("A watchpoint stops after a hit");
+        EmulatorError::Watchpoint {
+            program_counter: hit.instruction_address,
+            address: hit.address,
+            access: hit.access,
+        }
+    }
+
+    fn check_address_breakpoint(&mut self) -> Result<(), EmulatorError> {
+        let address = self.program_counter;
+        let Some(breakpoint) = self.breakpoints.get(&address) else {
+            return Ok(());
+        };
+        let condition = breakpoint
+            .condition
+            .as_ref()
+            .map_or(Ok(1), |condition| condition.evaluate(self));
+        // a condition that fails stops so its error can be seen
+        if condition == Ok(0) {
+            return Ok(());
+        }
+        let breakpoint = self.breakpoints.get_mut(&address).unwrap();
+        breakpoint.hits += 1;
+        if breakpoint.hits < breakpoint.hit_count.unwrap_or(0) {
+            return Ok(());
+        }
+        self.at_breakpoint = true;
+        Err(EmulatorError::Breakpoint {
+            program_counter: address,
+        })
+    }
+}
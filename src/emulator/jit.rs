@@ -0,0 +1,458 @@
+//! Compiles the basic blocks of a program into WebAssembly, an optional tier above
+//! [`tick`](Program::tick) for the browser.
+//!
+//! A block runs straight through the instructions that only use the registers, I and reads
+//! of memory. It stops before the first one the interpreter has to run: drawing, waiting for
+//! a key, the stack, the timers and the random numbers, and every write to memory so that
+//! self-modifying code is always noticed. A jump ends the block unless a skip can jump over
+//! it, and a jump back to the start of the block loops without leaving it. A block whose
+//! module would reach [`MAX_MODULE_SIZE`] loses instructions from its end until it fits.
+//!
+//! The module imports the memory of the emulator and exports
+//! `run(registers, memory, index, program_counter, budget) -> instructions`, the first four
+//! are the addresses of the fields of [`Program`] in that memory. It runs at most `budget`
+//! instructions. It also stops before an instruction that would fail, so the interpreter runs
+//! it and reports the error.
+
+mod wasm;
+
+use wasm::Code;
+
+use crate::{instruction::Instruction, quirks::Quirks};
+
+use super::{Platform, Program};
+
+const MAX_BLOCK_LENGTH: usize = 64;
+/// Browsers only compile modules under 4KB on the main thread, bigger blocks are shortened
+pub const MAX_MODULE_SIZE: usize = 4096;
+
+// the parameters of `run`, then its locals
+const REGISTERS: u32 = 0;
+const MEMORY: u32 = 1;
+const INDEX: u32 = 2;
+const PROGRAM_COUNTER: u32 = 3;
+const BUDGET: u32 = 4;
+const COUNT: u32 = 5; // the instructions that ran
+const I: u32 = 6; // the index register, stored back when the block ends
+const SKIP: u32 = 7; // set when the last instruction was a skip that skips
+const VALUE: u32 = 8;
+const PARAMETERS: u32 = 5;
+const LOCALS: u32 = 4;
+
+/// A run of instructions compiled into a WebAssembly module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    start: u16,
+    // the compiled instructions by address, followed by the one after a final skip
+    instructions: Vec<(u16, Instruction)>,
+    length: usize,
+    platform: Platform,
+    quirks: Quirks,
+    module: Vec<u8>,
+}
+
+impl Block {
+    /// The address of the first instruction
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// How many instructions were compiled
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// The addresses of the compiled instructions
+    pub fn addresses(&self) -> impl Iterator<Item = u16> + '_ {
+        self.instructions[..self.length]
+            .iter()
+            .map(|&(address, _)| address)
+    }
+
+    /// The bytes of the module, see the module documentation for its function
+    pub fn module(&self) -> &[u8] {
+        &self.module
+    }
+}
+
+impl Program {
+    /// Compiles the block that starts at the address, if its first instruction can be
+    /// compiled
+    pub fn compile_block(&mut self, start: u16) -> Option<Block> {
+        let mut instructions = Vec::new();
+        let mut address = start;
+        let mut after_skip = false;
+        while instructions.len() < MAX_BLOCK_LENGTH {
+            let Ok(instruction) = self.fetch(address) else {
+                break;
+            };
+            if !self.can_compile(address, instruction) {
+                break;
+            }
+            // how far a skip goes depends on the next instruction
+            if is_skip(instruction) && self.fetch(address.wrapping_add(2)).is_err() {
+                break;
+            }
+            instructions.push((address, instruction));
+            address = address.wrapping_add(instruction.size());
+            if ends_block(instruction) && !after_skip {
+                break;
+            }
+            after_skip = is_skip(instruction);
+        }
+        if after_skip {
+            let next = self.fetch(address).ok()?;
+            instructions.push((address, next));
+        }
+        let mut length = instructions.len() - after_skip as usize;
+        while length > 0 {
+            // a final skip keeps the instruction after it
+            let skips = is_skip(instructions[length - 1].1) as usize;
+            let mut block = Block {
+                start,
+                instructions: instructions[..length + skips].to_vec(),
+                length,
+                platform: self.platform,
+                quirks: self.quirks,
+                module: Vec::new(),
+            };
+            block.module = self.translate(&block);
+            if block.module.len() < MAX_MODULE_SIZE {
+                return Some(block);
+            }
+            length -= 1;
+        }
+        None
+    }
+
+    /// Whether the block still does what the program would do, the instructions it was
+    /// made from must not have been written over and the quirks must be the same
+    pub fn is_current(&self, block: &Block) -> bool {
+        block.platform == self.platform
+            && block.quirks == self.quirks
+            && block
+                .instructions
+                .iter()
+                .all(|&(address, instruction)| self.decoded[address as usize] == Some(instruction))
+    }
+
+    fn can_compile(&self, address: u16, instruction: Instruction) -> bool {
+        match instruction {
+            Instruction::LoadByte { .. }
+            | Instruction::AddByte { .. }
+            | Instruction::Load { .. }
+            | Instruction::Or { .. }
+            | Instruction::And { .. }
+            | Instruction::Xor { .. }
+            | Instruction::Add { .. }
+            | Instruction::Sub { .. }
+            | Instruction::ShiftRight { .. }
+            | Instruction::SubReverse { .. }
+            | Instruction::ShiftLeft { .. }
+            | Instruction::SkipIfEqualByte { .. }
+            | Instruction::SkipIfNotEqualByte { .. }
+            | Instruction::SkipIfEqual { .. }
+            | Instruction::SkipIfNotEqual { .. }
+            | Instruction::LoadIndex { .. }
+            | Instruction::AddIndex { .. }
+            | Instruction::LoadFont { .. }
+            | Instruction::LoadBigFont { .. }
+            | Instruction::LoadRegisters { .. }
+            | Instruction::Jump { .. }
+            | Instruction::JumpOffset { .. } => true,
+            // the address it loads has to be in memory
+            Instruction::LoadLongIndex => (address as usize + 3) < self.platform.memory_size(),
+            _ => false,
+        }
+    }
+
+    fn translate(&self, block: &Block) -> Vec<u8> {
+        let mut code = Code::default();
+        code.local_get(INDEX).load16(0).local_set(I);
+        code.loop_();
+        let compiled = &block.instructions[..block.length];
+        for (position, &(address, instruction)) in compiled.iter().enumerate() {
+            let follows_skip = position > 0 && is_skip(compiled[position - 1].1);
+            // the loop is one level further out inside the check of the skip
+            let depth = if follows_skip {
+                code.local_get(SKIP).if_();
+                code.i32_const(0).local_set(SKIP);
+                code.else_();
+                1
+            } else {
+                0
+            };
+            // out of instructions for now
+            code.local_get(COUNT).local_get(BUDGET).ge_u().if_();
+            exit(&mut code, address);
+            code.end();
+            self.translate_guard(&mut code, address, instruction);
+            code.local_get(COUNT).i32_const(1).add().local_set(COUNT);
+            self.translate_instruction(&mut code, block, address, instruction, depth);
+            if follows_skip {
+                code.end();
+            }
+        }
+        let &(last_address, last) = compiled.last().expect("A block has instructions");
+        let end = last_address.wrapping_add(last.size());
+        if is_skip(last) {
+            let &(_, skipped) = block.instructions.last().expect("The skipped instruction");
+            code.local_get(SKIP).if_();
+            exit(&mut code, end.wrapping_add(skipped.size()));
+            code.end();
+        }
+        exit(&mut code, end);
+        code.end();
+        // every path returns from inside the loop
+        code.unreachable();
+        wasm::module(PARAMETERS, LOCALS, code)
+    }
+
+    /// Leaves the block before an instruction that would fail, so the interpreter reports it
+    fn translate_guard(&self, code: &mut Code, address: u16, instruction: Instruction) {
+        if let Instruction::LoadRegisters { x } = instruction {
+            code.local_get(I).i32_const(x as i32 + 1).add();
+            code.i32_const(self.platform.memory_size() as i32)
+                .gt_u()
+                .if_();
+            exit(code, address);
+            code.end();
+        }
+    }
+
+    fn translate_instruction(
+        &self,
+        code: &mut Code,
+        block: &Block,
+        address: u16,
+        instruction: Instruction,
+        depth: u32,
+    ) {
+        let quirks = block.quirks;
+        match instruction {
+            Instruction::LoadByte { x, byte } => {
+                store_register(code, x, |code| {
+                    code.i32_const(byte as i32);
+                });
+            }
+            Instruction::AddByte { x, byte } => {
+                store_register(code, x, |code| {
+                    load_register(code, x).i32_const(byte as i32).add();
+                });
+            }
+            Instruction::Load { x, y } => {
+                store_register(code, x, |code| {
+                    load_register(code, y);
+                });
+            }
+            Instruction::Or { x, y } | Instruction::And { x, y } | Instruction::Xor { x, y } => {
+                store_register(code, x, |code| {
+                    load_register(code, x);
+                    load_register(code, y);
+                    match instruction {
+                        Instruction::Or { .. } => code.or(),
+                        Instruction::And { .. } => code.and(),
+                        _ => code.xor(),
+                    };
+                });
+                if quirks.vf_reset {
+                    store_register(code, 0xF, |code| {
+                        code.i32_const(0);
+                    });
+                }
+            }
+            Instruction::Add { x, y } => {
+                load_register(code, x);
+                load_register(code, y);
+                code.add().local_set(VALUE);
+                store_register(code, x, |code| {
+                    code.local_get(VALUE);
+                });
+                store_register(code, 0xF, |code| {
+                    code.local_get(VALUE).i32_const(8).shr_u();
+                });
+            }
+            Instruction::Sub { x, y } | Instruction::SubReverse { x, y } => {
+                let (left, right) = match instruction {
+                    Instruction::Sub { .. } => (x, y),
+                    _ => (y, x),
+                };
+                // the flag is taken before VX changes
+                load_register(code, left);
+                load_register(code, right);
+                code.ge_u().local_set(VALUE);
+                store_register(code, x, |code| {
+                    load_register(code, left);
+                    load_register(code, right);
+                    code.sub();
+                });
+                store_register(code, 0xF, |code| {
+                    code.local_get(VALUE);
+                });
+            }
+            Instruction::ShiftRight { x, y } | Instruction::ShiftLeft { x, y } => {
+                let source = if quirks.shifting { x } else { y };
+                load_register(code, source).local_set(VALUE);
+                let right = matches!(instruction, Instruction::ShiftRight { .. });
+                store_register(code, x, |code| {
+                    code.local_get(VALUE).i32_const(1);
+                    if right {
+                        code.shr_u();
+                    } else {
+                        code.shl();
+                    }
+                });
+                store_register(code, 0xF, |code| {
+                    if right {
+                        code.local_get(VALUE).i32_const(1).and();
+                    } else {
+                        code.local_get(VALUE).i32_const(7).shr_u();
+                    }
+                });
+            }
+            Instruction::SkipIfEqualByte { x, byte }
+            | Instruction::SkipIfNotEqualByte { x, byte } => {
+                load_register(code, x).i32_const(byte as i32);
+                if matches!(instruction, Instruction::SkipIfEqualByte { .. }) {
+                    code.eq();
+                } else {
+                    code.ne();
+                }
+                code.local_set(SKIP);
+            }
+            Instruction::SkipIfEqual { x, y } | Instruction::SkipIfNotEqual { x, y } => {
+                load_register(code, x);
+                load_register(code, y);
+                if matches!(instruction, Instruction::SkipIfEqual { .. }) {
+                    code.eq();
+                } else {
+                    code.ne();
+                }
+                code.local_set(SKIP);
+            }
+            Instruction::LoadIndex { address } => {
+                code.i32_const(address as i32).local_set(I);
+            }
+            Instruction::AddIndex { x } => {
+                code.local_get(I);
+                load_register(code, x)
+                    .add()
+                    .i32_const(0xFFFF)
+                    .and()
+                    .local_set(I);
+                // like the interpreter, only when I can go past the memory
+                if self.platform.memory_size() <= 0xFFFF {
+                    let end = self.platform.memory_size() as i32;
+                    code.local_get(I).i32_const(end).ge_u().if_();
+                    store_register(code, 0xF, |code| {
+                        code.i32_const(1);
+                    });
+                    code.end();
+                }
+            }
+            Instruction::LoadFont { x } | Instruction::LoadBigFont { x } => {
+                let (start, size) = match instruction {
+                    Instruction::LoadFont { .. } => (Self::FONT_START_ADDR, 5),
+                    _ => (Self::BIG_FONT_START_ADDR, 10),
+                };
+                load_register(code, x)
+                    .i32_const(0xF)
+                    .and()
+                    .i32_const(size)
+                    .mul();
+                code.i32_const(start as i32).add().local_set(I);
+            }
+            Instruction::LoadRegisters { x } => {
+                for register in 0..=x {
+                    store_register(code, register, |code| {
+                        code.local_get(MEMORY)
+                            .local_get(I)
+                            .add()
+                            .load8(register as u32);
+                    });
+                }
+                if quirks.memory_increment {
+                    code.local_get(I).i32_const(x as i32 + 1).add();
+                    code.i32_const(0xFFFF).and().local_set(I);
+                }
+            }
+            Instruction::LoadLongIndex => {
+                // the address is the next 16 bits, big endian
+                code.local_get(MEMORY)
+                    .load8(address as u32 + 2)
+                    .i32_const(8)
+                    .shl();
+                code.local_get(MEMORY)
+                    .load8(address as u32 + 3)
+                    .or()
+                    .local_set(I);
+            }
+            Instruction::Jump { address } if address == block.start => {
+                code.br(depth);
+            }
+            Instruction::Jump { address } => exit(code, address),
+            Instruction::JumpOffset { address } => {
+                // with the jumping quirk, BXNN uses VX instead of V0
+                let offset_register = if quirks.jumping {
+                    (address >> 8) as u8
+                } else {
+                    0
+                };
+                code.local_get(PROGRAM_COUNTER);
+                load_register(code, offset_register)
+                    .i32_const(address as i32)
+                    .add();
+                code.store16(0);
+                leave(code);
+            }
+            _ => unreachable!("{instruction} at {address:#06x} can't be compiled"),
+        }
+    }
+}
+
+fn load_register(code: &mut Code, register: u8) -> &mut Code {
+    code.local_get(REGISTERS).load8(register as u32)
+}
+
+/// Stores the value that `value` leaves on the stack in the register, only its low byte
+fn store_register(code: &mut Code, register: u8, value: impl FnOnce(&mut Code)) {
+    code.local_get(REGISTERS);
+    value(code);
+    code.store8(register as u32);
+}
+
+/// Stores I and the program counter and returns how many instructions ran
+fn exit(code: &mut Code, program_counter: u16) {
+    code.local_get(PROGRAM_COUNTER)
+        .i32_const(program_counter as i32)
+        .store16(0);
+    leave(code);
+}
+
+/// Returns after the program counter was stored
+fn leave(code: &mut Code) {
+    code.local_get(INDEX).local_get(I).store16(0);
+    code.local_get(COUNT).return_();
+}
+
+fn is_skip(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::SkipIfEqualByte { .. }
+            | Instruction::SkipIfNotEqualByte { .. }
+            | Instruction::SkipIfEqual { .. }
+            | Instruction::SkipIfNotEqual { .. }
+    )
+}
+
+/// The instructions that always change the program counter
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Jump { .. } | Instruction::JumpOffset { .. }
+    )
+}
@@ -0,0 +1,212 @@
+//! Just enough of the WebAssembly binary format for the blocks: one function that works
+//! on the memory it imports.
+//!
+//! https://webassembly.github.io/spec/core/binary/index.html
+
+const MAGIC: &[u8] = b"\0asm";
+const VERSION: &[u8] = &[1, 0, 0, 0];
+const I32: u8 = 0x7F;
+const FUNCTION_TYPE: u8 = 0x60;
+// the type of a block that takes and leaves nothing on the stack
+const EMPTY: u8 = 0x40;
+
+/// The body of a function, one method per instruction
+#[derive(Debug, Default)]
+pub(super) struct Code {
+    bytes: Vec<u8>,
+}
+
+impl Code {
+    fn op(&mut self, op: u8) -> &mut Self {
+        self.bytes.push(op);
+        self
+    }
+
+    fn memory_op(&mut self, op: u8, offset: u32) -> &mut Self {
+        // the registers are bytes and the 16 bit fields are only 2 aligned, so no alignment
+        self.op(op).unsigned(0).unsigned(offset)
+    }
+
+    fn unsigned(&mut self, mut value: u32) -> &mut Self {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.bytes.push(byte);
+                return self;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    fn signed(&mut self, mut value: i32) -> &mut Self {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+            if done {
+                self.bytes.push(byte);
+                return self;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    pub fn unreachable(&mut self) -> &mut Self {
+        self.op(0x00)
+    }
+
+    pub fn loop_(&mut self) -> &mut Self {
+        self.op(0x03).op(EMPTY)
+    }
+
+    pub fn if_(&mut self) -> &mut Self {
+        self.op(0x04).op(EMPTY)
+    }
+
+    pub fn else_(&mut self) -> &mut Self {
+        self.op(0x05)
+    }
+
+    pub fn end(&mut self) -> &mut Self {
+        self.op(0x0B)
+    }
+
+    /// Jumps to the start of the loop that is `depth` levels out
+    pub fn br(&mut self, depth: u32) -> &mut Self {
+        self.op(0x0C).unsigned(depth)
+    }
+
+    pub fn return_(&mut self) -> &mut Self {
+        self.op(0x0F)
+    }
+
+    pub fn local_get(&mut self, local: u32) -> &mut Self {
+        self.op(0x20).unsigned(local)
+    }
+
+    pub fn local_set(&mut self, local: u32) -> &mut Self {
+        self.op(0x21).unsigned(local)
+    }
+
+    pub fn load8(&mut self, offset: u32) -> &mut Self {
+        self.memory_op(0x2D, offset)
+    }
+
+    pub fn load16(&mut self, offset: u32) -> &mut Self {
+        self.memory_op(0x2F, offset)
+    }
+
+    pub fn store8(&mut self, offset: u32) -> &mut Self {
+        self.memory_op(0x3A, offset)
+    }
+
+    pub fn store16(&mut self, offset: u32) -> &mut Self {
+        self.memory_op(0x3B, offset)
+    }
+
+    pub fn i32_const(&mut self, value: i32) -> &mut Self {
+        self.op(0x41).signed(value)
+    }
+
+    pub fn eq(&mut self) -> &mut Self {
+        self.op(0x46)
+    }
+
+    pub fn ne(&mut self) -> &mut Self {
+        self.op(0x47)
+    }
+
+    pub fn gt_u(&mut self) -> &mut Self {
+        self.op(0x4B)
+    }
+
+    pub fn ge_u(&mut self) -> &mut Self {
+        self.op(0x4F)
+    }
+
+    pub fn add(&mut self) -> &mut Self {
+        self.op(0x6A)
+    }
+
+    pub fn sub(&mut self) -> &mut Self {
+        self.op(0x6B)
+    }
+
+    pub fn mul(&mut self) -> &mut Self {
+        self.op(0x6C)
+    }
+
+    pub fn and(&mut self) -> &mut Self {
+        self.op(0x71)
+    }
+
+    pub fn or(&mut self) -> &mut Self {
+        self.op(0x72)
+    }
+
+    pub fn xor(&mut self) -> &mut Self {
+        self.op(0x73)
+    }
+
+    pub fn shl(&mut self) -> &mut Self {
+        self.op(0x74)
+    }
+
+    pub fn shr_u(&mut self) -> &mut Self {
+        self.op(0x76)
+    }
+}
+
+/// A module that imports `env.memory` and exports its only function as `run`. The function
+/// takes `parameters` i32s, returns one and has `locals` more.
+pub(super) fn module(parameters: u32, locals: u32, mut code: Code) -> Vec<u8> {
+    let mut types = Code::default();
+    types.unsigned(1).op(FUNCTION_TYPE).unsigned(parameters);
+    types.bytes.extend((0..parameters).map(|_| I32));
+    types.unsigned(1).op(I32);
+
+    let mut imports = Code::default();
+    imports.unsigned(1);
+    name(&mut imports, "env");
+    name(&mut imports, "memory");
+    // a memory of at least one page, without a maximum
+    imports.op(0x02).op(0x00).unsigned(1);
+
+    let mut functions = Code::default();
+    functions.unsigned(1).unsigned(0);
+
+    let mut exports = Code::default();
+    exports.unsigned(1);
+    name(&mut exports, "run");
+    exports.op(0x00).unsigned(0);
+
+    let mut body = Code::default();
+    body.unsigned(1).unsigned(locals).op(I32);
+    code.end();
+    body.bytes.extend(code.bytes);
+    let mut bodies = Code::default();
+    bodies.unsigned(1).unsigned(body.bytes.len() as u32);
+    bodies.bytes.extend(body.bytes);
+
+    let mut module = [MAGIC, VERSION].concat();
+    for (id, section) in [
+        (1, types),
+        (2, imports),
+        (3, functions),
+        (7, exports),
+        (10, bodies),
+    ] {
+        module.push(id);
+        let mut size = Code::default();
+        size.unsigned(section.bytes.len() as u32);
+        module.extend(size.bytes);
+        module.extend(section.bytes);
+    }
+    module
+}
+
+fn name(code: &mut Code, name: &str) {
+    code.unsigned(name.len() as u32);
+    code.bytes.extend(name.as_bytes());
+}
@@ -0,0 +1,320 @@
+//! Snapshots of the whole machine so a moment can be saved, shared and restored.
+//!
+//! # Format
+//!
+//! All numbers are little endian.
+//!
+//! | Size          | Field                                                            |
+//! |---------------|------------------------------------------------------------------|
+//! | 4             | magic `C8SS`                                                     |
+//! | 2             | format version, currently 1                                      |
+//! | 20            | SHA-1 hash of the loaded rom                                     |
+//! | 1             | platform: 0 CHIP-8, 1 SUPER-CHIP, 2 XO-CHIP                      |
+//! | 1             | quirks, see [`Quirks::to_bits`]                                  |
+//! | 2             | program counter                                                  |
+//! | 2             | index register                                                   |
+//! | 1             | delay timer                                                      |
+//! | 1             | sound timer                                                      |
+//! | 16            | V0 to VF                                                         |
+//! | 16            | RPL flags                                                        |
+//! | 2             | pressed keys, one bit per key                                    |
+//! | 1             | vertical blank flag                                              |
+//! | 1             | pitch                                                            |
+//! | 1             | 1 if there is an audio pattern, 0 otherwise                      |
+//! | 16            | audio pattern, all 0 if there is none                            |
+//! | 1             | call stack length `n`                                            |
+//! | 2 * n         | call stack, oldest return address first                          |
+//! | 1             | 1 for the high resolution mode, 0 otherwise                      |
+//! | 1             | selected planes                                                  |
+//! | width*height  | one byte per pixel, row by row, with the planes that are on     |
+//! | 4             | memory size `m`, 4096 or 65536 depending on the platform         |
+//! | m             | memory                                                           |
+//! | 1             | random number generator: 0 xorshift, 1 VIP                       |
+//! | 8             | seed of the random number generator                              |
+//! | 8             | state of the random number generator                             |
+//! | 1             | the key FX0A waits to be released, 0xFF if there is none         |
+//! | 4             | the cycles the last VIP frame went over                          |
+//! | 4             | the instructions of the VIP frame a breakpoint stopped           |
+//! | 1             | 1 if stopped at a breakpoint, its instruction runs next          |
+//! | 1             | 1 if the last instruction hit a watchpoint, the next one stops   |
+//! | 1             | last watchpoint access: 0 none, 1 read, 2 write, 3 execute       |
+//! | 2             | the address of the instruction that hit it                       |
+//! | 2             | its opcode                                                       |
+//! | 2             | the address of the access                                        |
+//! | 1             | the length `a` of the access                                     |
+//! | 2 * a         | the bytes before the access, then the bytes after it             |
+//!
+//! A state only loads into the rom it was saved with.
+//! New fields are only ever added at the end, with a new version.
+
+use crate::{error::SaveStateError, quirks::Quirks, rng::RngKind};
+
+use super::{Access, Platform, Program, WatchpointHit};
+
+const MAGIC: &[u8; 4] = b"C8SS";
+const SAVE_STATE_VERSION: u16 = 1;
+const NO_KEY: u8 = 0xFF;
+const NO_ACCESS: u8 = 0;
+
+impl Program {
+    /// Serializes the whole machine, see the module documentation for the format
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        state.extend_from_slice(MAGIC);
+        state.extend_from_slice(&SAVE_STATE_VERSION.to_le_bytes());
+        state.extend_from_slice(&self.rom_hash);
+        state.push(platform_to_byte(self.platform));
+        state.push(self.quirks.to_bits());
+        state.extend_from_slice(&self.program_counter.to_le_bytes());
+        state.extend_from_slice(&self.index_register.to_le_bytes());
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        state.extend_from_slice(&self.variable_regsiters);
+        state.extend_from_slice(&self.rpl_flags);
+        state.extend_from_slice(&self.pressed_keys.to_le_bytes());
+        state.push(self.vertical_blank as u8);
+        state.push(self.pitch);
+        state.push(self.audio_pattern.is_some() as u8);
+        state.extend_from_slice(&self.audio_pattern.unwrap_or_default());
+        state.push(self.call_stack.len() as u8);
+        for address in &self.call_stack {
+            state.extend_from_slice(&address.to_le_bytes());
+        }
+        state.push(self.high_resolution as u8);
+        state.push(self.selected_planes);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                state.push(self.pixel_planes(self.pixel_location(x, y)));
+            }
+        }
+        let memory = &self.memory[..self.platform.memory_size()];
+        state.extend_from_slice(&(memory.len() as u32).to_le_bytes());
+        state.extend_from_slice(memory);
+        state.push(rng_kind_to_byte(self.rng.kind()));
+        state.extend_from_slice(&self.rng.seed().to_le_bytes());
+        state.extend_from_slice(&self.rng.state().to_le_bytes());
+        state.push(self.key_to_release.unwrap_or(NO_KEY));
+        state.extend_from_slice(&self.vip_cycle_debt.to_le_bytes());
+        state.extend_from_slice(&self.vip_instructions.to_le_bytes());
+        state.push(self.at_breakpoint as u8);
+        state.push(self.watchpoint_pending as u8);
+        match &self.watchpoint_hit {
+            Some(hit) => {
+                state.push(access_to_byte(hit.access));
+                state.extend_from_slice(&hit.instruction_address.to_le_bytes());
+                state.extend_from_slice(&hit.opcode.to_le_bytes());
+                state.extend_from_slice(&hit.address.to_le_bytes());
+                state.push(hit.new.len() as u8);
+                state.extend_from_slice(&hit.old);
+                state.extend_from_slice(&hit.new);
+            }
+            None => state.push(NO_ACCESS),
+        }
+        state
+    }
+
+    /// Restores a state made by [`Program::save_state`].
+    /// The program is only changed if the whole state is valid.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), SaveStateError> {
+        let mut reader = Reader { state };
+        if reader.bytes(4)? != MAGIC {
+            return Err(SaveStateError::NotASaveState);
+        }
+        let version = reader.u16()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        let rom_hash: [u8; 20] = reader.array()?;
+        if rom_hash != self.rom_hash {
+            return Err(SaveStateError::OtherRom);
+        }
+        let platform = platform_from_byte(reader.u8()?)?;
+        let quirks = Quirks::from_bits(reader.u8()?);
+        let program_counter = reader.u16()?;
+        let index_register = reader.u16()?;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let variable_regsiters = reader.array()?;
+        let rpl_flags = reader.array()?;
+        let pressed_keys = reader.u16()?;
+        let vertical_blank = reader.u8()? == 1;
+        let pitch = reader.u8()?;
+        let has_audio_pattern = reader.u8()? == 1;
+        let audio_pattern: [u8; 16] = reader.array()?;
+        let call_stack_length = reader.u8()? as usize;
+        if call_stack_length > Self::STACK_SIZE {
+            return Err(SaveStateError::Invalid("the call stack is too deep"));
+        }
+        let call_stack = (0..call_stack_length)
+            .map(|_| reader.u16())
+            .collect::<Result<Vec<u16>, _>>()?;
+        let high_resolution = reader.u8()? == 1;
+        let selected_planes = reader.u8()? & 0b11;
+        let (width, height) = if high_resolution {
+            (super::HIRES_DISPLAY_WIDTH, super::HIRES_DISPLAY_HEIGHT)
+        } else {
+            (super::DISPLAY_WIDTH, super::DISPLAY_HEIGHT)
+        };
+        let pixels = reader.bytes(width as usize * height as usize)?;
+        let memory_size = reader.u32()? as usize;
+        if memory_size != platform.memory_size() {
+            return Err(SaveStateError::Invalid(
+                "the memory size doesn't match the platform",
+            ));
+        }
+        let memory = reader.bytes(memory_size)?;
+        let rng_kind = rng_kind_from_byte(reader.u8()?)?;
+        let rng_seed = reader.u64()?;
+        let rng_state = reader.u64()?;
+        let key_to_release = match reader.u8()? {
+            NO_KEY => None,
+            key @ 0..=0xF => Some(key),
+            _ => return Err(SaveStateError::Invalid("unknown key")),
+        };
+        let vip_cycle_debt = reader.u32()?;
+        let vip_instructions = reader.u32()?;
+        let at_breakpoint = reader.u8()? == 1;
+        let watchpoint_pending = reader.u8()? == 1;
+        let watchpoint_hit = match reader.u8()? {
+            NO_ACCESS => None,
+            access => {
+                let access = access_from_byte(access)?;
+                let instruction_address = reader.u16()?;
+                let opcode = reader.u16()?;
+                let address = reader.u16()?;
+                let length = reader.u8()? as usize;
+                Some(WatchpointHit {
+                    access,
+                    instruction_address,
+                    opcode,
+                    address,
+                    old: reader.bytes(length)?.to_vec(),
+                    new: reader.bytes(length)?.to_vec(),
+                })
+            }
+        };
+        if watchpoint_pending && watchpoint_hit.is_none() {
+            return Err(SaveStateError::Invalid("a watchpoint stops without a hit"));
+        }
+
+        // everything was read, so it's safe to change the program
+        self.set_platform(platform);
+        self.quirks = quirks;
+        self.program_counter = program_counter;
+        self.index_register = index_register;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.variable_regsiters = variable_regsiters;
+        self.rpl_flags = rpl_flags;
+        self.pressed_keys = pressed_keys;
+        self.vertical_blank = vertical_blank;
+        self.pitch = pitch;
+        self.audio_pattern = has_audio_pattern.then_some(audio_pattern);
+        self.host.set_audio_pattern(self.audio_pattern, self.pitch);
+        self.call_stack = call_stack;
+        self.high_resolution = high_resolution;
+        self.selected_planes = selected_planes;
+        self.clear_display();
+        for (i, &planes) in pixels.iter().enumerate() {
+            let location =
+                self.pixel_location((i % width as usize) as u8, (i / width as usize) as u8);
+            self.set_pixel_planes(location, planes & 0b11);
+        }
+        self.write_memory(0, memory);
+        self.rng.restore(rng_kind, rng_seed, rng_state);
+        self.key_to_release = key_to_release;
+        self.vip_cycle_debt = vip_cycle_debt;
+        self.vip_instructions = vip_instructions;
+        self.at_breakpoint = at_breakpoint;
+        self.watchpoint_pending = watchpoint_pending;
+        self.watchpoint_hit = watchpoint_hit;
+        // the frame starts again from the state
+        self.frame_progress = 0;
+        Ok(())
+    }
+}
+
+fn platform_to_byte(platform: Platform) -> u8 {
+    match platform {
+        Platform::Chip8 => 0,
+        Platform::SuperChip => 1,
+        Platform::XoChip => 2,
+    }
+}
+
+fn platform_from_byte(byte: u8) -> Result<Platform, SaveStateError> {
+    match byte {
+        0 => Ok(Platform::Chip8),
+        1 => Ok(Platform::SuperChip),
+        2 => Ok(Platform::XoChip),
+        _ => Err(SaveStateError::Invalid("unknown platform")),
+    }
+}
+
+fn rng_kind_to_byte(kind: RngKind) -> u8 {
+    match kind {
+        RngKind::Xorshift => 0,
+        RngKind::Vip => 1,
+    }
+}
+
+fn rng_kind_from_byte(byte: u8) -> Result<RngKind, SaveStateError> {
+    match byte {
+        0 => Ok(RngKind::Xorshift),
+        1 => Ok(RngKind::Vip),
+        _ => Err(SaveStateError::Invalid("unknown random number generator")),
+    }
+}
+
+fn access_to_byte(access: Access) -> u8 {
+    match access {
+        Access::Read => 1,
+        Access::Write => 2,
+        Access::Execute => 3,
+    }
+}
+
+fn access_from_byte(byte: u8) -> Result<Access, SaveStateError> {
+    match byte {
+        1 => Ok(Access::Read),
+        2 => Ok(Access::Write),
+        3 => Ok(Access::Execute),
+        _ => Err(SaveStateError::Invalid("unknown memory access")),
+    }
+}
+
+/// Reads the state from the start, failing if it's too short
+struct Reader<'a> {
+    state: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.state.len() < length {
+            return Err(SaveStateError::Truncated);
+        }
+        let (bytes, rest) = self.state.split_at(length);
+        self.state = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        Ok(self.bytes(N)?.try_into().expect("the length was checked"))
+    }
+
+    fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
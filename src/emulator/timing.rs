@@ -0,0 +1,154 @@
+//! How long instructions take on the COSMAC VIP.
+//!
+//! The VIP runs the interpreter on a CDP1802 at 1.7609 MHz, 8 clock cycles per machine
+//! cycle. The CDP1861 video chip draws 262 lines of 14 machine cycles, so there are 3668
+//! machine cycles in a 60 Hz frame. The display takes 1832 of them: the interrupt routine
+//! stays busy for the 128 lines that show the display, because it points the video chip back
+//! at the same 8 bytes for the 4 lines of every row, and it decrements the timers.
+//!
+//! The costs are in machine cycles. Every instruction pays [`FETCH_CYCLES`] for the
+//! interpreter loop that fetches and decodes it, then the cost of the routine that runs it.
+//! The routine costs are the published timings of the original interpreter, the loops of
+//! the clear screen, draw, FX33, FX55 and FX65 routines are counted per iteration.
+
+use crate::{error::EmulatorError, instruction::Instruction};
+
+use super::Program;
+
+const CYCLES_PER_FRAME: u32 = 262 * 14;
+/// The 128 lines that show the display, and the rest of the interrupt routine
+const DISPLAY_CYCLES: u32 = 128 * 14 + 40;
+/// The interpreter loop, paid by every instruction before its routine runs
+pub const FETCH_CYCLES: u32 = 40;
+/// The cycles the interpreter can use in a frame
+pub const VIP_CYCLES_PER_FRAME: u32 = CYCLES_PER_FRAME - DISPLAY_CYCLES;
+
+/// How the instructions of a frame are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timing {
+    /// The same number of instructions in every frame
+    #[default]
+    Fixed,
+    /// Every instruction costs what it did on the COSMAC VIP, and sprites are only drawn
+    /// at the start of a frame
+    Vip,
+}
+
+impl Timing {
+    pub const ALL: [Timing; 2] = [Timing::Fixed, Timing::Vip];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Timing::Fixed => "fixed",
+            Timing::Vip => "vip",
+        }
+    }
+}
+
+impl std::fmt::Display for Timing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Timing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Timing::ALL
+            .into_iter()
+            .find(|timing| timing.name() == s)
+            .ok_or_else(|| format!("There is no timing named '{s}'"))
+    }
+}
+
+impl Program {
+    /// Runs the instructions that fit in one frame of the COSMAC VIP, without ticking the
+    /// timers. A sprite draw waits for the next frame, like the VIP waits for the display
+    /// interrupt before drawing. Returns how many instructions ran.
+    ///
+    /// A breakpoint stops the frame in the middle, the next call finishes it.
+    pub fn run_vip_frame(&mut self) -> Result<u32, EmulatorError> {
+        // an instruction that didn't finish in the last frame takes cycles from this one
+        let mut cycles = self.vip_cycle_debt;
+        let mut instructions = std::mem::take(&mut self.vip_instructions);
+        while cycles < VIP_CYCLES_PER_FRAME {
+            let instruction = self.fetch(self.program_counter)?;
+            let is_draw = matches!(instruction, Instruction::Draw { .. });
+            if is_draw && !self.vertical_blank {
+                // the rest of the frame is spent waiting
+                cycles = VIP_CYCLES_PER_FRAME;
+                break;
+            }
+            if let Err(e) = self.check_breakpoint() {
+                self.vip_cycle_debt = cycles;
+                self.vip_instructions = instructions;
+                return Err(e);
+            }
+            cycles += FETCH_CYCLES + self.vip_cycles(instruction);
+            self.step()?;
+            instructions += 1;
+            if is_draw {
+                // only one sprite per frame, even without the display wait quirk
+                self.vertical_blank = false;
+            }
+        }
+        self.vip_cycle_debt = cycles - VIP_CYCLES_PER_FRAME;
+        Ok(instructions)
+    }
+
+    /// The cost of the routine of the instruction with the current registers, without the
+    /// fetch and decode
+    fn vip_cycles(&self, instruction: Instruction) -> u32 {
+        let register = |x: u8| self.variable_regsiters[x as usize];
+        // the skips take a little longer when they skip
+        let skip = |taken: bool, cycles: u32| if taken { cycles + 4 } else { cycles };
+        match instruction {
+            // the loop clears the 256 bytes of the display one at a time
+            Instruction::ClearScreen => 6 + 256 * 12,
+            Instruction::Return => 10,
+            Instruction::Jump { .. } => 12,
+            Instruction::Call { .. } => 26,
+            Instruction::SkipIfEqualByte { x, byte } => skip(register(x) == byte, 10),
+            Instruction::SkipIfNotEqualByte { x, byte } => skip(register(x) != byte, 10),
+            Instruction::SkipIfEqual { x, y } => skip(register(x) == register(y), 14),
+            Instruction::LoadByte { .. } => 6,
+            Instruction::AddByte { .. } => 10,
+            Instruction::Load { .. }
+            | Instruction::Or { .. }
+            | Instruction::And { .. }
+            | Instruction::Xor { .. }
+            | Instruction::Add { .. }
+            | Instruction::Sub { .. }
+            | Instruction::ShiftRight { .. }
+            | Instruction::SubReverse { .. }
+            | Instruction::ShiftLeft { .. } => 44,
+            Instruction::SkipIfNotEqual { x, y } => skip(register(x) != register(y), 14),
+            Instruction::LoadIndex { .. } => 12,
+            Instruction::JumpOffset { .. } => 22,
+            Instruction::Random { .. } => 36,
+            Instruction::Draw { x, rows, .. } => {
+                // every row is shifted to the pixel it starts on, one bit at a time
+                let shift = (register(x) % 8) as u32;
+                26 + rows as u32 * (12 + 2 * shift)
+            }
+            Instruction::SkipIfPressed { x } => skip(self.key_is_pressed(register(x) & 0xF), 14),
+            Instruction::SkipIfNotPressed { x } => {
+                skip(!self.key_is_pressed(register(x) & 0xF), 14)
+            }
+            Instruction::AddIndex { .. } => 16,
+            Instruction::LoadFont { .. } => 16,
+            Instruction::StoreDigits { x } => {
+                // the digits are found by subtracting, once per unit
+                let vx = register(x);
+                40 + 16 * (vx / 100 + vx / 10 % 10 + vx % 10) as u32
+            }
+            // the loop copies one register at a time
+            Instruction::StoreRegisters { x } | Instruction::LoadRegisters { x } => {
+                18 + 14 * (x as u32 + 1)
+            }
+            // the timers, and the instructions the VIP never had
+            _ => 10,
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt::Display;
+
+use crate::emulator::Access;
+
+/// Everything that can stop the emulator.
+/// The program counter is the address of the instruction that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmulatorError {
+    /// 00EE with nothing on the call stack
+    StackUnderflow { program_counter: u16, opcode: u16 },
+    /// 2NNN with a full call stack
+    StackOverflow { program_counter: u16, opcode: u16 },
+    /// The next instruction can't be fetched because it's outside of memory
+    PcOutOfBounds { program_counter: u16 },
+    /// The instruction reads or writes outside of memory
+    AddressOutOfBounds {
+        program_counter: u16,
+        opcode: u16,
+        address: usize,
+    },
+    /// The instruction doesn't exist, or doesn't exist on the current platform
+    UnknownOpcode { program_counter: u16, opcode: u16 },
+    /// The rom doesn't fit in the memory after the start address
+    RomTooLarge { size: usize, max_size: usize },
+    /// The next instruction is at a breakpoint, it runs when the emulator goes on
+    Breakpoint { program_counter: u16 },
+    /// The instruction at the program counter used memory that a watchpoint watches, the
+    /// program stopped after it
+    Watchpoint {
+        program_counter: u16,
+        address: u16,
+        access: Access,
+    },
+}
+
+impl Display for EmulatorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            EmulatorError::StackUnderflow {
+                program_counter,
+                opcode,
+            } => write!(
+                f,
+                "Returned without a return address at {program_counter:#05x} ({opcode:#06x})"
+            ),
+            EmulatorError::StackOverflow {
+                program_counter,
+                opcode,
+            } => write!(
+                f,
+                "The call stack is full at {program_counter:#05x} ({opcode:#06x})"
+            ),
+            EmulatorError::PcOutOfBounds { program_counter } => write!(
+                f,
+                "The program counter {program_counter:#05x} is outside of memory"
+            ),
+            EmulatorError::AddressOutOfBounds {
+                program_counter,
+                opcode,
+                address,
+            } => write!(
+                f,
+                "The address {address:#05x} is outside of memory at {program_counter:#05x} ({opcode:#06x})"
+            ),
+            EmulatorError::UnknownOpcode {
+                program_counter,
+                opcode,
+            } => write!(
+                f,
+                "Unknown instruction {opcode:#06x} at {program_counter:#05x}"
+            ),
+            EmulatorError::RomTooLarge { size, max_size } => write!(
+                f,
+                "The rom is {size} bytes but only {max_size} bytes fit in memory"
+            ),
+            EmulatorError::Breakpoint { program_counter } => {
+                write!(f, "Stopped at the breakpoint at {program_counter:#05x}")
+            }
+            EmulatorError::Watchpoint {
+                program_counter,
+                address,
+                access,
+            } => match access {
+                Access::Read => write!(
+                    f,
+                    "The instruction at {program_counter:#05x} read the watched memory at {address:#05x}"
+                ),
+                Access::Write => write!(
+                    f,
+                    "The instruction at {program_counter:#05x} wrote the watched memory at {address:#05x}"
+                ),
+                Access::Execute => write!(
+                    f,
+                    "Stopped at the watched instruction at {program_counter:#05x}"
+                ),
+            },
+        }
+    }
+}
+
+impl std::error::Error for EmulatorError {}
+
+/// The opcode isn't an instruction of any platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownOpcode(pub u16);
+
+impl Display for UnknownOpcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown instruction {:#06x}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownOpcode {}
+
+/// Why an Octo program couldn't be assembled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AssemblyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+/// Why a debugger expression couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpressionError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for ExpressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+/// Why a save state couldn't be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveStateError {
+    /// The file doesn't start with the save state magic
+    NotASaveState,
+    /// The state was made by a newer version of the emulator
+    UnsupportedVersion(u16),
+    /// The state was saved with another rom than the loaded one
+    OtherRom,
+    /// The state ended before all the fields were read
+    Truncated,
+    /// A field has a value that can't be restored
+    Invalid(&'static str),
+}
+
+impl Display for SaveStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveStateError::NotASaveState => write!(f, "This file is not a save state"),
+            SaveStateError::UnsupportedVersion(version) => {
+                write!(f, "Version {version} save states are not supported")
+            }
+            SaveStateError::OtherRom => {
+                write!(
+                    f,
+                    "The state was saved with another rom, load that rom first"
+                )
+            }
+            SaveStateError::Truncated => write!(f, "The save state is incomplete"),
+            SaveStateError::Invalid(reason) => write!(f, "The save state is invalid: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+/// Why a movie couldn't be played
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieError {
+    /// The file doesn't start with the movie magic
+    NotAMovie,
+    /// The movie was made by a newer version of the emulator
+    UnsupportedVersion(u16),
+    /// The movie was recorded with another rom than the loaded one
+    OtherRom,
+    /// The movie ended before all the frames were read
+    Truncated,
+    /// A field has a value that can't be played
+    Invalid(&'static str),
+    /// The state the movie starts from can't be loaded
+    State(SaveStateError),
+}
+
+impl Display for MovieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MovieError::NotAMovie => write!(f, "This file is not a movie"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "Version {version} movies are not supported")
+            }
+            MovieError::OtherRom => {
+                write!(
+                    f,
+                    "The movie was recorded with another rom, load that rom first"
+                )
+            }
+            MovieError::Truncated => write!(f, "The movie is incomplete"),
+            MovieError::Invalid(reason) => write!(f, "The movie is invalid: {reason}"),
+            MovieError::State(e) => write!(f, "The movie can't start: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
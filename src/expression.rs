@@ -0,0 +1,332 @@
+//! The expressions of the debugger, for the conditions of the breakpoints and the watches:
+//!
+//! ```text
+//! V3 == 0x10 && I > 0x300
+//! mem[I + 2] != 0
+//! DT == 0 || stack.len() > 5
+//! ```
+//!
+//! The values are the registers `V0` to `VF`, `I`, `PC`, the timers `DT` and `ST`, the bytes
+//! of memory `mem[address]`, the return addresses `stack[n]` and `stack.len()`, and numbers
+//! in decimal, `0x` hexadecimal or `0b` binary. The operators are the ones of Rust with the
+//! same precedence, comparisons and `&&`, `||` and `!` give 1 for true and 0 for false.
+
+use std::fmt::Display;
+
+use crate::{emulator::Program, error::ExpressionError};
+
+/// A parsed expression, it keeps its source to be shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    source: String,
+    node: Node,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    Register(u8),
+    Index,
+    ProgramCounter,
+    DelayTimer,
+    SoundTimer,
+    StackLength,
+    Memory(Box<Node>),
+    Stack(Box<Node>),
+    Unary(char, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+}
+
+// from the loosest to the tightest
+const PRECEDENCE: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<", "<=", ">", ">="],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+// the longest first so `<=` isn't read as `<`
+const SYMBOLS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "|", "^", "&", "+", "-", "*", "/",
+    "%", "!", "~", "(", ")", "[", "]", ".",
+];
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Expression, ExpressionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            end: source.chars().count() + 1,
+        };
+        let node = parser.binary(0)?;
+        if let Some(token) = tokens.get(parser.position) {
+            return Err(ExpressionError {
+                column: token.column,
+                message: format!("Unexpected '{}'", token.text),
+            });
+        }
+        Ok(Expression {
+            source: source.trim().to_string(),
+            node,
+        })
+    }
+
+    /// The value with the state of the program, or why it has none
+    pub fn evaluate(&self, program: &Program) -> Result<i64, String> {
+        evaluate(&self.node, program)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    // counted in characters from 1
+    column: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExpressionError> {
+    let characters: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < characters.len() {
+        let column = i + 1;
+        let character = characters[i];
+        if character.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let length = if character.is_ascii_alphanumeric() || character == '_' {
+            characters[i..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .count()
+        } else {
+            let rest: String = characters[i..].iter().take(2).collect();
+            match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                Some(symbol) => symbol.len(),
+                None => {
+                    return Err(ExpressionError {
+                        column,
+                        message: format!("Unexpected '{character}'"),
+                    })
+                }
+            }
+        };
+        tokens.push(Token {
+            text: characters[i..i + length].iter().collect(),
+            column,
+        });
+        i += length;
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    // the column after the source, for the errors at the end
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens
+            .get(self.position)
+            .map(|token| token.text.as_str())
+    }
+
+    fn error(&self, message: impl Into<String>) -> ExpressionError {
+        let column = self
+            .tokens
+            .get(self.position)
+            .map_or(self.end, |token| token.column);
+        ExpressionError {
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), ExpressionError> {
+        if self.peek() != Some(text) {
+            return Err(self.error(format!("Expected '{text}'")));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    /// The operators of a precedence level are left associative
+    fn binary(&mut self, level: usize) -> Result<Node, ExpressionError> {
+        let Some(operators) = PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(&operator) = operators
+            .iter()
+            .find(|&&operator| self.peek() == Some(operator))
+        {
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = Node::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        match self.peek() {
+            Some(operator @ ("-" | "!" | "~")) => {
+                let operator = operator.chars().next().unwrap();
+                self.position += 1;
+                Ok(Node::Unary(operator, Box::new(self.unary()?)))
+            }
+            _ => self.value(),
+        }
+    }
+
+    fn value(&mut self) -> Result<Node, ExpressionError> {
+        let Some(token) = self.tokens.get(self.position) else {
+            return Err(self.error("Expected a value"));
+        };
+        let text = token.text.to_lowercase();
+        if text == "(" {
+            self.position += 1;
+            let node = self.binary(0)?;
+            self.expect(")")?;
+            return Ok(node);
+        }
+        if let Some(number) = parse_number(&text) {
+            self.position += 1;
+            return Ok(Node::Number(number));
+        }
+        let node = match text.as_str() {
+            "i" => Node::Index,
+            "pc" => Node::ProgramCounter,
+            "dt" => Node::DelayTimer,
+            "st" => Node::SoundTimer,
+            "mem" => {
+                self.position += 1;
+                return Ok(Node::Memory(Box::new(self.subscript()?)));
+            }
+            "stack" => {
+                self.position += 1;
+                if self.peek() == Some(".") {
+                    self.position += 1;
+                    self.expect("len")?;
+                    self.expect("(")?;
+                    self.expect(")")?;
+                    return Ok(Node::StackLength);
+                }
+                return Ok(Node::Stack(Box::new(self.subscript()?)));
+            }
+            _ => match text.strip_prefix('v').map(|x| u8::from_str_radix(x, 16)) {
+                Some(Ok(x)) if text.len() == 2 => Node::Register(x),
+                _ => return Err(self.error(format!("Unknown value '{}'", token.text))),
+            },
+        };
+        self.position += 1;
+        Ok(node)
+    }
+
+    fn subscript(&mut self) -> Result<Node, ExpressionError> {
+        self.expect("[")?;
+        let node = self.binary(0)?;
+        self.expect("]")?;
+        Ok(node)
+    }
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    if let Some(hex) = text.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn evaluate(node: &Node, program: &Program) -> Result<i64, String> {
+    Ok(match node {
+        Node::Number(number) => *number,
+        Node::Register(x) => program.variable_regsiters[*x as usize] as i64,
+        Node::Index => program.index_register as i64,
+        Node::ProgramCounter => program.program_counter as i64,
+        Node::DelayTimer => program.delay_timer as i64,
+        Node::SoundTimer => program.sound_timer as i64,
+        Node::StackLength => program.call_stack.len() as i64,
+        Node::Memory(address) => {
+            let address = evaluate(address, program)?;
+            let byte = usize::try_from(address)
+                .ok()
+                .filter(|&address| address < program.platform.memory_size())
+                .map(|address| program.memory[address]);
+            byte.ok_or_else(|| format!("mem[{address:#x}] is outside of memory"))? as i64
+        }
+        Node::Stack(index) => {
+            let index = evaluate(index, program)?;
+            let address = usize::try_from(index)
+                .ok()
+                .and_then(|index| program.call_stack.get(index));
+            *address.ok_or_else(|| format!("stack[{index}] is empty"))? as i64
+        }
+        Node::Unary(operator, value) => {
+            let value = evaluate(value, program)?;
+            match operator {
+                '-' => value.wrapping_neg(),
+                '!' => (value == 0) as i64,
+                _ => !value,
+            }
+        }
+        Node::Binary(operator, left, right) => {
+            let left = evaluate(left, program)?;
+            // the right side of && and || only matters when the left doesn't decide
+            match *operator {
+                "&&" if left == 0 => return Ok(0),
+                "||" if left != 0 => return Ok(1),
+                _ => {}
+            }
+            let right = evaluate(right, program)?;
+            binary(operator, left, right)?
+        }
+    })
+}
+
+fn binary(operator: &str, left: i64, right: i64) -> Result<i64, String> {
+    let truth = |value: bool| value as i64;
+    Ok(match operator {
+        "||" | "&&" => truth(right != 0),
+        "==" => truth(left == right),
+        "!=" => truth(left != right),
+        "<" => truth(left < right),
+        "<=" => truth(left <= right),
+        ">" => truth(left > right),
+        ">=" => truth(left >= right),
+        "|" => left | right,
+        "^" => left ^ right,
+        "&" => left & right,
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err("Division by zero".to_string()),
+        "/" => left.wrapping_div(right),
+        _ => left.wrapping_rem(right),
+    })
+}
@@ -1,21 +1,61 @@
-use log::{info, warn};
+use std::{fmt::Display, sync::Mutex, time::Duration};
+
+use log::{error, info, warn};
 use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
 use web_sys::{
     js_sys::{Promise, Uint8Array},
-    Document, Event, HtmlButtonElement, HtmlDivElement, HtmlInputElement, HtmlSelectElement,
+    Document, Element, Event, HtmlButtonElement, HtmlInputElement, HtmlSelectElement,
     HtmlTableRowElement, Request, RequestInit, Response,
 };
 
 use crate::{
-    debugger::{render_debugger, BREAKPOINTS, INTERVAL_HANDLE, RENDER_DEBUGGER},
-    emulator::{self, get_program},
-    runner::{Runner, UPDATES_PER_SECOND},
-    ui::{
-        self, add_class_name, document, get_canvas_context, get_element, remove_class_name,
-        render_emulator, to_number, window,
+    analysis::analyze,
+    debugger::{
+        hide_debugger, render_debugger, show_debugger, show_input_error, DISASSEMBLY,
+        INTERVAL_HANDLE, WATCHES,
+    },
+    disassembler::disassemble,
+    emulator::{self, get_program, Breakpoint, Platform, Timing, Watchpoint},
+    expression::Expression,
+    keys::KEYBOARD,
+    metadata::{parse_platform, Database, Metadata},
+    movie::{Movie, Playback, Recorder},
+    palette::Palette,
+    quirks::Preset,
+    rng::RngKind,
+    runner::{
+        MovieMode, Runner, FRAMES_PER_SECOND, INSTRUCTIONS_PER_FRAME, JIT, MAX_CATCH_UP, MOVIE,
+        PAUSE_ON_BLUR, REWIND, TIMING,
     },
+    ui::{self, document, get_canvas_context, get_element, render_emulator, to_number, window},
 };
 
+// the rom that is loaded when the page opens
+const DEFAULT_ROM: &str = "splash-screen.ch8";
+
+// set when the emulator was paused because the page went in the background
+static PAUSED_IN_BACKGROUND: Mutex<bool> = Mutex::new(false);
+// apply the settings of the metadata database to the roms it knows
+static USE_METADATA: Mutex<bool> = Mutex::new(true);
+
+/// What the page can fail at, the machine's own faults are [`EmulatorError`](crate::error::EmulatorError)s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageError {
+    /// The browser storage refused the save state, it's full or disabled
+    StorageFailed { size: usize },
+}
+
+impl Display for PageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            PageError::StorageFailed { size } => write!(
+                f,
+                "The browser couldn't store the {size} bytes of the state, its storage may be full"
+            ),
+        }
+    }
+}
+
 pub fn set_handlers() {
     let document = &document();
     start_button_handler(document);
@@ -24,9 +64,34 @@ pub fn set_handlers() {
     load_rom_handler(document);
     debugger_on_handler(document);
     toggle_breakpoint_handler(document);
+    breakpoint_form_handler(document);
+    breakpoint_list_handler(document);
+    watch_form_handler(document);
+    watchpoint_form_handler(document);
+    watchpoint_list_handler(document);
+    watch_list_handler(document);
+    export_octo_handler(document);
     set_clock_speed_handler(document);
+    select_timing_handler(document);
+    max_catch_up_handler(document);
+    pause_on_blur_handler(document);
+    jit_handler(document);
     select_rom_handler(document);
+    select_quirks_handler(document);
+    select_palette_handler(document);
+    use_metadata_handler(document);
     reset_emulator_handler(document);
+    save_state_handler(document);
+    load_state_handler(document);
+    download_state_handler(document);
+    upload_state_handler(document);
+    rng_seed_handler(document);
+    rng_kind_handler(document);
+    new_seed_handler(document);
+    record_movie_handler(document);
+    stop_movie_handler(document);
+    play_movie_handler(document);
+    ui::show_rng(&get_program().lock().unwrap().rng);
 }
 
 fn start_button_handler(document: &Document) {
@@ -53,19 +118,44 @@ fn load_rom_handler(document: &Document) {
         if let Some(files) = input.files() {
             let file = files.item(0).unwrap();
             info!("Loading rom: {}", file.name());
+            // a .ch8 can be for any platform, the code has to tell
+            let platform = Platform::from_file_name(&file.name());
+            if let Some(platform) = platform {
+                set_preset(Preset::for_platform(platform));
+            }
 
-            let closure = load_rom_from_array_promise();
+            let closure = load_rom_from_array_promise(platform.is_none());
             let _ = file.array_buffer().then(&closure);
             closure.forget();
         }
     });
 }
 
-fn load_rom_from_array_promise() -> Closure<dyn FnMut(JsValue)> {
-    Closure::new(|js_value: JsValue| {
+/// Loads the rom, and picks the quirks that the analysis suggests if `guess_preset` is set
+fn load_rom_from_array_promise(guess_preset: bool) -> Closure<dyn FnMut(JsValue)> {
+    Closure::new(move |js_value: JsValue| {
         let u8_vec = Uint8Array::new(&js_value).to_vec();
+        let analysis = analyze(&u8_vec);
+        info!("{analysis}");
+        if let Some(preset) = analysis.preset().filter(|_| guess_preset) {
+            set_preset(preset);
+        }
+        ui::show_compatibility(&analysis);
+        let metadata = Database::bundled().lookup(&u8_vec);
+        if let Some(metadata) = metadata.filter(|_| *USE_METADATA.lock().unwrap()) {
+            apply_metadata(metadata);
+        }
+        ui::show_metadata(metadata);
         let mut program = emulator::get_program().lock().unwrap();
-        program.load_rom(&u8_vec);
+        if let Err(e) = program.load_rom(&u8_vec) {
+            error!("{e}");
+            ui::show_error(&e.to_string());
+            return;
+        }
+        // the history of the previous rom can't be rewound into
+        REWIND.lock().unwrap().clear();
+        discard_movie();
+        *DISASSEMBLY.lock().unwrap() = Some(disassemble(&u8_vec));
         render_emulator(&program, &get_canvas_context());
         info!("Loaded rom!");
         Runner::start_loop()();
@@ -79,7 +169,11 @@ fn step_button_handler(document: &Document) {
         let mut emulator = emulator::get_program()
             .lock()
             .expect("Could not lock the program");
-        emulator.tick();
+        emulator.pressed_keys = *KEYBOARD.lock().unwrap();
+        if let Err(e) = emulator.tick() {
+            error!("{e}");
+            ui::show_error(&e.to_string());
+        }
         emulator.timer_tick();
         render_emulator(&emulator, &get_canvas_context());
         info!("stepped through {}", emulator.program_counter)
@@ -89,42 +183,42 @@ fn step_button_handler(document: &Document) {
 fn stop_runner() {
     let handle = INTERVAL_HANDLE
         .lock()
-        .expect("Could not get intveral handle");
-    if handle.is_some() {
-        window().clear_interval_with_handle(handle.unwrap());
+        .expect("Could not get intveral handle")
+        .take();
+    if let Some(handle) = handle {
+        window()
+            .cancel_animation_frame(handle)
+            .expect("Could not cancel the animation frame");
     }
 }
 
 fn debugger_on_handler(document: &Document) {
     let checkbox: HtmlButtonElement = get_element(document, "#show-debugger");
     add_event_listener(&checkbox, "click", |e| {
-        let document = &crate::ui::document();
         let checkbox: HtmlButtonElement = e
             .current_target()
             .expect("Could not get target of event")
             .dyn_into()
             .expect("Could not dyn into a checkbox");
-        let debugger_area: HtmlDivElement = get_element(document, "#debugger");
-        let turn_on = !checkbox.class_name().contains("checked");
-        if turn_on {
-            add_class_name(&checkbox, "checked");
-            remove_class_name(&debugger_area, "off");
+        if checkbox.class_name().contains("checked") {
+            hide_debugger();
         } else {
-            add_class_name(&debugger_area, "off");
-            remove_class_name(&checkbox, "checked");
+            show_debugger();
         }
-
-        let mut a = RENDER_DEBUGGER
-            .lock()
-            .expect("Could not acquire debugger variable");
-        *a = turn_on;
-        // drop the mutex before rendering since it needs it
-        drop(a);
         let program = get_program().lock().unwrap();
         render_debugger(&program);
     });
 }
 
+/// Adds the breakpoint or removes it if it's already there
+fn toggle_breakpoint(address: u16) {
+    let mut program = get_program().lock().unwrap();
+    if program.breakpoints.remove(&address).is_none() {
+        program.breakpoints.insert(address, Breakpoint::default());
+    }
+    render_debugger(&program);
+}
+
 fn toggle_breakpoint_handler(document: &Document) {
     let rows = document
         .query_selector_all("#memory-table tr")
@@ -138,35 +232,179 @@ fn toggle_breakpoint_handler(document: &Document) {
                 .dyn_into()
                 .expect("Could not dyn into a row");
 
-            let address: usize = to_number(
+            let address: u16 = to_number(
                 &row.child_nodes()
                     .item(1)
                     .expect("Could not get the child node for the address"),
             );
-
-            let is_selected = row.class_name().contains("breakpoint");
-            let mut breakpoints = BREAKPOINTS
-                .lock()
-                .expect("Could not acquire breakpoint lock");
-            if is_selected {
-                row.set_class_name("");
-                let index = breakpoints
-                    .iter()
-                    .position(|breakpoint| *breakpoint == address)
-                    .unwrap();
-                breakpoints.remove(index);
-            } else {
-                row.set_class_name("breakpoint");
-                breakpoints.push(address);
-            }
+            toggle_breakpoint(address);
         });
     }
 }
 
+/// Sets a breakpoint anywhere in memory, not only on the page that is shown, with an
+/// optional condition and hit count
+fn breakpoint_form_handler(document: &Document) {
+    let form: Element = get_element(document, "#breakpoint-form");
+    add_event_listener(&form, "submit", |e| {
+        e.prevent_default();
+        let document = ui::document();
+        let address_input: HtmlInputElement = get_element(&document, "#breakpoint-address");
+        let condition_input: HtmlInputElement = get_element(&document, "#breakpoint-condition");
+        let hits_input: HtmlInputElement = get_element(&document, "#breakpoint-hits");
+        let address = address_input.value();
+        let Ok(address) = u16::from_str_radix(address.trim().trim_start_matches("0x"), 16) else {
+            show_input_error("#breakpoint-error", Some("The address must be hexadecimal"));
+            return;
+        };
+        let condition = condition_input.value();
+        let condition = match condition.trim() {
+            "" => None,
+            source => match Expression::parse(source) {
+                Ok(condition) => Some(condition),
+                Err(e) => {
+                    show_input_error("#breakpoint-error", Some(&e.to_string()));
+                    return;
+                }
+            },
+        };
+        let hit_count = hits_input.value().parse().ok().filter(|&hits| hits > 1);
+        show_input_error("#breakpoint-error", None);
+        let mut program = get_program().lock().unwrap();
+        program
+            .breakpoints
+            .insert(address, Breakpoint::new(condition, hit_count));
+        render_debugger(&program);
+        address_input.set_value("");
+        condition_input.set_value("");
+        hits_input.set_value("");
+    });
+}
+
+/// Watches a range of memory, the program stops after the instruction that reads or
+/// writes it, or before one that is in it
+fn watchpoint_form_handler(document: &Document) {
+    let form: Element = get_element(document, "#watchpoint-form");
+    add_event_listener(&form, "submit", |e| {
+        e.prevent_default();
+        let document = ui::document();
+        let start_input: HtmlInputElement = get_element(&document, "#watchpoint-start");
+        let end_input: HtmlInputElement = get_element(&document, "#watchpoint-end");
+        let checked = |selector| get_element::<HtmlInputElement>(&document, selector).checked();
+        let parse = |text: String| u16::from_str_radix(text.trim().trim_start_matches("0x"), 16);
+        let Ok(start) = parse(start_input.value()) else {
+            show_input_error(
+                "#watchpoint-error",
+                Some("The addresses must be hexadecimal"),
+            );
+            return;
+        };
+        let end = match end_input.value() {
+            end if end.trim().is_empty() => Ok(start),
+            end => parse(end),
+        };
+        let Ok(end) = end else {
+            show_input_error(
+                "#watchpoint-error",
+                Some("The addresses must be hexadecimal"),
+            );
+            return;
+        };
+        if end < start {
+            show_input_error("#watchpoint-error", Some("The range ends before it starts"));
+            return;
+        }
+        let watchpoint = Watchpoint {
+            start,
+            end,
+            read: checked("#watchpoint-read"),
+            write: checked("#watchpoint-write"),
+            execute: checked("#watchpoint-execute"),
+        };
+        if !(watchpoint.read || watchpoint.write || watchpoint.execute) {
+            show_input_error(
+                "#watchpoint-error",
+                Some("Watch reads, writes or execution"),
+            );
+            return;
+        }
+        show_input_error("#watchpoint-error", None);
+        let mut program = get_program().lock().unwrap();
+        program.watchpoints.push(watchpoint);
+        render_debugger(&program);
+        start_input.set_value("");
+        end_input.set_value("");
+    });
+}
+
+fn watchpoint_list_handler(document: &Document) {
+    let list: Element = get_element(document, "#watchpoint-list");
+    add_event_listener(&list, "click", |e| {
+        let index: Option<usize> = e
+            .target()
+            .and_then(|target| target.dyn_into::<Element>().ok())
+            .and_then(|item| item.get_attribute("data-index"))
+            .and_then(|index| index.parse().ok());
+        if let Some(index) = index {
+            let mut program = get_program().lock().unwrap();
+            program.watchpoints.remove(index);
+            render_debugger(&program);
+        }
+    });
+}
+
+fn watch_form_handler(document: &Document) {
+    let form: Element = get_element(document, "#watch-form");
+    add_event_listener(&form, "submit", |e| {
+        e.prevent_default();
+        let input: HtmlInputElement = get_element(&ui::document(), "#watch-expression");
+        match Expression::parse(&input.value()) {
+            Ok(watch) => {
+                show_input_error("#watch-error", None);
+                WATCHES.lock().unwrap().push(watch);
+                input.set_value("");
+                render_debugger(&get_program().lock().unwrap());
+            }
+            Err(e) => show_input_error("#watch-error", Some(&e.to_string())),
+        }
+    });
+}
+
+fn watch_list_handler(document: &Document) {
+    let list: Element = get_element(document, "#watch-list");
+    add_event_listener(&list, "click", |e| {
+        let index: Option<usize> = e
+            .target()
+            .and_then(|target| target.dyn_into::<Element>().ok())
+            .and_then(|item| item.get_attribute("data-index"))
+            .and_then(|index| index.parse().ok());
+        if let Some(index) = index {
+            WATCHES.lock().unwrap().remove(index);
+            render_debugger(&get_program().lock().unwrap());
+        }
+    });
+}
+
+fn breakpoint_list_handler(document: &Document) {
+    let list: Element = get_element(document, "#breakpoint-list");
+    add_event_listener(&list, "click", |e| {
+        let address = e
+            .target()
+            .and_then(|target| target.dyn_into::<Element>().ok())
+            .and_then(|item| item.get_attribute("data-address"))
+            .and_then(|address| address.parse().ok());
+        if let Some(address) = address {
+            toggle_breakpoint(address);
+        }
+    });
+}
+
 fn set_clock_speed_handler(document: &Document) {
     let slider: HtmlInputElement = get_element(document, "#speed-knob");
     let number_input: HtmlInputElement = get_element(document, "#speed-display");
-    let update_func = |event: Event| {
+    let per_frame_input: HtmlInputElement = get_element(document, "#frame-speed-display");
+    // the speed is in instructions per second, rounded to whole instructions per frame
+    let per_second_func = |event: Event| {
         // having so many unwraps and dyn intos is the reason that I think running js like code from rust is bad
         if let Ok(value) = event
             .current_target()
@@ -176,19 +414,150 @@ fn set_clock_speed_handler(document: &Document) {
             .value()
             .parse::<f64>()
         {
-            let display: HtmlInputElement = get_element(&ui::document(), "#speed-display");
-            *UPDATES_PER_SECOND.lock().unwrap() = value;
-            display.set_value_as_number(value);
+            set_instructions_per_frame((value / FRAMES_PER_SECOND as f64).round() as u32);
+        } else {
+            warn!("Too big of a number");
+        }
+    };
+    let per_frame_func = |event: Event| {
+        if let Ok(value) = event
+            .current_target()
+            .unwrap()
+            .dyn_into::<HtmlInputElement>()
+            .unwrap()
+            .value()
+            .parse::<u32>()
+        {
+            set_instructions_per_frame(value);
         } else {
             warn!("Too big of a number");
         }
     };
-    add_event_listener(&slider, "input", update_func);
-    add_event_listener(&number_input, "change", update_func);
+    add_event_listener(&slider, "input", per_second_func);
+    add_event_listener(&number_input, "change", per_second_func);
+    add_event_listener(&per_frame_input, "change", per_frame_func);
+}
+
+/// Sets the speed and shows it both per second and per frame
+fn set_instructions_per_frame(instructions: u32) {
+    let instructions = instructions.max(1);
+    *INSTRUCTIONS_PER_FRAME.lock().unwrap() = instructions;
+    // a frame a breakpoint stopped ends at the new speed
+    get_program().lock().unwrap().frame_progress = 0;
+    let per_second = (instructions * FRAMES_PER_SECOND) as f64;
+    let document = ui::document();
+    let slider: HtmlInputElement = get_element(&document, "#speed-knob");
+    let display: HtmlInputElement = get_element(&document, "#speed-display");
+    let per_frame_display: HtmlInputElement = get_element(&document, "#frame-speed-display");
+    slider.set_value_as_number(per_second);
+    display.set_value_as_number(per_second);
+    per_frame_display.set_value_as_number(instructions as f64);
+}
+
+fn select_timing_handler(document: &Document) {
+    let selector: HtmlSelectElement = get_element(document, "#timing-selector");
+    add_event_listener(&selector, "change", |event| {
+        let selector = event
+            .current_target()
+            .unwrap()
+            .dyn_into::<HtmlSelectElement>()
+            .unwrap();
+        selector.blur().unwrap();
+        match selector.value().parse::<Timing>() {
+            Ok(timing) => {
+                info!("Using the {timing} timing");
+                *TIMING.lock().unwrap() = timing;
+                get_program().lock().unwrap().frame_progress = 0;
+            }
+            Err(e) => warn!("{e}"),
+        }
+    });
+}
+
+fn max_catch_up_handler(document: &Document) {
+    let input_element: HtmlInputElement = get_element(document, "#max-catch-up");
+    add_event_listener(&input_element, "change", |e| {
+        let input = e
+            .current_target()
+            .unwrap()
+            .dyn_into::<HtmlInputElement>()
+            .unwrap();
+        match input.value().parse::<u64>() {
+            Ok(millis) => *MAX_CATCH_UP.lock().unwrap() = Duration::from_millis(millis),
+            Err(e) => warn!("The catch up time must be in milliseconds: {e}"),
+        }
+    });
+}
+
+fn pause_on_blur_handler(document: &Document) {
+    let checkbox: HtmlInputElement = get_element(document, "#pause-on-blur");
+    add_event_listener(&checkbox, "change", |e| {
+        let checkbox = e
+            .current_target()
+            .unwrap()
+            .dyn_into::<HtmlInputElement>()
+            .unwrap();
+        *PAUSE_ON_BLUR.lock().unwrap() = checkbox.checked();
+    });
+    add_event_listener(&window(), "blur", |_| pause_in_background());
+    add_event_listener(&window(), "focus", |_| resume_from_background());
+    add_event_listener(document, "visibilitychange", |_| {
+        // the page gets the focus back when it's visible again
+        if ui::document().hidden() {
+            pause_in_background();
+        }
+    });
+}
+
+fn jit_handler(document: &Document) {
+    let checkbox: HtmlInputElement = get_element(document, "#jit");
+    add_event_listener(&checkbox, "change", |e| {
+        let checkbox = e
+            .current_target()
+            .unwrap()
+            .dyn_into::<HtmlInputElement>()
+            .unwrap();
+        *JIT.lock().unwrap() = checkbox.checked();
+    });
+}
+
+fn pause_in_background() {
+    // the keys that are released in the background never get their key up
+    *KEYBOARD.lock().unwrap() = 0;
+    let running = INTERVAL_HANDLE.lock().unwrap().is_some();
+    if *PAUSE_ON_BLUR.lock().unwrap() && running {
+        stop_runner();
+        *PAUSED_IN_BACKGROUND.lock().unwrap() = true;
+        info!("Paused in the background");
+    }
+}
+
+fn resume_from_background() {
+    // only resume what was paused by the page, not by the stop button
+    if std::mem::take(&mut *PAUSED_IN_BACKGROUND.lock().unwrap()) {
+        Runner::start_loop()();
+    }
+}
+
+/// Fills the rom selector with the roms of the catalog that match the search and the
+/// platform filter
+fn show_catalog() {
+    let document = document();
+    let search: HtmlInputElement = get_element(&document, "#rom-search");
+    let filter: HtmlSelectElement = get_element(&document, "#platform-filter");
+    let selector: HtmlSelectElement = get_element(&document, "#rom-selector");
+    let roms = Database::bundled().search(&search.value(), parse_platform(&filter.value()));
+    ui::show_catalog(&roms, &selector.value());
 }
 
 fn select_rom_handler(document: &Document) {
     let selector: HtmlSelectElement = get_element(document, "#rom-selector");
+    ui::show_catalog(&Database::bundled().catalog(), DEFAULT_ROM);
+    let search: HtmlInputElement = get_element(document, "#rom-search");
+    add_event_listener(&search, "input", |_| show_catalog());
+    ui::show_platforms(&Database::bundled().platforms());
+    let filter: HtmlSelectElement = get_element(document, "#platform-filter");
+    add_event_listener(&filter, "change", |_| show_catalog());
     add_event_listener(&selector, "change", |event| {
         stop_runner();
         let selector = event
@@ -202,7 +571,7 @@ fn select_rom_handler(document: &Document) {
             let response: Response = value
                 .dyn_into()
                 .expect("did not get a response object from fetching the rom");
-            let closure = load_rom_from_array_promise();
+            let closure = load_rom_from_array_promise(false);
             let _ = response
                 .array_buffer()
                 .expect("Could not turn rom into an array buffer")
@@ -215,6 +584,80 @@ fn select_rom_handler(document: &Document) {
     });
 }
 
+fn select_quirks_handler(document: &Document) {
+    let selector: HtmlSelectElement = get_element(document, "#quirk-selector");
+    add_event_listener(&selector, "change", |event| {
+        let selector = event
+            .current_target()
+            .unwrap()
+            .dyn_into::<HtmlSelectElement>()
+            .unwrap();
+        selector.blur().unwrap();
+        match selector.value().parse::<Preset>() {
+            Ok(preset) => set_preset(preset),
+            Err(e) => warn!("{e}"),
+        }
+    });
+}
+
+fn select_palette_handler(document: &Document) {
+    let selector: HtmlSelectElement = get_element(document, "#palette-selector");
+    add_event_listener(&selector, "change", |event| {
+        let selector = event
+            .current_target()
+            .unwrap()
+            .dyn_into::<HtmlSelectElement>()
+            .unwrap();
+        selector.blur().unwrap();
+        match Palette::named(&selector.value()) {
+            Some(palette) => {
+                *ui::PALETTE.lock().unwrap() = palette;
+                // show it right away, even when the emulator is stopped
+                render_emulator(&get_program().lock().unwrap(), &get_canvas_context());
+            }
+            None => warn!("There is no palette named '{}'", selector.value()),
+        }
+    });
+}
+
+fn use_metadata_handler(document: &Document) {
+    let checkbox: HtmlInputElement = get_element(document, "#use-metadata");
+    add_event_listener(&checkbox, "change", |e| {
+        let checkbox = e
+            .current_target()
+            .unwrap()
+            .dyn_into::<HtmlInputElement>()
+            .unwrap();
+        *USE_METADATA.lock().unwrap() = checkbox.checked();
+    });
+}
+
+/// Uses the speed, colors and quirks the rom was made for, the controls can still change
+/// them afterwards
+fn apply_metadata(metadata: &Metadata) {
+    info!("Using the settings of {}", metadata.title);
+    set_preset(metadata.preset());
+    get_program().lock().unwrap().quirks = metadata.quirks();
+    if let Some(tickrate) = metadata.options.tickrate {
+        set_instructions_per_frame(tickrate);
+    }
+    if let Some(palette) = metadata.palette() {
+        *ui::PALETTE.lock().unwrap() = palette;
+        let selector: HtmlSelectElement = get_element(&document(), "#palette-selector");
+        selector.set_value("rom");
+    }
+}
+
+/// Uses the quirks and platform of the preset and shows it in the selector
+fn set_preset(preset: Preset) {
+    info!("Using the {preset} quirks");
+    let mut program = get_program().lock().unwrap();
+    program.quirks = preset.quirks();
+    program.set_platform(preset.platform());
+    let selector: HtmlSelectElement = get_element(&document(), "#quirk-selector");
+    selector.set_value(preset.name());
+}
+
 fn fetch_rom(path: &str) -> Promise {
     info!("Fetching rom: {path}");
     let options = RequestInit::new();
@@ -236,9 +679,247 @@ fn reset_emulator_handler(document: &Document) {
     let reset_button: HtmlButtonElement = get_element(document, "#reset");
     add_event_listener(&reset_button, "click", |_| {
         get_program().lock().unwrap().reset();
+        REWIND.lock().unwrap().clear();
+        discard_movie();
+        ui::clear_error();
+    });
+}
+
+fn save_state_handler(document: &Document) {
+    let button: HtmlButtonElement = get_element(document, "#save-state");
+    add_event_listener(&button, "click", |_| {
+        let state = get_program().lock().unwrap().save_state();
+        // the local storage only holds strings, and it's small enough that the XO-CHIP
+        // states of a few slots can fill it
+        let saved = ui::local_storage()
+            .and_then(|storage| storage.set_item(&save_slot_key(), &to_hex(&state)).ok());
+        if saved.is_none() {
+            let e = PageError::StorageFailed { size: state.len() };
+            error!("{e}");
+            ui::show_error(&e.to_string());
+            return;
+        }
+        info!("Saved the state in {}", save_slot_key());
+    });
+}
+
+fn load_state_handler(document: &Document) {
+    let button: HtmlButtonElement = get_element(document, "#load-state");
+    add_event_listener(&button, "click", |_| {
+        let saved_state = ui::local_storage()
+            .and_then(|storage| storage.get_item(&save_slot_key()).ok())
+            .flatten();
+        match saved_state.as_deref().and_then(from_hex) {
+            Some(state) => load_state(&state),
+            None => warn!("There is no state saved in {}", save_slot_key()),
+        }
+    });
+}
+
+fn export_octo_handler(document: &Document) {
+    let button: HtmlButtonElement = get_element(document, "#export-octo");
+    add_event_listener(&button, "click", |_| {
+        let Some(octo) = DISASSEMBLY.lock().unwrap().as_ref().map(|d| d.to_octo()) else {
+            warn!("There is no rom to export");
+            return;
+        };
+        let rom_name = to_hex(&get_program().lock().unwrap().rom_hash[..4]);
+        ui::download(octo.as_bytes(), &format!("{rom_name}.8o"));
+    });
+}
+
+fn download_state_handler(document: &Document) {
+    let button: HtmlButtonElement = get_element(document, "#download-state");
+    add_event_listener(&button, "click", |_| {
+        let program = get_program().lock().unwrap();
+        let rom_name = to_hex(&program.rom_hash[..4]);
+        ui::download(&program.save_state(), &format!("{rom_name}.c8s"));
+    });
+}
+
+fn upload_state_handler(document: &Document) {
+    let input_element: HtmlInputElement = get_element(document, "#upload-state");
+    add_event_listener(&input_element, "change", |e| {
+        let input = e
+            .current_target()
+            .expect("There was no target for this event")
+            .dyn_into::<HtmlInputElement>()
+            .expect("Could not dyn into input element");
+        if let Some(file) = input.files().and_then(|files| files.item(0)) {
+            info!("Loading state: {}", file.name());
+            let closure = Closure::new(|js_value: JsValue| {
+                load_state(&Uint8Array::new(&js_value).to_vec());
+            });
+            let _ = file.array_buffer().then(&closure);
+            closure.forget();
+            // so the same file can be uploaded again
+            input.set_value("");
+        }
+    });
+}
+
+fn load_state(state: &[u8]) {
+    discard_movie();
+    let mut program = get_program().lock().unwrap();
+    match program.load_state(state) {
+        Ok(()) => {
+            ui::clear_error();
+            ui::show_rng(&program.rng);
+            render_emulator(&program, &get_canvas_context());
+            info!("Loaded the state");
+        }
+        Err(e) => {
+            error!("{e}");
+            ui::show_error(&e.to_string());
+        }
+    }
+}
+
+fn rng_seed_handler(document: &Document) {
+    let input_element: HtmlInputElement = get_element(document, "#rng-seed");
+    add_event_listener(&input_element, "change", |e| {
+        let input = e
+            .current_target()
+            .expect("There was no target for this event")
+            .dyn_into::<HtmlInputElement>()
+            .expect("Could not dyn into input element");
+        let mut program = get_program().lock().unwrap();
+        match u64::from_str_radix(input.value().trim(), 16) {
+            Ok(seed) => {
+                program.rng.reseed(seed);
+                info!("Using the seed {seed:x}");
+            }
+            Err(e) => warn!("The seed must be hexadecimal: {e}"),
+        }
+        ui::show_rng(&program.rng);
+    });
+}
+
+fn rng_kind_handler(document: &Document) {
+    let selector: HtmlSelectElement = get_element(document, "#rng-kind");
+    add_event_listener(&selector, "change", |event| {
+        let selector = event
+            .current_target()
+            .unwrap()
+            .dyn_into::<HtmlSelectElement>()
+            .unwrap();
+        selector.blur().unwrap();
+        match selector.value().parse::<RngKind>() {
+            Ok(kind) => get_program().lock().unwrap().rng.set_kind(kind),
+            Err(e) => warn!("{e}"),
+        }
+    });
+}
+
+fn new_seed_handler(document: &Document) {
+    let button: HtmlButtonElement = get_element(document, "#new-seed");
+    add_event_listener(&button, "click", |_| {
+        let mut program = get_program().lock().unwrap();
+        let seed = program.host.entropy();
+        program.rng.reseed(seed);
+        ui::show_rng(&program.rng);
+    });
+}
+
+fn record_movie_handler(document: &Document) {
+    let button: HtmlButtonElement = get_element(document, "#record-movie");
+    add_event_listener(&button, "click", |_| {
+        let program = get_program().lock().unwrap();
+        *MOVIE.lock().unwrap() = Some(MovieMode::Recording(Recorder::new(&program)));
+        ui::show_movie_status("Recording frame 0");
+        info!("Recording a movie");
+    });
+}
+
+fn stop_movie_handler(document: &Document) {
+    let button: HtmlButtonElement = get_element(document, "#stop-movie");
+    add_event_listener(&button, "click", |_| {
+        match MOVIE.lock().unwrap().take() {
+            Some(MovieMode::Recording(recorder)) => {
+                let movie = recorder.finish();
+                let rom_name = to_hex(&movie.rom_hash[..4]);
+                ui::download(&movie.to_bytes(), &format!("{rom_name}.c8m"));
+                info!("Recorded {} frames", movie.frames.len());
+            }
+            Some(MovieMode::Playing(playback)) => {
+                info!("Stopped the movie at frame {}", playback.frame());
+            }
+            None => {}
+        }
+        ui::show_movie_status("");
     });
 }
 
+fn play_movie_handler(document: &Document) {
+    let input_element: HtmlInputElement = get_element(document, "#play-movie");
+    add_event_listener(&input_element, "change", |e| {
+        let input = e
+            .current_target()
+            .expect("There was no target for this event")
+            .dyn_into::<HtmlInputElement>()
+            .expect("Could not dyn into input element");
+        if let Some(file) = input.files().and_then(|files| files.item(0)) {
+            info!("Loading movie: {}", file.name());
+            let closure = Closure::new(|js_value: JsValue| {
+                play_movie(&Uint8Array::new(&js_value).to_vec());
+            });
+            let _ = file.array_buffer().then(&closure);
+            closure.forget();
+            // so the same file can be played again
+            input.set_value("");
+        }
+    });
+}
+
+fn play_movie(bytes: &[u8]) {
+    let mut program = get_program().lock().unwrap();
+    let playback = Movie::from_bytes(bytes).and_then(|movie| Playback::new(movie, &mut program));
+    let mut playback = match playback {
+        Ok(playback) => playback,
+        Err(e) => {
+            error!("{e}");
+            ui::show_error(&e.to_string());
+            return;
+        }
+    };
+    let stop_frame: HtmlInputElement = get_element(&document(), "#movie-stop-frame");
+    if let Ok(frame) = stop_frame.value().parse::<usize>() {
+        playback.stop_at(frame);
+    }
+    info!("Playing {} frames", playback.len());
+    *MOVIE.lock().unwrap() = Some(MovieMode::Playing(playback));
+    REWIND.lock().unwrap().clear();
+    ui::clear_error();
+    ui::show_rng(&program.rng);
+    render_emulator(&program, &get_canvas_context());
+    drop(program);
+    Runner::start_loop()();
+}
+
+/// Anything that changes the program outside of a frame would make the movie go out of sync
+fn discard_movie() {
+    if MOVIE.lock().unwrap().take().is_some() {
+        warn!("The movie was stopped");
+        ui::show_movie_status("");
+    }
+}
+
+fn save_slot_key() -> String {
+    let selector: HtmlSelectElement = get_element(&document(), "#save-slot");
+    format!("save-state-{}", selector.value())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
 pub fn add_event_listener(target: &web_sys::EventTarget, event_name: &str, func: fn(e: Event)) {
     let closure: Closure<dyn Fn(Event)> = Closure::new(func);
     target
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+/// Everything the emulator core needs from the platform it runs on.
+/// The browser build uses [`WebHost`], native tools and tests use [`NativeHost`].
+pub trait Host {
+    /// Unpredictable bits to seed the random number generator of a new program
+    fn entropy(&mut self) -> u64;
+
+    /// Called on every timer tick while the sound timer is active
+    fn beep(&mut self);
+
+    /// Called on every timer tick while the sound timer is inactive
+    fn stop_beep(&mut self);
+
+    /// The XO-CHIP audio pattern and pitch to beep with, `None` for the default beep
+    fn set_audio_pattern(&mut self, pattern: Option<[u8; 16]>, pitch: u8);
+
+    /// Monotonic time since an arbitrary origin
+    fn now(&self) -> Duration;
+}
+
+#[cfg(feature = "web")]
+pub use web::WebHost;
+
+#[cfg(feature = "web")]
+mod web {
+    use std::time::Duration;
+
+    use web_sys::js_sys::Math::random;
+
+    use super::Host;
+    use crate::ui;
+
+    pub struct WebHost;
+
+    impl Host for WebHost {
+        fn entropy(&mut self) -> u64 {
+            // random() only has 53 bits of precision, so 32 bits at a time
+            let half = || (random() * 4_294_967_296.0) as u64;
+            (half() << 32) | half()
+        }
+
+        fn beep(&mut self) {
+            ui::beep();
+        }
+
+        fn stop_beep(&mut self) {
+            ui::stop_beep();
+        }
+
+        fn set_audio_pattern(&mut self, pattern: Option<[u8; 16]>, pitch: u8) {
+            ui::set_audio_pattern(pattern, pitch);
+        }
+
+        fn now(&self) -> Duration {
+            let millis = ui::window()
+                .performance()
+                .expect("There is no performance object on the window")
+                .now();
+            Duration::from_secs_f64(millis / 1000.0)
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+pub use native::NativeHost;
+
+#[cfg(feature = "native")]
+mod native {
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use super::Host;
+
+    /// A host without any audio or display, the sound state is only recorded
+    pub struct NativeHost {
+        start: Instant,
+        pub sound_on: bool,
+        pub audio_pattern: Option<[u8; 16]>,
+        pub pitch: u8,
+    }
+
+    impl NativeHost {
+        pub fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                sound_on: false,
+                audio_pattern: None,
+                pitch: 0,
+            }
+        }
+    }
+
+    impl Default for NativeHost {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Host for NativeHost {
+        fn entropy(&mut self) -> u64 {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_nanos() as u64)
+                .unwrap_or_default()
+        }
+
+        fn beep(&mut self) {
+            self.sound_on = true;
+        }
+
+        fn stop_beep(&mut self) {
+            self.sound_on = false;
+        }
+
+        fn set_audio_pattern(&mut self, pattern: Option<[u8; 16]>, pitch: u8) {
+            self.audio_pattern = pattern;
+            self.pitch = pitch;
+        }
+
+        fn now(&self) -> Duration {
+            self.start.elapsed()
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Every instruction of CHIP-8, SUPER-CHIP and XO-CHIP, decoded once and shared by the
+//! emulator and the debugger.
+
+use std::fmt::Display;
+
+use crate::{emulator::Platform, error::UnknownOpcode};
+
+/// A decoded instruction. `x` and `y` are register numbers, from 0 to F.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0 turns off the pixels of the selected planes
+    ClearScreen,
+    /// 00EE
+    Return,
+    /// 00CN SUPER-CHIP scrolls down by N pixels
+    ScrollDown { rows: u8 },
+    /// 00FB SUPER-CHIP scrolls right by 4 pixels
+    ScrollRight,
+    /// 00FC SUPER-CHIP scrolls left by 4 pixels
+    ScrollLeft,
+    /// 00FD SUPER-CHIP exits the interpreter
+    Exit,
+    /// 00FE SUPER-CHIP
+    LowResolution,
+    /// 00FF SUPER-CHIP
+    HighResolution,
+    /// 1NNN
+    Jump { address: u16 },
+    /// 2NNN
+    Call { address: u16 },
+    /// 3XNN
+    SkipIfEqualByte { x: u8, byte: u8 },
+    /// 4XNN
+    SkipIfNotEqualByte { x: u8, byte: u8 },
+    /// 5XY0
+    SkipIfEqual { x: u8, y: u8 },
+    /// 5XY2 XO-CHIP saves VX to VY at I without changing I
+    SaveRange { x: u8, y: u8 },
+    /// 5XY3 XO-CHIP loads VX to VY from I without changing I
+    LoadRange { x: u8, y: u8 },
+    /// 6XNN
+    LoadByte { x: u8, byte: u8 },
+    /// 7XNN doesn't change VF
+    AddByte { x: u8, byte: u8 },
+    /// 8XY0
+    Load { x: u8, y: u8 },
+    /// 8XY1
+    Or { x: u8, y: u8 },
+    /// 8XY2
+    And { x: u8, y: u8 },
+    /// 8XY3
+    Xor { x: u8, y: u8 },
+    /// 8XY4 VF is the carry
+    Add { x: u8, y: u8 },
+    /// 8XY5 VX = VX - VY, VF is 1 when there is no borrow
+    Sub { x: u8, y: u8 },
+    /// 8XY6 VF is the bit shifted out
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7 VX = VY - VX, VF is 1 when there is no borrow
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE VF is the bit shifted out
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0
+    SkipIfNotEqual { x: u8, y: u8 },
+    /// ANNN
+    LoadIndex { address: u16 },
+    /// BNNN jumps to NNN + V0, or to XNN + VX with the jumping quirk
+    JumpOffset { address: u16 },
+    /// CXNN
+    Random { x: u8, mask: u8 },
+    /// DXYN draws N rows, DXY0 is a 16x16 sprite
+    Draw { x: u8, y: u8, rows: u8 },
+    /// EX9E
+    SkipIfPressed { x: u8 },
+    /// EXA1
+    SkipIfNotPressed { x: u8 },
+    /// F000 NNNN XO-CHIP loads the 16 bit address that follows into I
+    LoadLongIndex,
+    /// FN01 XO-CHIP selects the planes that are drawn on
+    SelectPlanes { planes: u8 },
+    /// F002 XO-CHIP loads the 16 bytes at I as the audio pattern
+    LoadAudio,
+    /// FX07
+    LoadDelay { x: u8 },
+    /// FX0A waits for a key to be pressed and released
+    WaitKey { x: u8 },
+    /// FX15
+    SetDelay { x: u8 },
+    /// FX18
+    SetSound { x: u8 },
+    /// FX1E
+    AddIndex { x: u8 },
+    /// FX29 points I to the small font character of VX
+    LoadFont { x: u8 },
+    /// FX30 SUPER-CHIP points I to the big font character of VX
+    LoadBigFont { x: u8 },
+    /// FX33 stores the decimal digits of VX at I
+    StoreDigits { x: u8 },
+    /// FX3A XO-CHIP
+    SetPitch { x: u8 },
+    /// FX55 stores V0 to VX at I
+    StoreRegisters { x: u8 },
+    /// FX65 loads V0 to VX from I
+    LoadRegisters { x: u8 },
+    /// FX75 SUPER-CHIP saves V0 to VX in the RPL flags
+    SaveFlags { x: u8 },
+    /// FX85 SUPER-CHIP loads V0 to VX from the RPL flags
+    LoadFlags { x: u8 },
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Result<Instruction, UnknownOpcode> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let byte = (opcode & 0x00FF) as u8;
+        let address = opcode & 0x0FFF;
+        let instruction = match opcode >> 12 {
+            0x0 => match opcode {
+                0x00E0 => Instruction::ClearScreen,
+                0x00EE => Instruction::Return,
+                0x00C0..=0x00CF => Instruction::ScrollDown { rows: n },
+                0x00FB => Instruction::ScrollRight,
+                0x00FC => Instruction::ScrollLeft,
+                0x00FD => Instruction::Exit,
+                0x00FE => Instruction::LowResolution,
+                0x00FF => Instruction::HighResolution,
+                // executing machine language routines isn't possible
+                _ => return Err(UnknownOpcode(opcode)),
+            },
+            0x1 => Instruction::Jump { address },
+            0x2 => Instruction::Call { address },
+            0x3 => Instruction::SkipIfEqualByte { x, byte },
+            0x4 => Instruction::SkipIfNotEqualByte { x, byte },
+            0x5 => match n {
+                0x0 => Instruction::SkipIfEqual { x, y },
+                0x2 => Instruction::SaveRange { x, y },
+                0x3 => Instruction::LoadRange { x, y },
+                _ => return Err(UnknownOpcode(opcode)),
+            },
+            0x6 => Instruction::LoadByte { x, byte },
+            0x7 => Instruction::AddByte { x, byte },
+            0x8 => match n {
+                0x0 => Instruction::Load { x, y },
+                0x1 => Instruction::Or { x, y },
+                0x2 => Instruction::And { x, y },
+                0x3 => Instruction::Xor { x, y },
+                0x4 => Instruction::Add { x, y },
+                0x5 => Instruction::Sub { x, y },
+                0x6 => Instruction::ShiftRight { x, y },
+                0x7 => Instruction::SubReverse { x, y },
+                0xE => Instruction::ShiftLeft { x, y },
+                _ => return Err(UnknownOpcode(opcode)),
+            },
+            0x9 if n == 0 => Instruction::SkipIfNotEqual { x, y },
+            0xA => Instruction::LoadIndex { address },
+            0xB => Instruction::JumpOffset { address },
+            0xC => Instruction::Random { x, mask: byte },
+            0xD => Instruction::Draw { x, y, rows: n },
+            0xE => match byte {
+                0x9E => Instruction::SkipIfPressed { x },
+                0xA1 => Instruction::SkipIfNotPressed { x },
+                _ => return Err(UnknownOpcode(opcode)),
+            },
+            0xF => match byte {
+                0x00 if x == 0 => Instruction::LoadLongIndex,
+                0x01 => Instruction::SelectPlanes { planes: x },
+                0x02 if x == 0 => Instruction::LoadAudio,
+                0x07 => Instruction::LoadDelay { x },
+                0x0A => Instruction::WaitKey { x },
+                0x15 => Instruction::SetDelay { x },
+                0x18 => Instruction::SetSound { x },
+                0x1E => Instruction::AddIndex { x },
+                0x29 => Instruction::LoadFont { x },
+                0x30 => Instruction::LoadBigFont { x },
+                0x33 => Instruction::StoreDigits { x },
+                0x3A => Instruction::SetPitch { x },
+                0x55 => Instruction::StoreRegisters { x },
+                0x65 => Instruction::LoadRegisters { x },
+                0x75 => Instruction::SaveFlags { x },
+                0x85 => Instruction::LoadFlags { x },
+                _ => return Err(UnknownOpcode(opcode)),
+            },
+            _ => return Err(UnknownOpcode(opcode)),
+        };
+        Ok(instruction)
+    }
+
+    /// The opcode of the instruction, `decode(encode())` is always the same instruction
+    pub fn encode(self) -> u16 {
+        let xy = |prefix: u16, x: u8, y: u8, n: u16| {
+            prefix << 12 | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | n
+        };
+        let xnn = |prefix: u16, x: u8, byte: u8| prefix << 12 | (x as u16 & 0xF) << 8 | byte as u16;
+        let fx = |x: u8, byte: u16| 0xF000 | (x as u16 & 0xF) << 8 | byte;
+        match self {
+            Instruction::ClearScreen => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollDown { rows } => 0x00C0 | (rows as u16 & 0xF),
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowResolution => 0x00FE,
+            Instruction::HighResolution => 0x00FF,
+            Instruction::Jump { address } => 0x1000 | (address & 0x0FFF),
+            Instruction::Call { address } => 0x2000 | (address & 0x0FFF),
+            Instruction::SkipIfEqualByte { x, byte } => xnn(0x3, x, byte),
+            Instruction::SkipIfNotEqualByte { x, byte } => xnn(0x4, x, byte),
+            Instruction::SkipIfEqual { x, y } => xy(0x5, x, y, 0x0),
+            Instruction::SaveRange { x, y } => xy(0x5, x, y, 0x2),
+            Instruction::LoadRange { x, y } => xy(0x5, x, y, 0x3),
+            Instruction::LoadByte { x, byte } => xnn(0x6, x, byte),
+            Instruction::AddByte { x, byte } => xnn(0x7, x, byte),
+            Instruction::Load { x, y } => xy(0x8, x, y, 0x0),
+            Instruction::Or { x, y } => xy(0x8, x, y, 0x1),
+            Instruction::And { x, y } => xy(0x8, x, y, 0x2),
+            Instruction::Xor { x, y } => xy(0x8, x, y, 0x3),
+            Instruction::Add { x, y } => xy(0x8, x, y, 0x4),
+            Instruction::Sub { x, y } => xy(0x8, x, y, 0x5),
+            Instruction::ShiftRight { x, y } => xy(0x8, x, y, 0x6),
+            Instruction::SubReverse { x, y } => xy(0x8, x, y, 0x7),
+            Instruction::ShiftLeft { x, y } => xy(0x8, x, y, 0xE),
+            Instruction::SkipIfNotEqual { x, y } => xy(0x9, x, y, 0x0),
+            Instruction::LoadIndex { address } => 0xA000 | (address & 0x0FFF),
+            Instruction::JumpOffset { address } => 0xB000 | (address & 0x0FFF),
+            Instruction::Random { x, mask } => xnn(0xC, x, mask),
+            Instruction::Draw { x, y, rows } => xy(0xD, x, y, rows as u16 & 0xF),
+            Instruction::SkipIfPressed { x } => xnn(0xE, x, 0x9E),
+            Instruction::SkipIfNotPressed { x } => xnn(0xE, x, 0xA1),
+            Instruction::LoadLongIndex => 0xF000,
+            Instruction::SelectPlanes { planes } => fx(planes, 0x01),
+            Instruction::LoadAudio => 0xF002,
+            Instruction::LoadDelay { x } => fx(x, 0x07),
+            Instruction::WaitKey { x } => fx(x, 0x0A),
+            Instruction::SetDelay { x } => fx(x, 0x15),
+            Instruction::SetSound { x } => fx(x, 0x18),
+            Instruction::AddIndex { x } => fx(x, 0x1E),
+            Instruction::LoadFont { x } => fx(x, 0x29),
+            Instruction::LoadBigFont { x } => fx(x, 0x30),
+            Instruction::StoreDigits { x } => fx(x, 0x33),
+            Instruction::SetPitch { x } => fx(x, 0x3A),
+            Instruction::StoreRegisters { x } => fx(x, 0x55),
+            Instruction::LoadRegisters { x } => fx(x, 0x65),
+            Instruction::SaveFlags { x } => fx(x, 0x75),
+            Instruction::LoadFlags { x } => fx(x, 0x85),
+        }
+    }
+
+    /// The XO-CHIP additions don't exist on the older platforms, every other instruction
+    /// runs everywhere
+    pub fn is_supported_on(self, platform: Platform) -> bool {
+        let xo_chip_only = matches!(
+            self,
+            Instruction::SaveRange { .. }
+                | Instruction::LoadRange { .. }
+                | Instruction::LoadLongIndex
+                | Instruction::SelectPlanes { .. }
+                | Instruction::LoadAudio
+                | Instruction::SetPitch { .. }
+        );
+        !xo_chip_only || platform == Platform::XoChip
+    }
+
+    /// How many bytes the instruction takes, F000 is followed by its address
+    pub fn size(self) -> u16 {
+        match self {
+            Instruction::LoadLongIndex => 4,
+            _ => 2,
+        }
+    }
+}
+
+/// The mnemonics of the debugger
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollDown { rows } => write!(f, "SCD {rows:x}"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowResolution => write!(f, "LOW"),
+            Instruction::HighResolution => write!(f, "HIGH"),
+            Instruction::Jump { address } => write!(f, "JP {address:x}"),
+            Instruction::Call { address } => write!(f, "CALL {address:x}"),
+            Instruction::SkipIfEqualByte { x, byte } => write!(f, "SE V[{x:x}], {byte:x}"),
+            Instruction::SkipIfNotEqualByte { x, byte } => write!(f, "SNE V[{x:x}], {byte:x}"),
+            Instruction::SkipIfEqual { x, y } => write!(f, "SE V[{x:x}], V[{y:x}]"),
+            Instruction::SaveRange { x, y } => write!(f, "SAVE V[{x:x}]-V[{y:x}]"),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V[{x:x}]-V[{y:x}]"),
+            Instruction::LoadByte { x, byte } => write!(f, "LD V[{x:x}], {byte:x}"),
+            Instruction::AddByte { x, byte } => write!(f, "ADD V[{x:x}], {byte:x}"),
+            Instruction::Load { x, y } => write!(f, "LD V[{x:x}], V[{y:x}]"),
+            Instruction::Or { x, y } => write!(f, "OR V[{x:x}], V[{y:x}]"),
+            Instruction::And { x, y } => write!(f, "AND V[{x:x}], V[{y:x}]"),
+            Instruction::Xor { x, y } => write!(f, "XOR V[{x:x}], V[{y:x}]"),
+            Instruction::Add { x, y } => write!(f, "ADD V[{x:x}], V[{y:x}]"),
+            Instruction::Sub { x, y } => write!(f, "SUB V[{x:x}], V[{y:x}]"),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V[{x:x}], V[{y:x}]"),
+            Instruction::SubReverse { x, y } => write!(f, "SUBR V[{x:x}], V[{y:x}]"),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V[{x:x}], V[{y:x}]"),
+            Instruction::SkipIfNotEqual { x, y } => write!(f, "SNE V[{x:x}], V[{y:x}]"),
+            Instruction::LoadIndex { address } => write!(f, "LD I, {address:x}"),
+            Instruction::JumpOffset { address } => write!(f, "JP V0, {address:x}"),
+            Instruction::Random { x, mask } => write!(f, "RND V[{x:x}], {mask:x}"),
+            Instruction::Draw { x, y, rows } => write!(f, "DRW V[{x:x}], V[{y:x}], {rows}"),
+            Instruction::SkipIfPressed { x } => write!(f, "SKP V[{x:x}]"),
+            Instruction::SkipIfNotPressed { x } => write!(f, "SKNP V[{x:x}]"),
+            Instruction::LoadLongIndex => write!(f, "LD I, LONG"),
+            Instruction::SelectPlanes { planes } => write!(f, "PLANE {planes:x}"),
+            Instruction::LoadAudio => write!(f, "AUDIO"),
+            Instruction::LoadDelay { x } => write!(f, "LD V[{x:x}], DT"),
+            Instruction::WaitKey { x } => write!(f, "LD V[{x:x}], K"),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V[{x:x}]"),
+            Instruction::SetSound { x } => write!(f, "LD ST, V[{x:x}]"),
+            Instruction::AddIndex { x } => write!(f, "ADD I, V[{x:x}]"),
+            Instruction::LoadFont { x } => write!(f, "LD F, V[{x:x}]"),
+            Instruction::LoadBigFont { x } => write!(f, "LD HF, V[{x:x}]"),
+            Instruction::StoreDigits { x } => write!(f, "LD B, V[{x:x}]"),
+            Instruction::SetPitch { x } => write!(f, "PITCH V[{x:x}]"),
+            Instruction::StoreRegisters { x } => write!(f, "LD [I], V[{x:x}]"),
+            Instruction::LoadRegisters { x } => write!(f, "LD V[{x:x}], [I]"),
+            Instruction::SaveFlags { x } => write!(f, "LD R, V[{x:x}]"),
+            Instruction::LoadFlags { x } => write!(f, "LD V[{x:x}], R"),
+        }
+    }
+}
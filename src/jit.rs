@@ -0,0 +1,118 @@
+//! Runs the blocks that [`Program::compile_block`] compiles, the module imports the memory
+//! of this one so it works on the fields of the program directly.
+
+use std::collections::HashMap;
+
+use log::warn;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::js_sys::{Array, Function, Object, Reflect, Uint8Array, WebAssembly};
+
+use crate::{
+    emulator::{Block, Program},
+    error::EmulatorError,
+};
+
+pub struct Jit {
+    // by the address they start at
+    blocks: HashMap<u16, Compiled>,
+    // the blocks the browser refused, ticked through until their instructions change
+    failed: HashMap<u16, Block>,
+}
+
+struct Compiled {
+    block: Block,
+    run: Function,
+}
+
+impl Jit {
+    pub fn new() -> Self {
+        Self {
+            blocks: HashMap::new(),
+            failed: HashMap::new(),
+        }
+    }
+
+    /// Runs the block at the program counter, or ticks when there is none, and returns how
+    /// many instructions ran. The program ends up the same as after that many ticks, the
+    /// blocks with a breakpoint are ticked through so they stop on it.
+    pub fn step(&mut self, emulator: &mut Program, budget: u32) -> Result<u32, EmulatorError> {
+        match self.run_block(emulator, budget) {
+            0 => emulator.tick().map(|_| 1),
+            ran => Ok(ran),
+        }
+    }
+
+    /// Runs the block at the program counter, returns how many instructions ran
+    fn run_block(&mut self, emulator: &mut Program, budget: u32) -> u32 {
+        let address = emulator.program_counter;
+        let current = self
+            .blocks
+            .get(&address)
+            .is_some_and(|compiled| emulator.is_current(&compiled.block));
+        if !current {
+            let failed = self
+                .failed
+                .get(&address)
+                .is_some_and(|block| emulator.is_current(block));
+            if failed {
+                return 0;
+            }
+            // the first instruction can't be compiled, or it can now that memory changed
+            self.blocks.remove(&address);
+            self.failed.remove(&address);
+            let Some(block) = emulator.compile_block(address) else {
+                return 0;
+            };
+            match instantiate(&block) {
+                Ok(run) => {
+                    self.blocks.insert(address, Compiled { block, run });
+                }
+                Err(e) => {
+                    warn!("Couldn't compile the block at {address:#06x}: {e:?}");
+                    self.failed.insert(address, block);
+                    return 0;
+                }
+            }
+        }
+        // in wasm the pointers are the addresses in the memory the block imported
+        let arguments = Array::of5(
+            &JsValue::from(emulator.variable_regsiters.as_mut_ptr() as u32),
+            &JsValue::from(emulator.memory.as_mut_ptr() as u32),
+            &JsValue::from(&mut emulator.index_register as *mut u16 as u32),
+            &JsValue::from(&mut emulator.program_counter as *mut u16 as u32),
+            &JsValue::from(budget),
+        );
+        let compiled = &self.blocks[&address];
+        // the blocks use memory without the watchpoints seeing it
+        let has_breakpoint = !emulator.watchpoints.is_empty()
+            || !emulator.breakpoints.is_empty()
+                && compiled
+                    .block
+                    .addresses()
+                    .any(|address| emulator.breakpoints.contains_key(&address));
+        if has_breakpoint {
+            return 0;
+        }
+        let run = &compiled.run;
+        match run.apply(&JsValue::NULL, &arguments) {
+            Ok(ran) => ran.as_f64().unwrap_or_default() as u32,
+            Err(e) => {
+                warn!("The block at {address:#06x} failed: {e:?}");
+                if let Some(compiled) = self.blocks.remove(&address) {
+                    self.failed.insert(address, compiled.block);
+                }
+                0
+            }
+        }
+    }
+}
+
+fn instantiate(block: &Block) -> Result<Function, JsValue> {
+    let module = WebAssembly::Module::new(&Uint8Array::from(block.module()))?;
+    let env = Object::new();
+    Reflect::set(&env, &"memory".into(), &wasm_bindgen::memory())?;
+    let imports = Object::new();
+    Reflect::set(&imports, &"env".into(), &env)?;
+    let instance = WebAssembly::Instance::new(&module, &imports)?;
+    Reflect::get(&instance.exports(), &"run".into())?.dyn_into()
+}
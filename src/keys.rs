@@ -1,26 +1,36 @@
 use wasm_bindgen::{prelude::Closure, JsCast};
 use web_sys::{Event, KeyboardEvent};
 
-use crate::{emulator, ui};
+use std::sync::Mutex;
+
+use crate::{runner::REWINDING, ui};
+
+/// The keys held on the keyboard, one bit per key.
+/// The runner hands them to the program at the start of every frame.
+pub static KEYBOARD: Mutex<u16> = Mutex::new(0);
+
+// hold it to play the game backwards
+const REWIND_KEY: &str = "Backspace";
+/// The keyboard keys of the CHIP-8 keys, in the same square on the left of the keyboard
+pub const KEYS: [&str; 16] = [
+    "KeyX", "Digit1", "Digit2", "Digit3", "KeyQ", "KeyW", "KeyE", "KeyA", "KeyS", "KeyD", "KeyZ",
+    "KeyC", "Digit4", "KeyR", "KeyF", "KeyV",
+];
 
 pub fn set_handlers() {
     let document = ui::document();
-    const KEYS: [&str; 16] = [
-        "KeyX", "Digit1", "Digit2", "Digit3", "KeyQ", "KeyW", "KeyE", "KeyA", "KeyS", "KeyD",
-        "KeyZ", "KeyC", "Digit4", "KeyR", "KeyF", "KeyV",
-    ];
 
     let key_down_handler: Closure<dyn Fn(Event)> = Closure::new(|e: Event| {
         let key_code = e
             .dyn_into::<KeyboardEvent>()
             .expect("Could not dyn into keyboard event")
             .code();
+        if key_code == REWIND_KEY {
+            *REWINDING.lock().unwrap() = true;
+        }
         for (i, &key) in KEYS.iter().enumerate() {
             if key == key_code.as_str() {
-                emulator::get_program()
-                    .lock()
-                    .unwrap()
-                    .set_key_down(i as u8);
+                *KEYBOARD.lock().unwrap() |= 0b1 << i;
             }
         }
     });
@@ -29,9 +39,12 @@ pub fn set_handlers() {
             .dyn_into::<KeyboardEvent>()
             .expect("Could not dyn into keyboard event")
             .code();
+        if key_code == REWIND_KEY {
+            *REWINDING.lock().unwrap() = false;
+        }
         for (i, &key) in KEYS.iter().enumerate() {
             if key == key_code.as_str() {
-                emulator::get_program().lock().unwrap().set_key_up(i as u8);
+                *KEYBOARD.lock().unwrap() &= !(0b1 << i);
             }
         }
     });
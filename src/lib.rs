@@ -1,14 +1,38 @@
+pub mod analysis;
+pub mod assembler;
+#[cfg(feature = "web")]
 mod debugger;
-mod emulator;
+pub mod disassembler;
+pub mod emulator;
+pub mod error;
+pub mod expression;
+#[cfg(feature = "web")]
 mod handlers;
+pub mod host;
+pub mod instruction;
+#[cfg(feature = "web")]
+mod jit;
+#[cfg(feature = "web")]
 mod keys;
+pub mod metadata;
+pub mod movie;
+pub mod palette;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+#[cfg(feature = "web")]
 mod runner;
+#[cfg(feature = "web")]
 mod ui;
 
+#[cfg(feature = "web")]
 use std::panic;
+#[cfg(feature = "web")]
 use wasm_bindgen::prelude::wasm_bindgen;
+#[cfg(feature = "web")]
 extern crate console_error_panic_hook;
 
+#[cfg(feature = "web")]
 #[wasm_bindgen(start)]
 pub fn start() {
     init_console();
@@ -17,6 +41,7 @@ pub fn start() {
     handlers::trigger_select_splash_screen();
 }
 
+#[cfg(feature = "web")]
 fn init_console() {
     console_log::init_with_level(log::Level::Debug).expect("Couldn't initialize the console");
     panic::set_hook(Box::new(console_error_panic_hook::hook));
@@ -0,0 +1,349 @@
+//! The catalog of the bundled roms and the settings of known roms, looked up by the SHA-1
+//! of the rom.
+//!
+//! The database is `roms/metadata.json`, built into the emulator. Adding a rom to the page
+//! is copying it into `roms` and adding it here. It is an object keyed by the lowercase
+//! hexadecimal SHA-1 of each rom:
+//!
+//! ```json
+//! {
+//!   "0123456789abcdef0123456789abcdef01234567": {
+//!     "file": "game.ch8",
+//!     "title": "A Game",
+//!     "authors": ["Someone"],
+//!     "description": "What the game is about and how it's played.",
+//!     "platform": "chip8",
+//!     "options": { "tickrate": 20, "fillColor": "#FFCC00", "shiftQuirks": true },
+//!     "keys": [5, 7, 8, 9],
+//!     "thumbnail": "thumbnails/game.svg"
+//!   }
+//! }
+//! ```
+//!
+//! Only `file` and `title` are required, `file` and `thumbnail` are relative to `roms`. The
+//! `thumbnails` binary draws the screen of every rom that has a `thumbnail` after a few
+//! seconds of running. `platform` is `chip8`, `schip` or `xochip`, and
+//! `options` uses the names of the [Chip-8 Archive](https://github.com/JohnEarnest/chip8Archive)
+//! `programs.json`, so its entries can be copied over:
+//!
+//! - `tickrate`: instructions per frame
+//! - `backgroundColor`, `fillColor`, `fillColor2`, `blendColor`: the colors of no plane, the
+//!   first, the second and both, as `#RRGGBB`
+//! - `shiftQuirks`, `loadStoreQuirks`, `clipQuirks`, `jumpQuirks`, `logicQuirks`,
+//!   `vBlankQuirks`: the quirks that differ from Octo, `loadStoreQuirks` means that I is
+//!   *not* incremented
+//!
+//! `keys` are the controls, the CHIP-8 keys the game reads. The `controls` binary plays every
+//! rom, pressing the keys one after the other, and adds the keys it reads:
+//!
+//! ```sh
+//! cargo run --target x86_64-unknown-linux-gnu --no-default-features --features native \
+//!     --bin controls
+//! ```
+//!
+//! The `archive` binary copies the title, authors, description, platform and options of a
+//! `programs.json` into the roms whose file has the name of the archive entry:
+//!
+//! ```sh
+//! cargo run --target x86_64-unknown-linux-gnu --no-default-features --features native \
+//!     --bin archive -- path/to/programs.json
+//! ```
+
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+
+use serde::{Deserialize, Serialize, Serializer};
+use sha1_smol::Sha1;
+
+use crate::{emulator::Platform, palette::Palette, quirks::Preset, quirks::Quirks};
+
+/// Everything that is known about a rom
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Metadata {
+    pub file: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    #[serde(default, skip_serializing_if = "Options::is_empty")]
+    pub options: Options,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+/// The settings the rom was made for, missing ones are left as they are
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tickrate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_color2: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blend_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shift_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_store_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clip_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jump_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logic_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub v_blank_quirks: Option<bool>,
+}
+
+impl Options {
+    fn is_empty(&self) -> bool {
+        *self == Options::default()
+    }
+
+    fn has_quirks(&self) -> bool {
+        [
+            self.shift_quirks,
+            self.load_store_quirks,
+            self.clip_quirks,
+            self.jump_quirks,
+            self.logic_quirks,
+            self.v_blank_quirks,
+        ]
+        .iter()
+        .any(Option::is_some)
+    }
+}
+
+/// The roms by the hexadecimal SHA-1 of their bytes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Database(HashMap<String, Metadata>);
+
+/// An entry of the archive's `programs.json`, only the fields the database has
+#[derive(Deserialize)]
+struct ArchiveProgram {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    desc: Option<String>,
+    platform: Option<String>,
+    #[serde(default)]
+    options: Options,
+}
+
+impl Database {
+    pub fn parse(json: &str) -> Result<Database, String> {
+        serde_json::from_str(json).map_err(|e| format!("The rom metadata is invalid: {e}"))
+    }
+
+    /// The database of `roms/metadata.json`
+    pub fn bundled() -> &'static Database {
+        static DATABASE: OnceLock<Database> = OnceLock::new();
+        DATABASE.get_or_init(|| {
+            Database::parse(include_str!("../roms/metadata.json"))
+                .expect("The bundled rom metadata is valid")
+        })
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&Metadata> {
+        self.0.get(&Sha1::from(rom).digest().to_string())
+    }
+
+    /// The roms in the order of the picker: the ones in `roms` by name, then the ones in
+    /// its folders
+    pub fn catalog(&self) -> Vec<&Metadata> {
+        let mut catalog: Vec<&Metadata> = self.0.values().collect();
+        catalog.sort_by_key(|metadata| catalog_order(metadata));
+        catalog
+    }
+
+    /// The roms of the catalog whose title, file, authors or description contain the text,
+    /// ignoring case, and that are made for the platform when one is given
+    pub fn search(&self, text: &str, platform: Option<Platform>) -> Vec<&Metadata> {
+        let text = text.trim().to_lowercase();
+        self.catalog()
+            .into_iter()
+            .filter(|metadata| platform.is_none() || metadata.platform() == platform)
+            .filter(|metadata| text.is_empty() || metadata.mentions(&text))
+            .collect()
+    }
+
+    /// Copies the entries of the Chip-8 Archive `programs.json` into the roms whose file
+    /// name, without the extension, is the name of the entry. A platform the database
+    /// doesn't know is left as it was. Returns the files that changed, by name.
+    pub fn import_archive(&mut self, programs: &str) -> Result<Vec<String>, String> {
+        let programs: HashMap<String, ArchiveProgram> = serde_json::from_str(programs)
+            .map_err(|e| format!("The archive programs are invalid: {e}"))?;
+        let mut imported = Vec::new();
+        for metadata in self.0.values_mut() {
+            let name = Path::new(&metadata.file)
+                .file_stem()
+                .and_then(|name| name.to_str());
+            let Some(program) = name.and_then(|name| programs.get(name)) else {
+                continue;
+            };
+            metadata.title = program.title.clone();
+            metadata.authors = program.authors.clone();
+            metadata.description = program.desc.clone();
+            if let Some(platform) = &program.platform {
+                if parse_platform(platform).is_some() {
+                    metadata.platform = Some(platform.clone());
+                }
+            }
+            metadata.options = program.options.clone();
+            imported.push(metadata.file.clone());
+        }
+        imported.sort();
+        Ok(imported)
+    }
+
+    /// Every rom, in no particular order, to be filled in by the tools of `src/bin`
+    pub fn roms_mut(&mut self) -> impl Iterator<Item = &mut Metadata> + '_ {
+        self.0.values_mut()
+    }
+
+    /// The database in the format of `roms/metadata.json`, in the order of the catalog
+    pub fn to_json(&self) -> String {
+        let mut entries: Vec<(&String, &Metadata)> = self.0.iter().collect();
+        entries.sort_by_key(|(_, metadata)| catalog_order(metadata));
+        serde_json::to_string_pretty(&Entries(entries)).expect("The metadata can be serialized")
+            + "\n"
+    }
+
+    /// The platforms that have roms, in the order of the presets
+    pub fn platforms(&self) -> Vec<Platform> {
+        [Platform::Chip8, Platform::SuperChip, Platform::XoChip]
+            .into_iter()
+            .filter(|&platform| self.0.values().any(|rom| rom.platform() == Some(platform)))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// the roms in `roms` by name, then the ones in its folders
+fn catalog_order(metadata: &Metadata) -> (bool, String) {
+    (metadata.file.contains('/'), metadata.file.to_lowercase())
+}
+
+/// Writes the entries as an object in their order
+struct Entries<'a>(Vec<(&'a String, &'a Metadata)>);
+
+impl Serialize for Entries<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().copied())
+    }
+}
+
+impl Metadata {
+    pub fn platform(&self) -> Option<Platform> {
+        parse_platform(self.platform.as_deref()?)
+    }
+
+    // the text is already lowercase
+    fn mentions(&self, text: &str) -> bool {
+        std::iter::once(&self.title)
+            .chain(std::iter::once(&self.file))
+            .chain(&self.authors)
+            .chain(&self.description)
+            .any(|field| field.to_lowercase().contains(text))
+    }
+
+    /// The preset of the platform, Octo runs the CHIP-8 games
+    pub fn preset(&self) -> Preset {
+        match self.platform() {
+            Some(Platform::SuperChip) => Preset::SuperChip,
+            Some(Platform::XoChip) => Preset::XoChip,
+            _ => Preset::Octo,
+        }
+    }
+
+    /// The quirks of the preset. The archive describes its quirks as changes to Octo, so
+    /// when the options give any quirk they change the quirks of Octo instead.
+    pub fn quirks(&self) -> Quirks {
+        let options = &self.options;
+        let mut quirks = if options.has_quirks() {
+            Preset::Octo.quirks()
+        } else {
+            self.preset().quirks()
+        };
+        let set = |quirk: &mut bool, option: Option<bool>| {
+            if let Some(value) = option {
+                *quirk = value;
+            }
+        };
+        set(&mut quirks.shifting, options.shift_quirks);
+        set(
+            &mut quirks.memory_increment,
+            options.load_store_quirks.map(|quirk| !quirk),
+        );
+        set(&mut quirks.clipping, options.clip_quirks);
+        set(&mut quirks.jumping, options.jump_quirks);
+        set(&mut quirks.vf_reset, options.logic_quirks);
+        set(&mut quirks.display_wait, options.v_blank_quirks);
+        quirks
+    }
+
+    /// The colors of the rom, the ones that aren't given are Octo's
+    pub fn palette(&self) -> Option<Palette> {
+        let options = &self.options;
+        if options.background_color.is_none() && options.fill_color.is_none() {
+            return None;
+        }
+        let octo = Palette::OCTO;
+        let color = |option: &Option<String>, planes: u8| {
+            option
+                .as_deref()
+                .and_then(parse_color)
+                .unwrap_or_else(|| u32::from_be_bytes(octo.color(planes)))
+        };
+        Some(Palette::new(
+            color(&options.background_color, 0b00),
+            color(&options.fill_color, 0b01),
+            color(&options.fill_color2, 0b10),
+            color(&options.blend_color, 0b11),
+        ))
+    }
+}
+
+/// The platform of a `platform` field: `chip8`, `schip` or `xochip`
+pub fn parse_platform(platform: &str) -> Option<Platform> {
+    match platform {
+        "chip8" => Some(Platform::Chip8),
+        "schip" => Some(Platform::SuperChip),
+        "xochip" => Some(Platform::XoChip),
+        _ => None,
+    }
+}
+
+/// The `platform` field of a platform, the opposite of [`parse_platform`]
+pub fn platform_id(platform: Platform) -> &'static str {
+    match platform {
+        Platform::Chip8 => "chip8",
+        Platform::SuperChip => "schip",
+        Platform::XoChip => "xochip",
+    }
+}
+
+/// Turns `#RRGGBB` into `0xRRGGBBFF`
+fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some((rgb << 8) | 0xFF)
+}
@@ -0,0 +1,253 @@
+//! Recordings of the keys pressed on every frame, to replay a run exactly.
+//!
+//! A movie starts from a save state and then stores, for every frame, the keys that were
+//! held and how many instructions ran before the timers ticked. Playing it back goes through
+//! [`Program::tick`] and [`Program::timer_tick`] like the live emulator, so the random
+//! numbers, which are in the save state, come out the same.
+//!
+//! # Format
+//!
+//! All numbers are little endian.
+//!
+//! | Size      | Field                                                   |
+//! |-----------|---------------------------------------------------------|
+//! | 4         | magic `C8MV`                                            |
+//! | 2         | format version, currently 1                             |
+//! | 20        | SHA-1 hash of the rom                                   |
+//! | 1         | quirks, see [`Quirks::to_bits`]                         |
+//! | 1         | random number generator: 0 xorshift, 1 VIP              |
+//! | 8         | seed                                                    |
+//! | 4         | save state length `s`                                   |
+//! | s         | save state the movie starts from                        |
+//! | 4         | frame count `n`                                         |
+//! | 6 * n     | frames: 2 bytes for the keys, 4 for the instructions    |
+//!
+//! The hash, quirks and seed are also in the save state, they are repeated so a movie can
+//! be described without loading it.
+
+use crate::{
+    emulator::Program,
+    error::{EmulatorError, MovieError},
+    quirks::Quirks,
+    rng::RngKind,
+};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+const MOVIE_VERSION: u16 = 1;
+
+/// What happened during one frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// The keys held during the frame, one bit per key
+    pub keys: u16,
+    /// The instructions that ran before the timers ticked
+    pub instructions: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: [u8; 20],
+    pub quirks: Quirks,
+    pub rng_kind: RngKind,
+    pub seed: u64,
+    start: Vec<u8>,
+    pub frames: Vec<Frame>,
+}
+
+impl Movie {
+    /// An empty movie that starts at the current state of the program
+    pub fn new(program: &Program) -> Self {
+        Self {
+            rom_hash: program.rom_hash,
+            quirks: program.quirks,
+            rng_kind: program.rng.kind(),
+            seed: program.rng.seed(),
+            start: program.save_state(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Serializes the movie, see the module documentation for the format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&MOVIE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.rom_hash);
+        bytes.push(self.quirks.to_bits());
+        bytes.push(match self.rng_kind {
+            RngKind::Xorshift => 0,
+            RngKind::Vip => 1,
+        });
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.start.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.start);
+        bytes.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for frame in &self.frames {
+            bytes.extend_from_slice(&frame.keys.to_le_bytes());
+            bytes.extend_from_slice(&frame.instructions.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, MovieError> {
+        let bytes = &mut bytes;
+        if take(bytes, 4)? != MAGIC {
+            return Err(MovieError::NotAMovie);
+        }
+        let version = u16::from_le_bytes(take_array(bytes)?);
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let rom_hash = take_array(bytes)?;
+        let quirks = Quirks::from_bits(take_array::<1>(bytes)?[0]);
+        let rng_kind = match take_array::<1>(bytes)?[0] {
+            0 => RngKind::Xorshift,
+            1 => RngKind::Vip,
+            _ => return Err(MovieError::Invalid("unknown random number generator")),
+        };
+        let seed = u64::from_le_bytes(take_array(bytes)?);
+        let start_length = u32::from_le_bytes(take_array(bytes)?) as usize;
+        let start = take(bytes, start_length)?.to_vec();
+        let frame_count = u32::from_le_bytes(take_array(bytes)?) as usize;
+        let frames = (0..frame_count)
+            .map(|_| {
+                Ok(Frame {
+                    keys: u16::from_le_bytes(take_array(bytes)?),
+                    instructions: u32::from_le_bytes(take_array(bytes)?),
+                })
+            })
+            .collect::<Result<Vec<Frame>, MovieError>>()?;
+        Ok(Self {
+            rom_hash,
+            quirks,
+            rng_kind,
+            seed,
+            start,
+            frames,
+        })
+    }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], length: usize) -> Result<&'a [u8], MovieError> {
+    if bytes.len() < length {
+        return Err(MovieError::Truncated);
+    }
+    let (taken, rest) = bytes.split_at(length);
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn take_array<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], MovieError> {
+    Ok(take(bytes, N)?.try_into().expect("the length was checked"))
+}
+
+/// Adds the frames to a movie as the program runs
+pub struct Recorder {
+    movie: Movie,
+    instructions: u32,
+}
+
+impl Recorder {
+    pub fn new(program: &Program) -> Self {
+        Self {
+            movie: Movie::new(program),
+            instructions: 0,
+        }
+    }
+
+    /// Counts the instructions that ran in the current frame
+    pub fn count_instructions(&mut self, count: usize) {
+        self.instructions = self.instructions.saturating_add(count as u32);
+    }
+
+    /// Call right after [`Program::timer_tick`], with the keys that were held in the frame
+    pub fn end_frame(&mut self, keys: u16) {
+        self.movie.frames.push(Frame {
+            keys,
+            instructions: self.instructions,
+        });
+        self.instructions = 0;
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.movie.frames.len()
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Plays a movie one frame at a time
+pub struct Playback {
+    movie: Movie,
+    frame: usize,
+    // the instructions of the frame that ran before a breakpoint stopped it
+    instructions: u32,
+    end: usize,
+}
+
+impl Playback {
+    /// Restores the state the movie starts from, the rom of the movie must be loaded
+    pub fn new(movie: Movie, program: &mut Program) -> Result<Self, MovieError> {
+        if movie.rom_hash != program.rom_hash {
+            return Err(MovieError::OtherRom);
+        }
+        program
+            .load_state(&movie.start)
+            .map_err(MovieError::State)?;
+        let end = movie.frames.len();
+        Ok(Self {
+            movie,
+            frame: 0,
+            instructions: 0,
+            end,
+        })
+    }
+
+    /// Stops before `frame` instead of the end of the movie
+    pub fn stop_at(&mut self, frame: usize) {
+        self.end = frame.min(self.movie.frames.len());
+    }
+
+    /// Runs the next frame. Returns false once the movie is over, the program can then be
+    /// played with the keyboard from where the movie stopped. After a breakpoint the next
+    /// call finishes the frame.
+    pub fn play_frame(&mut self, program: &mut Program) -> Result<bool, EmulatorError> {
+        if self.is_finished() {
+            return Ok(false);
+        }
+        let frame = self.movie.frames[self.frame];
+        program.pressed_keys = frame.keys;
+        while self.instructions < frame.instructions {
+            program.tick()?;
+            self.instructions += 1;
+        }
+        self.instructions = 0;
+        self.frame += 1;
+        program.timer_tick();
+        Ok(true)
+    }
+
+    /// How many frames were played
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// How many frames will be played
+    pub fn len(&self) -> usize {
+        self.end
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end == 0
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.end
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+}
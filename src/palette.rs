@@ -0,0 +1,74 @@
+//! The colors the display is shown with. The emulator only knows which planes a pixel is
+//! on, the palette turns that into RGBA when the frame is presented.
+
+/// Four colors indexed by the planes that are on: bit 0 is the first plane and bit 1 the
+/// second. Only the first two are used outside of XO-CHIP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    colors: [[u8; 4]; 4],
+}
+
+impl Palette {
+    /// The colors of the page, yellow pixels on a dark background
+    pub const GRUVBOX: Palette = Palette::new(0x1d2021ff, 0xfabd2fff, 0xcc2412ff, 0x458588ff);
+    /// Dark pixels on a yellow background
+    pub const GRUVBOX_LIGHT: Palette = Palette::new(0xfabd2fff, 0x1d2021ff, 0xcc2412ff, 0x458588ff);
+    /// White pixels on black, like a television
+    pub const CLASSIC: Palette = Palette::new(0x000000ff, 0xffffffff, 0xaaaaaaff, 0x555555ff);
+    /// The defaults of the Octo assembler
+    pub const OCTO: Palette = Palette::new(0x996600ff, 0xffcc00ff, 0xff6600ff, 0x662200ff);
+
+    pub const ALL: [(&'static str, Palette); 4] = [
+        ("gruvbox", Palette::GRUVBOX),
+        ("gruvbox-light", Palette::GRUVBOX_LIGHT),
+        ("classic", Palette::CLASSIC),
+        ("octo", Palette::OCTO),
+    ];
+
+    /// Colors are written as `0xRRGGBBAA`
+    pub const fn new(off: u32, on: u32, second_plane: u32, both_planes: u32) -> Self {
+        Self {
+            colors: [
+                hex_to_rgba(off),
+                hex_to_rgba(on),
+                hex_to_rgba(second_plane),
+                hex_to_rgba(both_planes),
+            ],
+        }
+    }
+
+    pub fn named(name: &str) -> Option<Palette> {
+        Palette::ALL
+            .into_iter()
+            .find(|&(palette_name, _)| palette_name == name)
+            .map(|(_, palette)| palette)
+    }
+
+    pub fn color(&self, planes: u8) -> [u8; 4] {
+        self.colors[(planes & 0b11) as usize]
+    }
+
+    /// Turns the pixels of [`Program::get_display`](crate::emulator::Program::get_display)
+    /// into RGBA bytes
+    pub fn render(&self, pixels: &[u8]) -> Vec<u8> {
+        pixels
+            .iter()
+            .flat_map(|&planes| self.color(planes))
+            .collect()
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::GRUVBOX
+    }
+}
+
+const fn hex_to_rgba(hex: u32) -> [u8; 4] {
+    [
+        ((hex & 0xFF000000) >> 24) as u8,
+        ((hex & 0x00FF0000) >> 16) as u8,
+        ((hex & 0x0000FF00) >> 8) as u8,
+        (hex & 0x000000FF) as u8,
+    ]
+}
@@ -0,0 +1,172 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::emulator::Platform;
+
+/// The behaviors that changed between the different chip 8 interpreters.
+/// https://github.com/Timendus/chip8-test-suite#quirks-test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub vf_reset: bool,
+    /// FX55 and FX65 increment I for every register
+    pub memory_increment: bool,
+    /// DXYN waits for the vertical blank before drawing, so only 60 sprites are drawn per second
+    pub display_wait: bool,
+    /// sprites are clipped at the edges of the screen instead of wrapping around
+    pub clipping: bool,
+    /// 8XY6 and 8XYE shift VX in place instead of shifting VY into VX
+    pub shifting: bool,
+    /// BNNN becomes BXNN and jumps to XNN + VX instead of NNN + V0
+    pub jumping: bool,
+}
+
+impl Quirks {
+    /// Packs the quirks in a byte, in the order of the fields starting from the least significant bit
+    pub fn to_bits(self) -> u8 {
+        [
+            self.vf_reset,
+            self.memory_increment,
+            self.display_wait,
+            self.clipping,
+            self.shifting,
+            self.jumping,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, &quirk)| bits | ((quirk as u8) << i))
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        let quirk = |i: u8| (bits >> i) & 0b1 == 1;
+        Self {
+            vf_reset: quirk(0),
+            memory_increment: quirk(1),
+            display_wait: quirk(2),
+            clipping: quirk(3),
+            shifting: quirk(4),
+            jumping: quirk(5),
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Preset::default().quirks()
+    }
+}
+
+/// Named sets of quirks for the interpreters that games were written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Preset {
+    /// The original interpreter on the COSMAC VIP
+    Vip,
+    /// The HP48 calculator interpreter
+    Chip48,
+    /// SUPER-CHIP 1.1 for the HP48
+    SuperChip,
+    /// John Earnest's extension of SUPER-CHIP
+    XoChip,
+    /// The defaults of the Octo assembler, which most of the Chip-8 Archive uses
+    #[default]
+    Octo,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [
+        Preset::Vip,
+        Preset::Chip48,
+        Preset::SuperChip,
+        Preset::XoChip,
+        Preset::Octo,
+    ];
+
+    pub const fn quirks(self) -> Quirks {
+        match self {
+            Preset::Vip => Quirks {
+                vf_reset: true,
+                memory_increment: true,
+                display_wait: true,
+                clipping: true,
+                shifting: false,
+                jumping: false,
+            },
+            Preset::Chip48 => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                display_wait: false,
+                clipping: true,
+                shifting: true,
+                jumping: true,
+            },
+            Preset::SuperChip => Quirks {
+                vf_reset: false,
+                memory_increment: false,
+                display_wait: false,
+                clipping: true,
+                shifting: true,
+                jumping: true,
+            },
+            Preset::XoChip => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                display_wait: false,
+                clipping: false,
+                shifting: false,
+                jumping: false,
+            },
+            Preset::Octo => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                display_wait: false,
+                clipping: false,
+                shifting: false,
+                jumping: false,
+            },
+        }
+    }
+
+    /// The instructions the games of this preset expect
+    pub const fn platform(self) -> Platform {
+        match self {
+            Preset::Vip | Preset::Chip48 => Platform::Chip8,
+            Preset::SuperChip => Platform::SuperChip,
+            Preset::XoChip | Preset::Octo => Platform::XoChip,
+        }
+    }
+
+    /// The preset that matches a platform the best
+    pub const fn for_platform(platform: Platform) -> Preset {
+        match platform {
+            Platform::Chip8 => Preset::Vip,
+            Platform::SuperChip => Preset::SuperChip,
+            Platform::XoChip => Preset::XoChip,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Preset::Vip => "vip",
+            Preset::Chip48 => "chip-48",
+            Preset::SuperChip => "super-chip",
+            Preset::XoChip => "xo-chip",
+            Preset::Octo => "octo",
+        }
+    }
+}
+
+impl Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == s)
+            .ok_or_else(|| format!("There is no quirk preset named '{s}'"))
+    }
+}
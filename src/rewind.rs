@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+
+use crate::emulator::Program;
+
+/// A bounded history of the program, one snapshot per frame, to play the game backwards.
+///
+/// Only the newest save state is kept whole. Every older frame is a delta that turns the
+/// frame after it back into that frame, so the oldest frame can be dropped at any time.
+/// Consecutive frames barely change, which keeps the deltas small.
+pub struct Rewind {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+// the first byte of a delta
+const XOR_DELTA: u8 = 0;
+const FULL_STATE: u8 = 1;
+
+impl Rewind {
+    /// Keeps at most `capacity` frames before the latest one
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Saves the current frame
+    pub fn record(&mut self, program: &Program) {
+        let state = program.save_state();
+        if let Some(previous) = self.latest.replace(state) {
+            let latest = self.latest.as_ref().expect("the state was just saved");
+            self.deltas.push_back(encode_delta(latest, &previous));
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+    }
+
+    /// Restores the frame before the latest one.
+    /// Returns false if there is no older frame, the program is then at the oldest frame.
+    pub fn step_back(&mut self, program: &mut Program) -> bool {
+        let Some(latest) = self.latest.as_mut() else {
+            return false;
+        };
+        let stepped = match self.deltas.pop_back() {
+            Some(delta) => {
+                *latest = decode_delta(latest, &delta);
+                true
+            }
+            None => false,
+        };
+        program
+            .load_state(latest)
+            .expect("Rewind states are always valid");
+        stepped
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+
+    /// How many frames can be rewound
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// The bytes used by the history
+    pub fn size(&self) -> usize {
+        let latest = self.latest.as_ref().map_or(0, Vec::len);
+        latest + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+/// Encodes how to get `to` from `from`: the runs of bytes that didn't change are skipped,
+/// the others are stored xored with `from`
+fn encode_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+    if from.len() != to.len() {
+        // the resolution or the platform changed, it's rare enough to store everything
+        let mut delta = vec![FULL_STATE];
+        delta.extend_from_slice(to);
+        return delta;
+    }
+    let mut delta = vec![XOR_DELTA];
+    let mut i = 0;
+    while i < to.len() {
+        let unchanged = (i..to.len()).take_while(|&j| from[j] == to[j]).count();
+        i += unchanged;
+        let changed = (i..to.len()).take_while(|&j| from[j] != to[j]).count();
+        write_varint(&mut delta, unchanged);
+        write_varint(&mut delta, changed);
+        delta.extend((i..i + changed).map(|j| from[j] ^ to[j]));
+        i += changed;
+    }
+    delta
+}
+
+fn decode_delta(from: &[u8], delta: &[u8]) -> Vec<u8> {
+    let (&kind, mut rest) = delta.split_first().expect("A delta is never empty");
+    if kind == FULL_STATE {
+        return rest.to_vec();
+    }
+    let mut to = from.to_vec();
+    let mut i = 0;
+    while !rest.is_empty() {
+        i += read_varint(&mut rest);
+        let changed = read_varint(&mut rest);
+        let (xored, remaining) = rest.split_at(changed);
+        for (byte, xor) in to[i..i + changed].iter_mut().zip(xored) {
+            *byte ^= xor;
+        }
+        i += changed;
+        rest = remaining;
+    }
+    to
+}
+
+// LEB128, 7 bits at a time starting with the least significant ones
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = bytes.split_first() {
+        *bytes = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    value
+}
@@ -0,0 +1,146 @@
+//! The random numbers of the `CXNN` instruction.
+//!
+//! The generator is part of the machine: it's seeded, and its state is saved with the rest
+//! of the program, so a run with the same seed and the same inputs always plays the same.
+
+use std::{fmt::Display, str::FromStr};
+
+/// Where the VIP interpreter kept the code its generator reads
+const VIP_PAGE: usize = 0x100;
+
+/// The algorithms `CXNN` can draw from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RngKind {
+    /// xorshift64, good enough for any game
+    #[default]
+    Xorshift,
+    /// The COSMAC VIP interpreter's generator. It keeps a 16 bit register: every `CXNN` adds
+    /// one to it, reads the byte its low half points to in page 0x100 of the memory, adds the
+    /// high half and keeps the sum there. The timer interrupt also adds one, so the numbers
+    /// depend on the timing. On the VIP that page is the interpreter's own code, here it is
+    /// whatever this machine holds there, part of the big font.
+    Vip,
+}
+
+impl RngKind {
+    pub const ALL: [RngKind; 2] = [RngKind::Xorshift, RngKind::Vip];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            RngKind::Xorshift => "xorshift",
+            RngKind::Vip => "vip",
+        }
+    }
+}
+
+impl Display for RngKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for RngKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RngKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("There is no random number generator named '{s}'"))
+    }
+}
+
+/// A seeded random number generator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    kind: RngKind,
+    seed: u64,
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64, kind: RngKind) -> Self {
+        let mut rng = Self {
+            kind,
+            seed,
+            state: 0,
+        };
+        rng.restart();
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn kind(&self) -> RngKind {
+        self.kind
+    }
+
+    /// Starts over from a new seed
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.restart();
+    }
+
+    /// Switches the algorithm and starts over from the seed
+    pub fn set_kind(&mut self, kind: RngKind) {
+        self.kind = kind;
+        self.restart();
+    }
+
+    /// Goes back to the first number of the seed
+    pub fn restart(&mut self) {
+        self.state = match self.kind {
+            // xorshift gets stuck on 0 forever
+            RngKind::Xorshift => splitmix64(self.seed).max(1),
+            // the VIP register only has 16 bits
+            RngKind::Vip => self.seed & 0xFFFF,
+        };
+    }
+
+    /// The next number, the VIP generator reads it from the `memory` of the machine
+    pub fn next_byte(&mut self, memory: &[u8]) -> u8 {
+        match self.kind {
+            RngKind::Xorshift => {
+                self.state ^= self.state << 13;
+                self.state ^= self.state >> 7;
+                self.state ^= self.state << 17;
+                (self.state >> 56) as u8
+            }
+            RngKind::Vip => {
+                let register = (self.state as u16).wrapping_add(1);
+                let [low, high] = register.to_le_bytes();
+                let number = memory[VIP_PAGE | low as usize].wrapping_add(high);
+                self.state = u16::from_le_bytes([low, number]) as u64;
+                number
+            }
+        }
+    }
+
+    /// Called 60 times per second with the timers
+    pub fn timer_tick(&mut self) {
+        if self.kind == RngKind::Vip {
+            self.state = (self.state as u16).wrapping_add(1) as u64;
+        }
+    }
+
+    /// The internal state, only meant to be saved and restored
+    pub(crate) fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub(crate) fn restore(&mut self, kind: RngKind, seed: u64, state: u64) {
+        self.kind = kind;
+        self.seed = seed;
+        self.state = state;
+    }
+}
+
+// spreads close seeds far apart, so seeds 1 and 2 don't start with similar numbers
+const fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -1,87 +1,293 @@
-use std::{cell::RefCell, rc::Rc, sync::Mutex};
+use std::{cell::RefCell, rc::Rc, sync::Mutex, time::Duration};
 
+use log::{error, info};
 use wasm_bindgen::{prelude::Closure, JsCast};
 use web_sys::CanvasRenderingContext2d;
-use web_time::{Duration, Instant};
 
 use crate::{
-    debugger::INTERVAL_HANDLE,
-    emulator::{get_program, Program},
-    ui::{get_canvas_context, render_emulator, window},
+    debugger::{show_debugger, show_stop, INTERVAL_HANDLE},
+    emulator::{get_program, Program, Timing},
+    error::EmulatorError,
+    host::{Host, WebHost},
+    jit::Jit,
+    keys::KEYBOARD,
+    movie::{Playback, Recorder},
+    rewind::Rewind,
+    ui::{
+        clear_error, get_canvas_context, render_emulator, show_error, show_movie_status,
+        show_runner_stats, window,
+    },
 };
 
-const MIN_REPAINT_TIME: Duration = Duration::from_millis(16);
-pub static UPDATES_PER_SECOND: Mutex<f64> = Mutex::new(1_000.0);
+/// The timers tick 60 times per emulated second, every instruction runs between two ticks
+pub const FRAMES_PER_SECOND: u32 = 60;
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND as u64);
+// 30 seconds of frames
+const REWIND_FRAMES: usize = 30 * FRAMES_PER_SECOND as usize;
+// how long the frames of one update can take before the rest are skipped
+const FRAME_BUDGET: Duration = Duration::from_millis(12);
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+pub static INSTRUCTIONS_PER_FRAME: Mutex<u32> = Mutex::new(16);
+/// With the VIP timing the speed comes from the cost of the instructions instead
+pub static TIMING: Mutex<Timing> = Mutex::new(Timing::Fixed);
+/// The most emulated time that is made up for after the page couldn't run,
+/// anything older is dropped instead of running in one burst
+pub static MAX_CATCH_UP: Mutex<Duration> = Mutex::new(Duration::from_millis(250));
+/// Pause while the page is hidden or doesn't have the focus
+pub static PAUSE_ON_BLUR: Mutex<bool> = Mutex::new(true);
+/// Run the fixed speed frames with the compiled blocks, see `emulator/jit.rs`
+pub static JIT: Mutex<bool> = Mutex::new(false);
+pub static REWIND: Mutex<Rewind> = Mutex::new(Rewind::new(REWIND_FRAMES));
+// set while the rewind key is held
+pub static REWINDING: Mutex<bool> = Mutex::new(false);
+pub static MOVIE: Mutex<Option<MovieMode>> = Mutex::new(None);
+
+pub enum MovieMode {
+    Recording(Recorder),
+    Playing(Playback),
+}
 
 pub struct Runner {
-    last_update: Instant,
-    last_paint: Instant,
+    host: WebHost,
+    last_update: Duration,
+    // the emulated time that is owed to the program, run one frame at a time
+    accumulator: Duration,
     context: CanvasRenderingContext2d,
+    stats: Stats,
+    jit: Jit,
+}
+
+/// What the runner achieved since the last report
+struct Stats {
+    since: Duration,
+    frames: u32,
+    paints: u32,
+    instructions: u64,
+    // frames that were never emulated because the host couldn't keep up
+    dropped: u64,
+}
+
+impl Stats {
+    fn new(now: Duration) -> Self {
+        Self {
+            since: now,
+            frames: 0,
+            paints: 0,
+            instructions: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Shows the rates once per interval and starts counting again, the dropped frames
+    /// are counted since the start
+    fn report(&mut self, now: Duration) {
+        let elapsed = now.saturating_sub(self.since);
+        if elapsed < STATS_INTERVAL {
+            return;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let speed = self.frames as f64 / seconds / FRAMES_PER_SECOND as f64 * 100.0;
+        show_runner_stats(&format!(
+            "{:.0} FPS, {speed:.0}% speed, {:.0} IPS, {} frames dropped",
+            self.paints as f64 / seconds,
+            self.instructions as f64 / seconds,
+            self.dropped,
+        ));
+        *self = Self {
+            dropped: self.dropped,
+            ..Self::new(now)
+        };
+    }
 }
 
 impl Runner {
     pub fn new() -> Self {
+        let host = WebHost;
+        let now = host.now();
         Self {
-            last_update: Instant::now(),
-            last_paint: Instant::now(),
+            host,
+            last_update: now,
+            accumulator: Duration::ZERO,
             context: get_canvas_context(),
+            stats: Stats::new(now),
+            jit: Jit::new(),
+        }
+    }
+
+    /// Adds the time since the last update to the accumulator, up to the catch up limit
+    fn advance(&mut self) {
+        let now = self.host.now();
+        self.accumulator += now.saturating_sub(self.last_update);
+        self.last_update = now;
+        let max_catch_up = (*MAX_CATCH_UP.lock().unwrap()).max(FRAME_TIME);
+        if self.accumulator > max_catch_up {
+            self.drop_frames(self.accumulator - max_catch_up);
+        }
+    }
+
+    /// Forgets whole frames of the accumulator, they will never be emulated
+    fn drop_frames(&mut self, time: Duration) {
+        let frames = (time.as_nanos() / FRAME_TIME.as_nanos()) as u32;
+        self.accumulator -= FRAME_TIME * frames;
+        self.stats.dropped += frames as u64;
+    }
+
+    /// Takes a frame from the accumulator if a whole one has passed
+    fn next_frame(&mut self) -> bool {
+        if self.accumulator < FRAME_TIME {
+            return false;
         }
+        self.accumulator -= FRAME_TIME;
+        true
     }
 
     pub fn start_loop() -> Box<dyn FnOnce()> {
+        clear_error();
+        show_stop(None);
         let function = Rc::new(RefCell::new(None));
         let starter = function.clone();
         let mut runner = Runner::new();
 
         *starter.borrow_mut() = Some(Closure::new(move || {
             let mut emulator = get_program().lock().unwrap();
-            let time_since_last_update = runner.last_update.elapsed();
-            let how_many_updates = (time_since_last_update.as_secs_f64()
-                * *UPDATES_PER_SECOND.lock().unwrap())
-            .floor() as usize;
-
-            // because we can't update that fast, we'll run the updates that should've
-            // been done since the last time it was updates
-            for _ in 0..how_many_updates {
-                emulator.tick();
-            }
-
-            if runner.last_paint.elapsed() > MIN_REPAINT_TIME {
-                emulator.timer_tick();
+            let mut movie = MOVIE.lock().unwrap();
+            runner.advance();
 
+            // going back would make the movie go out of sync
+            if *REWINDING.lock().unwrap() && movie.is_none() {
+                while runner.next_frame() {
+                    REWIND.lock().unwrap().step_back(&mut emulator);
+                }
                 Runner::render(&emulator, &runner.context);
-                runner.last_paint = Instant::now();
+                request_animation_frame(function.borrow().as_ref().unwrap());
+                return;
             }
 
-            if how_many_updates != 0 {
-                runner.last_update = Instant::now();
+            let update_start = runner.host.now();
+            while runner.next_frame() {
+                match Runner::run_frame(&mut emulator, &mut movie, &mut runner.jit) {
+                    Ok(instructions) => {
+                        runner.stats.frames += 1;
+                        runner.stats.instructions += instructions as u64;
+                    }
+                    Err(
+                        e @ (EmulatorError::Breakpoint { .. } | EmulatorError::Watchpoint { .. }),
+                    ) => {
+                        // the frame goes on from the breakpoint when the loop starts again
+                        info!("{e}");
+                        INTERVAL_HANDLE.lock().unwrap().take();
+                        show_debugger();
+                        show_stop(Some(&e.to_string()));
+                        Runner::render(&emulator, &runner.context);
+                        return;
+                    }
+                    Err(e) => {
+                        // stop the loop and leave the program as it is so it can be debugged
+                        error!("{e}");
+                        show_error(&e.to_string());
+                        if let Some(MovieMode::Playing(_)) = *movie {
+                            *movie = None;
+                        }
+                        INTERVAL_HANDLE.lock().unwrap().take();
+                        Runner::render(&emulator, &runner.context);
+                        return;
+                    }
+                }
+                // the host is too slow for this speed, skip the frames it can't run
+                // instead of falling further and further behind
+                if runner.elapsed(update_start) > FRAME_BUDGET {
+                    runner.drop_frames(runner.accumulator);
+                    break;
+                }
             }
+
+            Runner::render(&emulator, &runner.context);
+            runner.stats.paints += 1;
+            runner.stats.report(runner.host.now());
             // loop and reloop
-            set_timeout(function.borrow().as_ref().unwrap());
+            request_animation_frame(function.borrow().as_ref().unwrap());
         }));
 
         Box::new(move || {
             // after this function is invocted, the starter function will be dropped
             // and there will only be one function in the rc
-            set_timeout(starter.borrow().as_ref().unwrap());
+            request_animation_frame(starter.borrow().as_ref().unwrap());
         })
     }
 
+    fn elapsed(&self, since: Duration) -> Duration {
+        self.host.now().saturating_sub(since)
+    }
+
+    /// Runs the instructions of one frame and ticks the timers, or plays the next frame
+    /// of the movie. Returns how many instructions ran.
+    fn run_frame(
+        emulator: &mut Program,
+        movie: &mut Option<MovieMode>,
+        jit: &mut Jit,
+    ) -> Result<u32, EmulatorError> {
+        if let Some(MovieMode::Playing(playback)) = movie {
+            if playback.play_frame(emulator)? {
+                show_movie_status(&format!(
+                    "Playing frame {} of {}",
+                    playback.frame(),
+                    playback.len()
+                ));
+                REWIND.lock().unwrap().record(emulator);
+                return Ok(playback.movie().frames[playback.frame() - 1].instructions);
+            }
+            // the keyboard takes over from here
+            info!("The movie is over");
+            *movie = None;
+            show_movie_status("");
+        }
+
+        let instructions = match *TIMING.lock().unwrap() {
+            Timing::Fixed => {
+                let instructions = *INSTRUCTIONS_PER_FRAME.lock().unwrap();
+                let use_jit = *JIT.lock().unwrap();
+                while emulator.frame_progress < instructions {
+                    emulator.frame_progress += if use_jit {
+                        jit.step(emulator, instructions - emulator.frame_progress)?
+                    } else {
+                        emulator.tick()?;
+                        1
+                    };
+                }
+                emulator.frame_progress = 0;
+                instructions
+            }
+            Timing::Vip => emulator.run_vip_frame()?,
+        };
+        emulator.timer_tick();
+        if let Some(MovieMode::Recording(recorder)) = movie {
+            recorder.count_instructions(instructions as usize);
+            recorder.end_frame(emulator.pressed_keys);
+            show_movie_status(&format!("Recording frame {}", recorder.frame_count()));
+        }
+        // the keys only change between frames, so a movie can replay them exactly
+        emulator.pressed_keys = *KEYBOARD.lock().unwrap();
+        REWIND.lock().unwrap().record(emulator);
+        Ok(instructions)
+    }
+
     fn render(emulator: &Program, ctx: &CanvasRenderingContext2d) {
         render_emulator(emulator, ctx);
     }
 }
 
-fn set_timeout(f: &Closure<dyn FnMut()>) {
+fn request_animation_frame(f: &Closure<dyn FnMut()>) {
     let window = window();
     let mut old_handle = INTERVAL_HANDLE.lock().unwrap();
 
-    if old_handle.is_some() {
-        window.clear_interval_with_handle(old_handle.unwrap());
+    if let Some(handle) = old_handle.take() {
+        window
+            .cancel_animation_frame(handle)
+            .expect("Couldn't cancel the animation frame");
     }
 
     let new_handle = window
-        .set_timeout_with_callback(f.as_ref().unchecked_ref())
+        .request_animation_frame(f.as_ref().unchecked_ref())
         .expect("Couldn't register 'request_animation_frame'");
     old_handle.replace(new_handle);
 }
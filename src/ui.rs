@@ -1,19 +1,39 @@
-use std::str::FromStr;
+use std::{cell::RefCell, str::FromStr, sync::Mutex};
 
 use crate::{
+    analysis::Analysis,
     debugger::{render_debugger, RENDER_DEBUGGER},
-    emulator::Program,
+    emulator::{Platform, Program},
+    keys::KEYS,
+    metadata::{platform_id, Metadata},
+    palette::Palette,
+    rng::Rng,
 };
 use wasm_bindgen::{Clamped, JsCast};
 use web_sys::{
-    CanvasRenderingContext2d, Document, Element, HtmlAudioElement, HtmlCanvasElement, ImageData,
-    Node,
+    js_sys::{Array, Uint8Array},
+    AudioBufferSourceNode, AudioContext, AudioScheduledSourceNode, Blob, CanvasRenderingContext2d,
+    Document, Element, GainNode, HtmlAnchorElement, HtmlAudioElement, HtmlCanvasElement,
+    HtmlImageElement, HtmlInputElement, HtmlOptionElement, HtmlSelectElement, ImageData, Node,
+    Storage, Url,
 };
 
+/// The colors the display is presented with, can be changed at any time
+pub static PALETTE: Mutex<Palette> = Mutex::new(Palette::GRUVBOX);
+
 pub fn render_emulator(program: &Program, ctx: &CanvasRenderingContext2d) {
-    let width = Program::width() as u32;
+    let width = program.width() as u32;
+    let height = program.height() as u32;
+
+    // follow the resolution switches of SUPER-CHIP
+    let canvas = canvas();
+    if canvas.width() != width || canvas.height() != height {
+        canvas.set_width(width);
+        canvas.set_height(height);
+    }
 
-    let data = ImageData::new_with_u8_clamped_array(Clamped(&program.get_display()), width)
+    let rgba = PALETTE.lock().unwrap().render(program.get_display());
+    let data = ImageData::new_with_u8_clamped_array(Clamped(&rgba), width)
         .expect("Could not create the image data");
 
     ctx.put_image_data(&data, 0.0, 0.0)
@@ -23,6 +43,153 @@ pub fn render_emulator(program: &Program, ctx: &CanvasRenderingContext2d) {
     }
 }
 
+/// Shows why the emulator stopped under the canvas
+pub fn show_error(message: &str) {
+    let element: Element = get_element(&document(), "#error-message");
+    element.set_text_content(Some(message));
+    remove_class_name(&element, "off");
+}
+
+/// Shows which frame of the movie is recorded or played, nothing if the text is empty
+pub fn show_movie_status(status: &str) {
+    let element: Element = get_element(&document(), "#movie-status");
+    element.set_text_content(Some(status));
+}
+
+/// Shows how fast the emulator really runs
+pub fn show_runner_stats(stats: &str) {
+    let element: Element = get_element(&document(), "#runner-stats");
+    element.set_text_content(Some(stats));
+}
+
+/// Shows what the loaded rom needs under the canvas
+pub fn show_compatibility(analysis: &Analysis) {
+    let document = document();
+    let summary: Element = get_element(&document, "#compatibility-summary");
+    summary.set_text_content(Some(&analysis.to_string()));
+    let list: Element = get_element(&document, "#compatibility-findings");
+    list.set_inner_html("");
+    for finding in analysis.findings() {
+        let item = document.create_element("li").unwrap();
+        item.set_text_content(Some(&finding));
+        list.append_child(&item).unwrap();
+    }
+    remove_class_name(&get_element(&document, "#compatibility"), "off");
+}
+
+/// Shows what is known about the rom next to the canvas, nothing for unknown roms
+pub fn show_metadata(metadata: Option<&Metadata>) {
+    let document = document();
+    let info: Element = get_element(&document, "#rom-info");
+    let Some(metadata) = metadata else {
+        add_class_name(&info, "off");
+        return;
+    };
+    let set_text = |selector: &str, text: &str| {
+        let element: Element = get_element(&document, selector);
+        element.set_text_content(Some(text));
+    };
+    set_text("#rom-title", &metadata.title);
+    set_text(
+        "#rom-platform",
+        metadata.platform().map(Platform::name).unwrap_or_default(),
+    );
+    set_text("#rom-authors", &metadata.authors.join(", "));
+    set_text(
+        "#rom-description",
+        metadata.description.as_deref().unwrap_or_default(),
+    );
+    let controls: Vec<String> = metadata
+        .keys
+        .iter()
+        .filter_map(|&key| KEYS.get(key as usize).map(|code| (key, code)))
+        .map(|(key, code)| {
+            let name = code.trim_start_matches("Key").trim_start_matches("Digit");
+            format!("{key:X} ({name})")
+        })
+        .collect();
+    if controls.is_empty() {
+        set_text("#rom-controls", "");
+    } else {
+        set_text("#rom-controls", &format!("Keys: {}", controls.join(" ")));
+    }
+    let thumbnail: HtmlImageElement = get_element(&document, "#rom-thumbnail");
+    match &metadata.thumbnail {
+        Some(path) => {
+            thumbnail.set_src(&format!("roms/{path}"));
+            remove_class_name(&thumbnail, "off");
+        }
+        None => add_class_name(&thumbnail, "off"),
+    }
+    remove_class_name(&info, "off");
+}
+
+/// Adds the platforms after "All platforms" in the platform filter, so only the ones that
+/// have roms can be picked
+pub fn show_platforms(platforms: &[Platform]) {
+    let document = document();
+    let filter: HtmlSelectElement = get_element(&document, "#platform-filter");
+    for &platform in platforms {
+        let option: HtmlOptionElement = document
+            .create_element("option")
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+        option.set_value(platform_id(platform));
+        option.set_text(platform.name());
+        filter.append_child(&option).unwrap();
+    }
+}
+
+/// Fills the rom selector with the roms. When the selected rom isn't one of them, the
+/// selector shows how many there are until one is picked.
+pub fn show_catalog(roms: &[&Metadata], selected: &str) {
+    let document = document();
+    let selector: HtmlSelectElement = get_element(&document, "#rom-selector");
+    selector.set_inner_html("");
+    let add_option = |value: &str, text: &str| {
+        let option: HtmlOptionElement = document
+            .create_element("option")
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+        option.set_value(value);
+        option.set_text(text);
+        selector.append_child(&option).unwrap();
+        option
+    };
+    if !roms.iter().any(|metadata| metadata.file == selected) {
+        let placeholder = add_option("", &format!("{} roms", roms.len()));
+        placeholder.set_disabled(true);
+    }
+    for metadata in roms {
+        let option = add_option(&metadata.file, &metadata.title);
+        if let Some(description) = &metadata.description {
+            option.set_title(description);
+        }
+    }
+    selector.set_value(selected);
+    if selector.selected_index() < 0 {
+        selector.set_selected_index(0);
+    }
+}
+
+pub fn clear_error() {
+    let element: Element = get_element(&document(), "#error-message");
+    if !element.class_name().contains("off") {
+        add_class_name(&element, "off");
+    }
+}
+
+/// Shows the seed in hexadecimal so it can be copied into a bug report
+pub fn show_rng(rng: &Rng) {
+    let document = document();
+    let seed: HtmlInputElement = get_element(&document, "#rng-seed");
+    seed.set_value(&format!("{:x}", rng.seed()));
+    let kind: HtmlSelectElement = get_element(&document, "#rng-kind");
+    kind.set_value(rng.kind().name());
+}
+
 pub fn get_element<T: JsCast>(document: &Document, id: &str) -> T {
     let type_name = std::any::type_name::<T>();
     document
@@ -67,13 +234,129 @@ fn audio() -> HtmlAudioElement {
 // the audio is continuously playing, we only unmute it to have a sound.
 // this is faster and easier than having play/pause
 pub fn beep() {
-    audio().set_muted(false);
+    if !set_pattern_volume(1.0) {
+        audio().set_muted(false);
+    }
 }
 
 pub fn stop_beep() {
+    set_pattern_volume(0.0);
     audio().set_muted(true);
 }
 
+/// Plays the XO-CHIP audio pattern in a loop, muted until the next beep.
+struct PatternAudio {
+    context: AudioContext,
+    gain: GainNode,
+    source: Option<AudioBufferSourceNode>,
+}
+
+thread_local! {
+    // audio nodes can't be shared between threads, but there is only one thread on the web anyway
+    static PATTERN_AUDIO: RefCell<Option<PatternAudio>> = const { RefCell::new(None) };
+}
+
+impl PatternAudio {
+    fn new() -> Self {
+        let context = AudioContext::new().expect("Could not create an audio context");
+        let gain = context.create_gain().expect("Could not create a gain node");
+        gain.gain().set_value(0.0);
+        gain.connect_with_audio_node(&context.destination())
+            .expect("Could not connect the gain to the speakers");
+        Self {
+            context,
+            gain,
+            source: None,
+        }
+    }
+
+    fn play(&mut self, pattern: &[u8; 16], pitch: u8) {
+        if let Some(old_source) = self.source.take() {
+            let _ = AudioScheduledSourceNode::stop(&old_source);
+        }
+        // the pattern is 128 bits played at 4000 * 2^((pitch - 64) / 48) bits per second
+        let pattern_rate = 4000.0 * 2_f32.powf((pitch as f32 - 64.0) / 48.0);
+        let sample_rate = self.context.sample_rate();
+        let length = ((128.0 * sample_rate / pattern_rate) as usize).max(1);
+        let samples: Vec<f32> = (0..length)
+            .map(|i| {
+                let bit = (i as f32 * pattern_rate / sample_rate) as usize % 128;
+                let is_on = (pattern[bit / 8] >> (7 - bit % 8)) & 0b1 == 1;
+                if is_on {
+                    0.25
+                } else {
+                    -0.25
+                }
+            })
+            .collect();
+        let buffer = self
+            .context
+            .create_buffer(1, length as u32, sample_rate)
+            .expect("Could not create the audio pattern buffer");
+        buffer
+            .copy_to_channel(&samples, 0)
+            .expect("Could not copy the audio pattern");
+        let source = self
+            .context
+            .create_buffer_source()
+            .expect("Could not create the audio pattern source");
+        source.set_buffer(Some(&buffer));
+        source.set_loop(true);
+        source
+            .connect_with_audio_node(&self.gain)
+            .expect("Could not connect the audio pattern");
+        source.start().expect("Could not start the audio pattern");
+        self.source = Some(source);
+    }
+}
+
+pub fn set_audio_pattern(pattern: Option<[u8; 16]>, pitch: u8) {
+    PATTERN_AUDIO.with_borrow_mut(|audio| match pattern {
+        Some(pattern) => audio
+            .get_or_insert_with(PatternAudio::new)
+            .play(&pattern, pitch),
+        None => {
+            if let Some(source) = audio.as_mut().and_then(|audio| audio.source.take()) {
+                let _ = AudioScheduledSourceNode::stop(&source);
+            }
+        }
+    });
+}
+
+/// Returns false if there is no pattern to play
+fn set_pattern_volume(volume: f32) -> bool {
+    PATTERN_AUDIO.with_borrow(|audio| match audio {
+        Some(audio) if audio.source.is_some() => {
+            // browsers only allow audio after the user interacted with the page
+            let _ = audio.context.resume();
+            audio.gain.gain().set_value(volume);
+            true
+        }
+        _ => false,
+    })
+}
+
+/// Makes the browser download the bytes as a file
+pub fn download(bytes: &[u8], file_name: &str) {
+    let parts = Array::of1(&Uint8Array::from(bytes));
+    let blob = Blob::new_with_u8_array_sequence(&parts).expect("Could not create the blob");
+    let url = Url::create_object_url_with_blob(&blob).expect("Could not create the blob url");
+    let link: HtmlAnchorElement = document()
+        .create_element("a")
+        .expect("Could not create a link")
+        .dyn_into()
+        .expect("Could not dyn into a link");
+    link.set_href(&url);
+    link.set_download(file_name);
+    link.click();
+    Url::revoke_object_url(&url).expect("Could not revoke the blob url");
+}
+
+/// The storage of the page, which the browser can refuse to give
+pub fn local_storage() -> Option<Storage> {
+    window().local_storage().ok().flatten()
+}
+
 pub fn canvas() -> HtmlCanvasElement {
     document()
         .query_selector("canvas")
@@ -0,0 +1,76 @@
+use chip_8::{analysis::analyze, assembler::assemble, emulator::Platform, quirks::Preset};
+
+fn analyze_source(source: &str) -> chip_8::analysis::Analysis {
+    analyze(&assemble(source).unwrap().rom)
+}
+
+#[test]
+fn the_instructions_choose_the_platform() {
+    let chip_8 = analyze_source(": main  v0 := 1  jump main");
+    assert_eq!(chip_8.platform(), Platform::Chip8);
+    assert_eq!(chip_8.preset(), Some(Preset::Vip));
+
+    let super_chip = analyze_source(": main  hires  scroll-left  jump main");
+    assert_eq!(super_chip.super_chip_instructions.len(), 2);
+    assert_eq!(super_chip.preset(), Some(Preset::SuperChip));
+
+    // the data after the jump is never reached, so it doesn't count
+    let xo_chip = analyze_source(": main  plane 2  jump main  0xF0 0x00");
+    assert_eq!(xo_chip.xo_chip_instructions.len(), 1);
+    assert_eq!(xo_chip.preset(), Some(Preset::XoChip));
+
+    let large = analyze(&[0x12, 0x00].repeat(0x800));
+    assert_eq!(large.platform(), Platform::XoChip);
+}
+
+#[test]
+fn a_plain_chip_8_rom_gets_a_chip_8_preset() {
+    let analysis = analyze_source(
+        "
+        : main
+          clear
+          i := dot
+          sprite v0 v1 1
+          v0 += 1
+          jump main
+        : dot
+          0x80",
+    );
+    let preset = analysis.preset().unwrap();
+    assert_eq!(preset.platform(), Platform::Chip8);
+    assert_eq!(preset.platform(), analysis.platform());
+    assert!(analysis.to_string().starts_with("Looks like CHIP-8"));
+}
+
+#[test]
+fn the_code_that_depends_on_quirks_is_found() {
+    let analysis = analyze_source(
+        "
+        : main
+          v1 >>= v2
+          v1 <<= v1
+          i := data
+          load v1
+          load v1
+          i := data
+          save v1
+          jump0 main
+        : data
+          0 0",
+    );
+    assert_eq!(analysis.shifts, [0x200]);
+    // the second load continues where the first one left I
+    assert_eq!(analysis.index_reuses, [0x206]);
+    assert_eq!(analysis.jump_offsets, [0x20E]);
+}
+
+#[test]
+fn machine_code_calls_are_unsupported() {
+    // a subroutine that calls the machine code at 0x123, and a placeholder after the call
+    // that is written at runtime
+    let analysis = analyze(&[0x22, 0x04, 0x00, 0x00, 0x01, 0x23]);
+    assert_eq!(analysis.machine_calls, [(0x204, 0x0123)]);
+    assert_eq!(analysis.preset(), None);
+    assert!(analysis.findings()[1].contains("machine code"));
+    assert!(analysis.to_string().contains("isn't supported"));
+}
@@ -0,0 +1,94 @@
+//! Assembles Octo programs and checks the bytes against what Octo makes.
+
+use std::fs;
+
+use chip_8::assembler::assemble;
+
+fn rom(source: &str) -> Vec<u8> {
+    assemble(source)
+        .unwrap_or_else(|e| panic!("Could not assemble: {e}"))
+        .rom
+}
+
+#[test]
+fn splash_screen_matches_the_bundled_rom() {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let source = fs::read_to_string(format!("{root}/splash-screen/splash-screen.8o")).unwrap();
+    let expected = fs::read(format!("{root}/roms/splash-screen.ch8")).unwrap();
+    let assembly = assemble(&source).unwrap();
+    assert_eq!(assembly.rom, expected);
+    assert_eq!(assembly.labels["wait-vA"], 0x2BE);
+}
+
+#[test]
+fn loop_with_while() {
+    let source = "
+        : main
+          loop
+            v0 += 1
+            while v0 != 10
+            v1 := key
+          again";
+    assert_eq!(
+        rom(source),
+        [0x70, 0x01, 0x40, 0x0A, 0x12, 0x0A, 0xF1, 0x0A, 0x12, 0x00]
+    );
+}
+
+#[test]
+fn if_begin_else_end() {
+    let source = "
+        : main
+          if v0 == v1 begin
+            v2 := 1
+          else
+            v2 := 2
+          end";
+    assert_eq!(
+        rom(source),
+        [0x50, 0x10, 0x12, 0x08, 0x62, 0x01, 0x12, 0x0A, 0x62, 0x02]
+    );
+}
+
+#[test]
+fn jumps_to_main_when_it_is_not_first() {
+    let source = "
+        :macro twice register { register += register }
+        # evaluated right to left like Octo: 2 * (3 + 1)
+        :calc size { 2 * 3 + 1 }
+        : data size
+        : main
+          twice v3
+          i := data
+          jump main";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(
+        assembly.rom,
+        [0x12, 0x03, 0x08, 0x83, 0x34, 0xA2, 0x02, 0x12, 0x03]
+    );
+    assert_eq!(assembly.symbol_table(), "0x0202 data\n0x0203 main\n");
+}
+
+#[test]
+fn macros_count_their_calls() {
+    let source = "
+        :macro count { :byte CALLS }
+        : main
+          count count
+        :org 0x300
+          :pointer main";
+    let rom = rom(source);
+    assert_eq!(&rom[..2], [0x00, 0x01]);
+    assert_eq!(rom.len(), 0x102);
+    assert_eq!(&rom[0x100..], [0x02, 0x00]);
+}
+
+#[test]
+fn errors_have_the_line() {
+    let error = assemble(": main\n  jump nowhere\n").unwrap_err();
+    assert_eq!(error.line, 2);
+    assert!(error.message.contains("nowhere"), "{error}");
+
+    let error = assemble(": main\n  if v0 == 1 begin\n  clear\n").unwrap_err();
+    assert_eq!(error.line, 2);
+}
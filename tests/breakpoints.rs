@@ -0,0 +1,176 @@
+//! The breakpoints stop the emulator before their instruction, and going on from there
+//! gives the same program as never stopping.
+
+use chip_8::{
+    assembler::assemble,
+    emulator::{Breakpoint, Program},
+    error::EmulatorError,
+    expression::Expression,
+    host::NativeHost,
+    movie::{Playback, Recorder},
+};
+
+// counts in v0 and draws every 4 loops, so the VIP frames end at different places
+const COUNTER: &str = "
+    : main
+      v0 += 1
+      v1 := v0
+      v2 := 3
+      v1 &= v2
+      if v1 == 0 then
+        sprite v0 v0 1
+      jump main";
+const LOOP_ADDRESS: u16 = 0x200;
+const DRAW_ADDRESS: u16 = 0x20A;
+
+/// What the counter changes
+fn state(program: &Program) -> (u16, [u8; 16], Vec<u8>, u8) {
+    (
+        program.program_counter,
+        program.variable_regsiters,
+        program.get_display().to_vec(),
+        program.delay_timer,
+    )
+}
+
+fn load(source: &str) -> Program {
+    let rom = assemble(source).unwrap().rom;
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.load_rom(&rom).unwrap();
+    program
+}
+
+#[test]
+fn ticks_stop_before_the_breakpoint_once() {
+    let mut program = load(COUNTER);
+    program
+        .breakpoints
+        .insert(LOOP_ADDRESS + 2, Breakpoint::default());
+    program.tick().unwrap();
+    assert_eq!(
+        program.tick(),
+        Err(EmulatorError::Breakpoint {
+            program_counter: LOOP_ADDRESS + 2
+        })
+    );
+    assert_eq!(program.program_counter, LOOP_ADDRESS + 2);
+    assert_eq!(program.variable_regsiters[1], 0);
+    // the instruction runs when the program goes on, and it stops there again next loop
+    program.tick().unwrap();
+    assert_eq!(program.variable_regsiters[1], 1);
+    let ticks = (0..10).take_while(|_| program.tick().is_ok()).count();
+    assert_eq!(ticks, 5);
+
+    program.breakpoints.clear();
+    assert!((0..100).all(|_| program.tick().is_ok()));
+}
+
+#[test]
+fn conditions_and_hit_counts_skip_breakpoints() {
+    let mut program = load(COUNTER);
+    let condition = Expression::parse("V0 % 2 == 0").unwrap();
+    program
+        .breakpoints
+        .insert(LOOP_ADDRESS + 2, Breakpoint::new(Some(condition), Some(3)));
+    let stop = |program: &mut Program| loop {
+        match program.tick() {
+            Ok(()) => {}
+            Err(EmulatorError::Breakpoint { .. }) => return program.variable_regsiters[0],
+            Err(e) => panic!("{e}"),
+        }
+    };
+    // the third even count, then every even count after it
+    assert_eq!(stop(&mut program), 6);
+    assert_eq!(stop(&mut program), 8);
+    assert_eq!(program.breakpoints[&(LOOP_ADDRESS + 2)].hits, 4);
+
+    // a condition that can't be evaluated stops
+    let broken = Expression::parse("mem[0x10000] == 0").unwrap();
+    program
+        .breakpoints
+        .insert(LOOP_ADDRESS, Breakpoint::new(Some(broken), None));
+    assert_eq!(stop(&mut program), 8);
+    assert_eq!(program.program_counter, LOOP_ADDRESS);
+}
+
+#[test]
+fn vip_frames_go_on_from_the_breakpoint() {
+    let mut expected = load(COUNTER);
+    let instructions: Vec<u32> = (0..20)
+        .map(|_| {
+            let instructions = expected.run_vip_frame().unwrap();
+            expected.timer_tick();
+            instructions
+        })
+        .collect();
+
+    let mut program = load(COUNTER);
+    program
+        .breakpoints
+        .insert(DRAW_ADDRESS, Breakpoint::default());
+    let mut stops = 0;
+    for &frame_instructions in &instructions {
+        loop {
+            match program.run_vip_frame() {
+                Ok(ran) => {
+                    assert_eq!(ran, frame_instructions);
+                    break;
+                }
+                Err(EmulatorError::Breakpoint { program_counter }) => {
+                    assert_eq!(program_counter, DRAW_ADDRESS);
+                    stops += 1;
+                }
+                Err(e) => panic!("{e}"),
+            }
+        }
+        program.timer_tick();
+    }
+    assert!(stops > 0);
+    assert!(state(&program) == state(&expected));
+}
+
+#[test]
+fn movies_go_on_from_the_breakpoint() {
+    let mut program = load(COUNTER);
+    let mut recorder = Recorder::new(&program);
+    for frame in 0..10 {
+        for _ in 0..frame + 5 {
+            program.tick().unwrap();
+        }
+        recorder.count_instructions(frame + 5);
+        program.timer_tick();
+        recorder.end_frame(program.pressed_keys);
+    }
+    let expected = state(&program);
+    let movie = recorder.finish();
+
+    let mut program = load(COUNTER);
+    let mut playback = Playback::new(movie, &mut program).unwrap();
+    program
+        .breakpoints
+        .insert(LOOP_ADDRESS, Breakpoint::default());
+    let mut stops = 0;
+    loop {
+        match playback.play_frame(&mut program) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(EmulatorError::Breakpoint { .. }) => stops += 1,
+            Err(e) => panic!("{e}"),
+        }
+    }
+    assert!(stops > 0);
+    assert!(state(&program) == expected);
+}
+
+#[test]
+fn loading_starts_a_new_frame() {
+    let mut program = load(COUNTER);
+    let state = program.save_state();
+    program.frame_progress = 7;
+    program.load_state(&state).unwrap();
+    assert_eq!(program.frame_progress, 0);
+
+    program.frame_progress = 7;
+    program.reset();
+    assert_eq!(program.frame_progress, 0);
+}
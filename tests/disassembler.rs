@@ -0,0 +1,45 @@
+//! Disassembles the bundled roms to Octo and assembles them back.
+
+use std::fs;
+
+use chip_8::{assembler::assemble, disassembler::disassemble, instruction::Instruction};
+
+#[test]
+fn every_rom_assembles_back_to_itself() {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let roms = fs::read_dir(format!("{root}/roms")).unwrap();
+    let test_roms = fs::read_dir(format!("{root}/roms/tests")).unwrap();
+    for entry in roms.chain(test_roms) {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "ch8") {
+            continue;
+        }
+        let rom = fs::read(&path).unwrap();
+        let octo = disassemble(&rom).to_octo();
+        let assembly = assemble(&octo).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        assert!(assembly.rom == rom, "{} changed", path.display());
+    }
+}
+
+#[test]
+fn splash_screen_code_and_data() {
+    let rom = fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/roms/splash-screen.ch8"
+    ))
+    .unwrap();
+    let disassembly = disassemble(&rom);
+    assert_eq!(
+        disassembly.instruction_at(0x200),
+        Some(Instruction::ClearScreen)
+    );
+    assert_eq!(disassembly.label(0x246), Some("sub_246"));
+    assert_eq!(disassembly.label(0x238), Some("loop_238"));
+    assert_eq!(disassembly.label(0x2C8), Some("data_2C8"));
+    // the sprites come after the last return
+    assert!(disassembly.is_code(0x2C6));
+    assert!(disassembly.is_data(0x2C8));
+    assert!(disassembly
+        .to_octo()
+        .contains("\t0x3c # ..####..\n\t0x7e # .######.\n"));
+}
@@ -0,0 +1,67 @@
+use chip_8::{emulator::Program, error::ExpressionError, expression::Expression, host::NativeHost};
+
+fn program() -> Program {
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.variable_regsiters[3] = 0x10;
+    program.index_register = 0x302;
+    program.memory[0x304] = 7;
+    program.delay_timer = 0;
+    program.call_stack = vec![0x200, 0x210];
+    program
+}
+
+fn evaluate(source: &str) -> Result<i64, String> {
+    Expression::parse(source).unwrap().evaluate(&program())
+}
+
+#[test]
+fn the_state_of_the_machine_is_read() {
+    assert_eq!(evaluate("V3 == 0x10 && I > 0x300"), Ok(1));
+    assert_eq!(evaluate("mem[I+2] != 0"), Ok(1));
+    assert_eq!(evaluate("mem[i + 2]"), Ok(7));
+    assert_eq!(evaluate("DT == 0"), Ok(1));
+    assert_eq!(evaluate("stack.len() > 5"), Ok(0));
+    assert_eq!(evaluate("stack[1]"), Ok(0x210));
+    assert_eq!(evaluate("vA + PC"), Ok(0x200));
+}
+
+#[test]
+fn the_operators_have_the_precedence_of_rust() {
+    assert_eq!(evaluate("1 + 2 * 3"), Ok(7));
+    assert_eq!(evaluate("(1 + 2) * 3"), Ok(9));
+    assert_eq!(evaluate("10 - 4 - 3"), Ok(3));
+    // the bits before the comparison, unlike C
+    assert_eq!(evaluate("1 | 2 == 2"), Ok(0));
+    assert_eq!(evaluate("0b1100 & 0b1010 ^ 1"), Ok(9));
+    assert_eq!(evaluate("!0 && -2 < ~0 || 0"), Ok(1));
+    assert_eq!(evaluate("1 << 4 >> 2"), Ok(4));
+}
+
+#[test]
+fn values_that_dont_exist_are_errors() {
+    assert_eq!(
+        evaluate("1 / (V0 - V0)"),
+        Err("Division by zero".to_string())
+    );
+    assert!(evaluate("mem[0x10000]").is_err());
+    assert!(evaluate("stack[2]").is_err());
+    // the right side isn't evaluated when the left decides
+    assert_eq!(evaluate("0 && 1 / 0"), Ok(0));
+}
+
+#[test]
+fn parse_errors_have_the_column() {
+    let error = |source: &str| Expression::parse(source).unwrap_err();
+    assert_eq!(
+        error("V3 == "),
+        ExpressionError {
+            column: 7,
+            message: "Expected a value".to_string()
+        }
+    );
+    assert_eq!(error("VG > 1").column, 1);
+    assert_eq!(error("mem[I").message, "Expected ']'");
+    assert_eq!(error("stack.size()").column, 7);
+    assert_eq!(error("1 2").message, "Unexpected '2'");
+    assert_eq!(error("V1 = 2").column, 4);
+}
@@ -0,0 +1,294 @@
+//! The blocks of the JIT: what gets compiled, when a block has to be compiled again, and
+//! that running the blocks in a WebAssembly engine leaves the program exactly like ticking.
+
+use std::collections::HashMap;
+
+use chip_8::{
+    assembler::assemble,
+    emulator::{Block, Platform, Program, MAX_MODULE_SIZE},
+    error::EmulatorError,
+    host::NativeHost,
+    metadata::Database,
+};
+use wasmi::{Engine, Linker, Memory, MemoryType, Module, Store, TypedFunc};
+
+// where the fields of the program go in the memory the blocks import
+const REGISTERS: usize = 0;
+const INDEX: usize = 16;
+const PROGRAM_COUNTER: usize = 18;
+const MEMORY: usize = 32;
+
+type Run = TypedFunc<(u32, u32, u32, u32, u32), u32>;
+
+/// Runs the blocks like the browser does, with the fields of the program copied in and out
+/// of the memory of the engine
+struct Engine8 {
+    engine: Engine,
+    store: Store<()>,
+    memory: Memory,
+    blocks: HashMap<u16, (Block, Run)>,
+    // the instructions that ran in blocks
+    compiled: u32,
+}
+
+impl Engine8 {
+    fn new() -> Self {
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        // the registers and the 64KB of XO-CHIP memory
+        let memory = Memory::new(&mut store, MemoryType::new(2, None).unwrap()).unwrap();
+        Self {
+            engine,
+            store,
+            memory,
+            blocks: HashMap::new(),
+            compiled: 0,
+        }
+    }
+
+    /// Like `Jit::step`, runs the block at the program counter or ticks
+    fn step(&mut self, program: &mut Program, budget: u32) -> Result<u32, EmulatorError> {
+        match self.run_block(program, budget) {
+            0 => program.tick().map(|_| 1),
+            ran => Ok(ran),
+        }
+    }
+
+    fn run_block(&mut self, program: &mut Program, budget: u32) -> u32 {
+        let address = program.program_counter;
+        let current = self
+            .blocks
+            .get(&address)
+            .is_some_and(|(block, _)| program.is_current(block));
+        if !current {
+            let Some(block) = program.compile_block(address) else {
+                self.blocks.remove(&address);
+                return 0;
+            };
+            assert!(block.module().len() < MAX_MODULE_SIZE);
+            let run = self.instantiate(&block);
+            self.blocks.insert(address, (block, run));
+        }
+        let run = self.blocks[&address].1;
+        let memory = self.memory.data_mut(&mut self.store);
+        memory[REGISTERS..REGISTERS + 16].copy_from_slice(&program.variable_regsiters);
+        memory[INDEX..INDEX + 2].copy_from_slice(&program.index_register.to_le_bytes());
+        memory[PROGRAM_COUNTER..PROGRAM_COUNTER + 2]
+            .copy_from_slice(&program.program_counter.to_le_bytes());
+        memory[MEMORY..MEMORY + program.memory.len()].copy_from_slice(&program.memory);
+        let ran = run
+            .call(
+                &mut self.store,
+                (
+                    REGISTERS as u32,
+                    MEMORY as u32,
+                    INDEX as u32,
+                    PROGRAM_COUNTER as u32,
+                    budget,
+                ),
+            )
+            .unwrap();
+        let memory = self.memory.data(&self.store);
+        program
+            .variable_regsiters
+            .copy_from_slice(&memory[REGISTERS..REGISTERS + 16]);
+        program.index_register = u16::from_le_bytes([memory[INDEX], memory[INDEX + 1]]);
+        program.program_counter =
+            u16::from_le_bytes([memory[PROGRAM_COUNTER], memory[PROGRAM_COUNTER + 1]]);
+        self.compiled += ran;
+        ran
+    }
+
+    fn instantiate(&mut self, block: &Block) -> Run {
+        let module = Module::new(&self.engine, block.module()).unwrap();
+        let mut linker = Linker::new(&self.engine);
+        linker.define("env", "memory", self.memory).unwrap();
+        let instance = linker
+            .instantiate(&mut self.store, &module)
+            .unwrap()
+            .start(&mut self.store)
+            .unwrap();
+        instance.get_typed_func(&self.store, "run").unwrap()
+    }
+}
+
+fn load(source: &str) -> Program {
+    let rom = assemble(source).unwrap().rom;
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.load_rom(&rom).unwrap();
+    program
+}
+
+/// Loads a bundled rom with the quirks of its metadata
+fn load_bundled(rom: &[u8]) -> Program {
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    if let Some(metadata) = Database::bundled().lookup(rom) {
+        program.set_platform(metadata.preset().platform());
+        program.quirks = metadata.quirks();
+    }
+    program.load_rom(rom).unwrap();
+    program.rng.reseed(8);
+    program
+}
+#[test]
+fn blocks_stop_before_what_the_interpreter_runs() {
+    let mut program = load(
+        "
+        : main
+          v0 := 1
+          v1 += v0
+          if v1 == 4 then
+            jump main
+          i := main
+          sprite v0 v1 5",
+    );
+    let block = program.compile_block(0x200).unwrap();
+    // the jump can be skipped so the block goes on after it
+    assert_eq!(block.len(), 5);
+    assert!(block.module().starts_with(b"\0asm"));
+    assert_eq!(program.compile_block(0x20A), None);
+}
+
+#[test]
+fn blocks_list_the_addresses_of_their_instructions() {
+    let mut program = load(
+        "
+        : main
+          v0 := 1
+          if v0 == 1 then
+            v1 := 2
+          jump main",
+    );
+    let block = program.compile_block(0x200).unwrap();
+    // the breakpoints at these addresses make the block tick instead
+    assert!(block.addresses().eq([0x200, 0x202, 0x204, 0x206]));
+}
+
+#[test]
+fn writes_make_blocks_stale() {
+    let mut program = load(
+        "
+        : main
+          v0 := 0x60
+          i := main
+          save v0
+          jump main",
+    );
+    let block = program.compile_block(0x200).unwrap();
+    assert_eq!(block.len(), 2);
+    for _ in 0..3 {
+        program.tick().unwrap();
+    }
+    // `save` wrote the same byte over the first instruction
+    assert!(!program.is_current(&block));
+    let mut quirky = program.compile_block(0x200).unwrap();
+    assert!(program.is_current(&quirky));
+    program.quirks.shifting = !program.quirks.shifting;
+    assert!(!program.is_current(&quirky));
+    quirky = program.compile_block(0x200).unwrap();
+    assert!(program.is_current(&quirky));
+}
+
+#[test]
+fn big_blocks_are_shortened_to_fit_the_browser() {
+    // every load of all the registers compiles to 16 loads and stores
+    let source = format!(": main\n{}", "  i := main\n  load vf\n".repeat(32));
+    let mut program = load(&source);
+    let block = program.compile_block(0x200).unwrap();
+    assert!(block.len() < 64);
+    assert!(block.module().len() < MAX_MODULE_SIZE);
+}
+
+#[test]
+fn only_the_4kb_platforms_flag_i_past_the_memory() {
+    let rom = assemble(
+        "
+        : main
+          vf := 0
+          v0 := 4
+          i := 0xFFE
+          i += v0
+          loop again",
+    )
+    .unwrap()
+    .rom;
+    for (platform, flag) in [
+        (Platform::Chip8, 1),
+        (Platform::SuperChip, 1),
+        (Platform::XoChip, 0),
+    ] {
+        let mut ticked = Program::new(Box::new(NativeHost::new()));
+        ticked.set_platform(platform);
+        ticked.load_rom(&rom).unwrap();
+        ticked.rng.reseed(8);
+        let mut compiled = Program::new(Box::new(NativeHost::new()));
+        compiled.set_platform(platform);
+        compiled.load_rom(&rom).unwrap();
+        compiled.rng.reseed(8);
+        for _ in 0..4 {
+            ticked.tick().unwrap();
+        }
+        assert_eq!(Engine8::new().step(&mut compiled, 4).unwrap(), 4);
+        assert_eq!(ticked.index_register, 0x1002);
+        assert_eq!(ticked.variable_regsiters[0xF], flag, "{platform:?}");
+        assert!(compiled.save_state() == ticked.save_state(), "{platform:?}");
+    }
+}
+
+#[test]
+fn the_bundled_roms_run_the_same_with_the_jit() {
+    const FRAMES: usize = 300;
+    const INSTRUCTIONS_PER_FRAME: u32 = 30;
+    let mut roms: Vec<_> = std::fs::read_dir("roms")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ch8"))
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty());
+    let mut compiled_instructions = 0;
+    for path in roms {
+        let rom = std::fs::read(&path).unwrap();
+        let mut ticked = load_bundled(&rom);
+        let mut compiled = load_bundled(&rom);
+        let mut engine = Engine8::new();
+        for frame in 0..FRAMES {
+            // hold a different key every few frames so the games move
+            let keys = 1 << (frame / 8 % 16);
+            ticked.pressed_keys = keys;
+            compiled.pressed_keys = keys;
+            let mut expected = Ok(());
+            for _ in 0..INSTRUCTIONS_PER_FRAME {
+                expected = ticked.tick();
+                if expected.is_err() {
+                    break;
+                }
+            }
+            let mut progress = 0;
+            let mut result = Ok(());
+            while progress < INSTRUCTIONS_PER_FRAME {
+                match engine.step(&mut compiled, INSTRUCTIONS_PER_FRAME - progress) {
+                    Ok(ran) => progress += ran,
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            }
+            assert_eq!(result, expected, "{} frame {frame}", path.display());
+            assert!(
+                compiled.save_state() == ticked.save_state(),
+                "{} differs after frame {frame}",
+                path.display()
+            );
+            if expected.is_err() {
+                break;
+            }
+            ticked.timer_tick();
+            compiled.timer_tick();
+        }
+        compiled_instructions += engine.compiled;
+    }
+    // the comparison means nothing if no block ran
+    assert!(compiled_instructions > 0);
+}
@@ -0,0 +1,191 @@
+use chip_8::{emulator::Platform, metadata::Database, palette::Palette, quirks::Preset};
+
+// the key is the SHA-1 of `jump 0x200`
+const JUMP_HASH: &str = "92a5652d382a18e89c4881ec57041fc7d885ca80";
+
+#[test]
+fn the_bundled_roms_are_found_by_their_bytes() {
+    let database = Database::bundled();
+    assert_eq!(database.len(), 55);
+
+    let splash_screen = database
+        .lookup(include_bytes!("../roms/splash-screen.ch8"))
+        .unwrap();
+    assert_eq!(splash_screen.file, "splash-screen.ch8");
+    assert_eq!(splash_screen.preset(), Preset::Octo);
+
+    assert!(database.lookup(&[0x12, 0x00]).is_none());
+}
+
+#[test]
+fn the_splash_screen_uses_the_colors_of_the_page() {
+    let splash_screen = Database::bundled()
+        .lookup(include_bytes!("../roms/splash-screen.ch8"))
+        .unwrap();
+    assert_eq!(splash_screen.options.tickrate, Some(16));
+    assert_eq!(splash_screen.palette(), Some(Palette::GRUVBOX));
+    assert_eq!(splash_screen.quirks(), Preset::Octo.quirks());
+}
+
+#[test]
+fn the_catalog_is_searched_and_filtered() {
+    let database = Database::bundled();
+    let catalog = database.catalog();
+    assert_eq!(catalog.len(), database.len());
+    // the test roms are in their own folder, after the games
+    assert_eq!(catalog[0].file, "1dcell.ch8");
+    assert_eq!(catalog.last().unwrap().file, "tests/7-beep.ch8");
+
+    let octojams = database.search(" OctoJam ", None);
+    assert_eq!(octojams.len(), 10);
+    assert_eq!(octojams[0].file, "octojam10title.ch8");
+
+    // the authors and descriptions are searched too
+    let test_suite = database.search("timendus", Some(Platform::Chip8));
+    assert_eq!(test_suite.len(), 6);
+    assert!(test_suite.iter().all(|rom| rom.file.starts_with("tests/")));
+
+    // the filter only offers the platforms that have roms
+    assert_eq!(database.platforms(), [Platform::Chip8]);
+    let chip_8 = database.search("", Some(Platform::Chip8));
+    assert_eq!(chip_8.len(), database.len() - 1);
+    assert!(chip_8.iter().all(|rom| rom.file != "tests/5-quirks.ch8"));
+}
+
+#[test]
+fn the_platform_filter_keeps_the_roms_of_the_platform() {
+    let database = Database::parse(
+        r#"{
+            "1111111111111111111111111111111111111111": {
+                "file": "old.ch8", "title": "Old", "platform": "chip8"
+            },
+            "2222222222222222222222222222222222222222": {
+                "file": "new.ch8", "title": "New", "platform": "xochip"
+            },
+            "3333333333333333333333333333333333333333": {
+                "file": "newer.ch8", "title": "Newer", "platform": "xochip"
+            },
+            "4444444444444444444444444444444444444444": {"file": "any.ch8", "title": "Any"}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(database.platforms(), [Platform::Chip8, Platform::XoChip]);
+    let xo_chip: Vec<&str> = database
+        .search("", Some(Platform::XoChip))
+        .iter()
+        .map(|rom| rom.file.as_str())
+        .collect();
+    assert_eq!(xo_chip, ["new.ch8", "newer.ch8"]);
+    assert!(database.search("", Some(Platform::SuperChip)).is_empty());
+    assert_eq!(database.search("", None).len(), 4);
+}
+
+#[test]
+fn the_options_change_the_quirks_and_colors() {
+    let database = Database::parse(&format!(
+        r##"{{
+            "{JUMP_HASH}": {{
+                "file": "game.sc8",
+                "title": "Game",
+                "platform": "schip",
+                "options": {{
+                    "tickrate": 30,
+                    "fillColor": "#123456",
+                    "loadStoreQuirks": false,
+                    "clipQuirks": false
+                }}
+            }}
+        }}"##
+    ))
+    .unwrap();
+    let metadata = database.lookup(&[0x12, 0x00]).unwrap();
+    assert_eq!(metadata.options.tickrate, Some(30));
+    assert_eq!(metadata.preset(), Preset::SuperChip);
+
+    // the quirks of the archive are changes to Octo, whatever the platform
+    let quirks = metadata.quirks();
+    let octo = Preset::Octo.quirks();
+    assert!(quirks.memory_increment);
+    assert!(!quirks.clipping);
+    assert_eq!(quirks.shifting, octo.shifting);
+    assert_ne!(quirks.shifting, Preset::SuperChip.quirks().shifting);
+
+    let palette = metadata.palette().unwrap();
+    assert_eq!(palette.color(0b01), [0x12, 0x34, 0x56, 0xFF]);
+    assert_eq!(palette.color(0b00), Palette::OCTO.color(0b00));
+}
+
+#[test]
+fn without_quirks_the_platform_picks_them() {
+    let database = Database::parse(&format!(
+        r#"{{"{JUMP_HASH}": {{"file": "game.sc8", "title": "Game", "platform": "schip"}}}}"#
+    ))
+    .unwrap();
+    let metadata = database.lookup(&[0x12, 0x00]).unwrap();
+    assert_eq!(metadata.quirks(), Preset::SuperChip.quirks());
+}
+
+#[test]
+fn the_archive_fills_in_the_roms_with_its_names() {
+    let mut database = Database::parse(&format!(
+        r#"{{
+            "{JUMP_HASH}": {{
+                "file": "games/jumper.ch8",
+                "title": "jumper",
+                "platform": "chip8",
+                "keys": [5],
+                "thumbnail": "thumbnails/jumper.svg"
+            }}
+        }}"#
+    ))
+    .unwrap();
+    // a made up entry with the fields of the archive, and one for a rom that isn't bundled
+    let imported = database
+        .import_archive(
+            r##"{
+                "jumper": {
+                    "title": "Jumper",
+                    "event": "Octojam",
+                    "authors": ["Someone", "Someone Else"],
+                    "desc": "Jumps forever.",
+                    "platform": "xochip",
+                    "options": {
+                        "tickrate": 1000,
+                        "fillColor": "#FF00FF",
+                        "vfOrderQuirks": false,
+                        "shiftQuirks": true
+                    }
+                },
+                "other": {"title": "Other"}
+            }"##,
+        )
+        .unwrap();
+    assert_eq!(imported, ["games/jumper.ch8"]);
+
+    let metadata = database.lookup(&[0x12, 0x00]).unwrap();
+    assert_eq!(metadata.title, "Jumper");
+    assert_eq!(metadata.authors, ["Someone", "Someone Else"]);
+    assert_eq!(metadata.description.as_deref(), Some("Jumps forever."));
+    assert_eq!(metadata.platform(), Some(Platform::XoChip));
+    assert_eq!(metadata.options.tickrate, Some(1000));
+    assert!(metadata.quirks().shifting);
+    // what the archive doesn't have stays
+    assert_eq!(metadata.keys, [5]);
+    assert_eq!(metadata.thumbnail.as_deref(), Some("thumbnails/jumper.svg"));
+
+    assert_eq!(Database::parse(&database.to_json()).unwrap(), database);
+    assert!(database.import_archive("[]").is_err());
+}
+
+#[test]
+fn the_tools_write_the_bundled_roms_as_they_are() {
+    assert_eq!(
+        Database::bundled().to_json(),
+        include_str!("../roms/metadata.json")
+    );
+}
+
+#[test]
+fn invalid_metadata_is_an_error() {
+    assert!(Database::parse(r#"{"abc": {"title": "No file"}}"#).is_err());
+}
@@ -0,0 +1,109 @@
+//! A movie replays the run it recorded exactly, after going through its file format.
+
+use chip_8::{
+    assembler::assemble,
+    emulator::Program,
+    error::MovieError,
+    host::NativeHost,
+    movie::{Movie, Playback, Recorder},
+    rng::RngKind,
+};
+
+// moves a sprite with the keys and draws random ones, so the keys and the random numbers
+// both change what is on the screen
+const GAME: &str = "
+    : main
+      v2 := 5
+      if v2 key then v0 += 1
+      v2 := 8
+      if v2 key then v1 += 1
+      v3 := random 0x3F
+      v4 := random 0x1F
+      i := dot
+      sprite v3 v4 1
+      sprite v0 v1 1
+      jump main
+    : dot
+      0x80";
+const FRAMES: usize = 120;
+
+fn load(source: &str) -> Program {
+    let rom = assemble(source).unwrap().rom;
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.load_rom(&rom).unwrap();
+    program
+}
+
+/// Runs frames like the runner, with keys that change over time and a varying speed
+fn record(program: &mut Program) -> Movie {
+    let mut recorder = Recorder::new(program);
+    for frame in 0..FRAMES {
+        program.pressed_keys = match frame / 10 % 3 {
+            0 => 1 << 5,
+            1 => 1 << 8,
+            _ => 0,
+        };
+        let instructions = 10 + frame % 7;
+        for _ in 0..instructions {
+            program.tick().unwrap();
+        }
+        program.timer_tick();
+        recorder.count_instructions(instructions);
+        recorder.end_frame(program.pressed_keys);
+    }
+    recorder.finish()
+}
+
+#[test]
+fn a_movie_replays_the_recorded_run() {
+    let mut program = load(GAME);
+    program.rng.reseed(0xBEEF);
+    for _ in 0..50 {
+        program.tick().unwrap();
+    }
+    let movie = record(&mut program);
+    let recorded = program.save_state();
+
+    let bytes = movie.to_bytes();
+    let movie = Movie::from_bytes(&bytes).unwrap();
+    assert_eq!(movie.to_bytes(), bytes);
+    assert_eq!(movie.frames.len(), FRAMES);
+
+    // another seed and another moment, the movie brings its own
+    let mut replay = load(GAME);
+    replay.rng.reseed(1);
+    let mut playback = Playback::new(movie, &mut replay).unwrap();
+    while playback.play_frame(&mut replay).unwrap() {}
+    assert_eq!(playback.frame(), FRAMES);
+    assert_eq!(replay.save_state(), recorded);
+}
+
+#[test]
+fn a_movie_of_another_rom_is_refused() {
+    let movie = record(&mut load(GAME));
+    let mut other = load(
+        "
+        : main
+          jump main",
+    );
+    assert_eq!(
+        Playback::new(movie, &mut other).err(),
+        Some(MovieError::OtherRom)
+    );
+}
+
+#[test]
+fn a_movie_keeps_the_vip_generator() {
+    let mut program = load(GAME);
+    program.rng.set_kind(RngKind::Vip);
+    let movie = record(&mut program);
+    let recorded = program.save_state();
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(movie.rng_kind, RngKind::Vip);
+
+    let mut replay = load(GAME);
+    let mut playback = Playback::new(movie, &mut replay).unwrap();
+    while playback.play_frame(&mut replay).unwrap() {}
+    assert_eq!(replay.rng.kind(), RngKind::Vip);
+    assert_eq!(replay.save_state(), recorded);
+}
@@ -0,0 +1,49 @@
+//! The instructions that run again stay inside memory when the program counter wrapped
+//! around after the last instruction of memory.
+
+use chip_8::{
+    emulator::{Platform, Program},
+    host::NativeHost,
+};
+
+// the last instruction of the XO-CHIP memory, the program counter wraps to 0 after it
+const LAST_ADDRESS: u16 = 0xFFFE;
+
+fn run_at_the_end(opcode: u16) -> Program {
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.set_platform(Platform::XoChip);
+    program.quirks.display_wait = true;
+    program.memory[LAST_ADDRESS as usize..].copy_from_slice(&opcode.to_be_bytes());
+    program.invalidate_decoded();
+    program.program_counter = LAST_ADDRESS;
+    program.tick().unwrap();
+    program
+}
+
+#[test]
+fn exit_stays_at_the_end_of_memory() {
+    assert_eq!(run_at_the_end(0x00FD).program_counter, LAST_ADDRESS);
+}
+
+#[test]
+fn waiting_for_a_key_stays_at_the_end_of_memory() {
+    assert_eq!(run_at_the_end(0xF00A).program_counter, LAST_ADDRESS);
+}
+
+#[test]
+fn waiting_for_the_vertical_blank_stays_at_the_end_of_memory() {
+    // nothing ticked the timers yet, so the sprite waits
+    assert_eq!(run_at_the_end(0xD011).program_counter, LAST_ADDRESS);
+}
+
+#[test]
+fn a_long_load_at_the_end_of_memory_wraps_around() {
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.set_platform(Platform::XoChip);
+    program.memory[0xFFFC..].copy_from_slice(&[0xF0, 0x00, 0x12, 0x34]);
+    program.invalidate_decoded();
+    program.program_counter = 0xFFFC;
+    program.tick().unwrap();
+    assert_eq!(program.index_register, 0x1234);
+    assert_eq!(program.program_counter, 0);
+}
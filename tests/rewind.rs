@@ -0,0 +1,75 @@
+//! Stepping back restores the recorded frames exactly, across every kind of delta and after
+//! the oldest frames were dropped.
+
+use chip_8::{
+    emulator::{Platform, Program},
+    host::NativeHost,
+    rewind::Rewind,
+};
+
+const CAPACITY: usize = 5;
+const FRAMES: usize = 12;
+
+/// Changes the program differently every frame: a run of more than 127 changed bytes, runs
+/// of unchanged bytes longer than 16383 in the 64KB of memory, and a resolution change that
+/// changes the length of the state
+fn change(program: &mut Program, frame: usize) {
+    let start = 0x300 + frame * 7;
+    program.memory[start..start + 200].fill(frame as u8 + 1);
+    program.memory[0xFF00 + frame] = frame as u8;
+    program.variable_regsiters[frame % 16] = frame as u8;
+    program.delay_timer = frame as u8;
+    program.high_resolution = frame >= 7;
+}
+
+#[test]
+fn stepping_back_restores_every_recorded_frame() {
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.set_platform(Platform::XoChip);
+    let mut rewind = Rewind::new(CAPACITY);
+    let mut states = Vec::new();
+    for frame in 0..FRAMES {
+        change(&mut program, frame);
+        rewind.record(&program);
+        states.push(program.save_state());
+    }
+    // the oldest frames went around the ring
+    assert_eq!(rewind.len(), CAPACITY);
+
+    for back in 1..=CAPACITY {
+        assert!(rewind.step_back(&mut program));
+        assert_eq!(
+            program.save_state(),
+            states[FRAMES - 1 - back],
+            "{back} frames back"
+        );
+    }
+    // the oldest frame that is kept stays
+    assert!(!rewind.step_back(&mut program));
+    assert_eq!(program.save_state(), states[FRAMES - 1 - CAPACITY]);
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn recording_after_stepping_back_goes_on_from_there() {
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    let mut rewind = Rewind::new(CAPACITY);
+    let mut states = Vec::new();
+    for frame in 0..4 {
+        change(&mut program, frame);
+        rewind.record(&program);
+        states.push(program.save_state());
+    }
+    rewind.step_back(&mut program);
+    rewind.step_back(&mut program);
+    assert_eq!(program.save_state(), states[1]);
+
+    change(&mut program, 9);
+    rewind.record(&program);
+    let changed = program.save_state();
+    assert!(rewind.step_back(&mut program));
+    assert_eq!(program.save_state(), states[1]);
+    assert!(rewind.step_back(&mut program));
+    assert_eq!(program.save_state(), states[0]);
+    assert_ne!(changed, states[0]);
+}
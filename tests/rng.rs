@@ -0,0 +1,130 @@
+//! The random numbers only depend on the seed, so a run can be played again.
+
+use chip_8::{
+    assembler::assemble,
+    emulator::Program,
+    host::NativeHost,
+    rng::{Rng, RngKind},
+};
+
+fn numbers(rng: &mut Rng, count: usize) -> Vec<u8> {
+    (0..count).map(|_| rng.next_byte(&[0; 4096])).collect()
+}
+
+#[test]
+fn the_same_seed_gives_the_same_numbers() {
+    let first = numbers(&mut Rng::new(0x1234, RngKind::Xorshift), 1000);
+    assert_eq!(
+        numbers(&mut Rng::new(0x1234, RngKind::Xorshift), 1000),
+        first
+    );
+    assert_ne!(
+        numbers(&mut Rng::new(0x1235, RngKind::Xorshift), 1000),
+        first
+    );
+    // every byte comes out, 255 too
+    let many = numbers(&mut Rng::new(0x1234, RngKind::Xorshift), 5000);
+    assert!((0..=255).all(|byte| many.contains(&byte)));
+}
+
+#[test]
+fn reseeding_starts_the_numbers_over() {
+    let mut rng = Rng::new(7, RngKind::Xorshift);
+    let first = numbers(&mut rng, 100);
+    rng.reseed(0x1234);
+    assert_eq!(rng.seed(), 0x1234);
+    assert_eq!(
+        numbers(&mut rng, 100),
+        numbers(&mut Rng::new(0x1234, RngKind::Xorshift), 100)
+    );
+    rng.reseed(7);
+    assert_eq!(numbers(&mut rng, 100), first);
+}
+
+#[test]
+fn a_seeded_program_draws_the_same_numbers_after_a_reset() {
+    let rom = assemble(
+        "
+        : main
+          v0 := random 0xFF
+          v1 := random 0xFF
+          v2 := random 0x0F
+          i := 0x300
+          save v2
+          jump main",
+    )
+    .unwrap()
+    .rom;
+    let run = |program: &mut Program| {
+        program.load_rom(&rom).unwrap();
+        for _ in 0..60 {
+            program.tick().unwrap();
+        }
+        program.memory[0x300..0x303].to_vec()
+    };
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.rng.reseed(0xC0FFEE);
+    let first = run(&mut program);
+    assert_eq!(run(&mut program), first);
+
+    let mut other = Program::new(Box::new(NativeHost::new()));
+    other.rng.reseed(0xC0FFEE);
+    assert_eq!(run(&mut other), first);
+}
+
+#[test]
+fn the_vip_generator_adds_the_interpreter_page_to_its_last_number() {
+    let mut memory = [0; 4096];
+    memory[0x101] = 5;
+    memory[0x102] = 7;
+    memory[0x104] = 0xF8;
+    let mut rng = Rng::new(0, RngKind::Vip);
+    assert_eq!(rng.next_byte(&memory), 5);
+    assert_eq!(rng.next_byte(&memory), 5 + 7);
+    // the timer interrupt moves the pointer past 0x103
+    rng.timer_tick();
+    assert_eq!(rng.next_byte(&memory), 12u8.wrapping_add(0xF8));
+    rng.restart();
+    assert_eq!(rng.next_byte(&memory), 5);
+    // the seed is where the pointer and the last number start
+    assert_eq!(Rng::new(0x0300, RngKind::Vip).next_byte(&memory), 3 + 5);
+}
+
+#[test]
+fn a_save_state_keeps_the_vip_generator() {
+    let rom = assemble(
+        "
+        : main
+          v0 := random 0xFF
+          i := 0x300
+          save v0
+          jump main",
+    )
+    .unwrap()
+    .rom;
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.load_rom(&rom).unwrap();
+    program.rng.set_kind(RngKind::Vip);
+    program.rng.reseed(0x1234);
+    for _ in 0..7 {
+        program.tick().unwrap();
+    }
+    program.timer_tick();
+    let state = program.save_state();
+    let draw = |program: &mut Program| {
+        (0..40)
+            .map(|_| {
+                program.tick().unwrap();
+                program.memory[0x300]
+            })
+            .collect::<Vec<_>>()
+    };
+    let numbers = draw(&mut program);
+
+    let mut other = Program::new(Box::new(NativeHost::new()));
+    other.load_rom(&rom).unwrap();
+    other.load_state(&state).unwrap();
+    assert_eq!(other.rng.kind(), RngKind::Vip);
+    assert_eq!(other.rng.seed(), 0x1234);
+    assert_eq!(draw(&mut other), numbers);
+}
@@ -0,0 +1,108 @@
+//! A loaded state goes on exactly like the program it was saved from, even in the middle of
+//! a frame that a breakpoint stopped.
+
+use chip_8::{
+    assembler::assemble,
+    emulator::{Breakpoint, Program},
+    error::{EmulatorError, SaveStateError},
+    host::NativeHost,
+};
+
+// draws random sprites at random places and counts, so every part of the state changes
+const RANDOM_SPRITES: &str = "
+    : main
+      v0 := random 0x3F
+      v1 := random 0x1F
+      v2 := random 0xFF
+      i := scratch
+      save v2
+      sprite v0 v1 3
+      v3 += 1
+      delay := v3
+      jump main
+    : scratch
+      0 0 0";
+const SAVE_ADDRESS: u16 = 0x208;
+
+fn load(source: &str) -> Program {
+    let rom = assemble(source).unwrap().rom;
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.load_rom(&rom).unwrap();
+    program
+}
+
+fn run_frames(program: &mut Program, frames: usize) {
+    for _ in 0..frames {
+        for _ in 0..16 {
+            program.tick().unwrap();
+        }
+        program.timer_tick();
+    }
+}
+
+/// Runs VIP frames, going on from every breakpoint
+fn run_vip_frames(program: &mut Program, frames: usize) -> usize {
+    let mut stops = 0;
+    for _ in 0..frames {
+        loop {
+            match program.run_vip_frame() {
+                Ok(_) => break,
+                Err(EmulatorError::Breakpoint { .. }) => stops += 1,
+                Err(e) => panic!("{e}"),
+            }
+        }
+        program.timer_tick();
+    }
+    stops
+}
+
+#[test]
+fn a_loaded_state_runs_like_the_saved_program() {
+    let mut program = load(RANDOM_SPRITES);
+    run_frames(&mut program, 10);
+    let state = program.save_state();
+    run_frames(&mut program, 30);
+    let expected = program.save_state();
+
+    program.load_state(&state).unwrap();
+    assert_eq!(program.save_state(), state);
+    run_frames(&mut program, 30);
+    assert_eq!(program.save_state(), expected);
+}
+
+#[test]
+fn a_state_saved_at_a_breakpoint_finishes_the_frame() {
+    let mut program = load(RANDOM_SPRITES);
+    program
+        .breakpoints
+        .insert(SAVE_ADDRESS, Breakpoint::default());
+    run_vip_frames(&mut program, 3);
+    // stopped in the middle of a frame, before `save v2`
+    assert!(matches!(
+        program.run_vip_frame(),
+        Err(EmulatorError::Breakpoint { .. })
+    ));
+    let state = program.save_state();
+    let stops = run_vip_frames(&mut program, 10);
+    let expected = program.save_state();
+
+    program.load_state(&state).unwrap();
+    assert_eq!(run_vip_frames(&mut program, 10), stops);
+    assert_eq!(program.save_state(), expected);
+}
+
+#[test]
+fn a_state_of_another_rom_is_refused() {
+    let mut program = load(RANDOM_SPRITES);
+    run_frames(&mut program, 5);
+    let state = program.save_state();
+
+    let mut other = load(
+        "
+        : main
+          jump main",
+    );
+    let before = other.save_state();
+    assert_eq!(other.load_state(&state), Err(SaveStateError::OtherRom));
+    assert_eq!(other.save_state(), before);
+}
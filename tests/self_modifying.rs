@@ -0,0 +1,54 @@
+//! Programs that write over their own instructions, which must not run the old ones
+//! from the decoded instruction cache.
+
+use chip_8::{assembler::assemble, emulator::Program, host::NativeHost};
+
+fn run(source: &str, instructions: usize) -> Program {
+    let rom = assemble(source).unwrap().rom;
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.load_rom(&rom).unwrap();
+    for _ in 0..instructions {
+        program.tick().unwrap();
+    }
+    program
+}
+
+#[test]
+fn save_overwrites_an_instruction_that_already_ran() {
+    // the first pass runs `v1 := 1`, then `save` turns it into `v1 := 2`
+    let program = run(
+        "
+        : main
+          v2 := 0
+        : patched
+          v1 := 1
+          if v2 == 1 then
+            jump done
+          i := patched
+          v0 := 0x61
+          v1 := 0x02
+          save v1
+          v2 := 1
+          jump patched
+        : done
+          jump done",
+        12,
+    );
+    assert_eq!(program.variable_regsiters[1], 2);
+}
+
+#[test]
+fn direct_writes_are_seen_after_invalidating() {
+    let mut program = run(
+        "
+        : main
+          v1 := 1
+          jump main",
+        2,
+    );
+    // `v1 := 3`
+    program.memory[0x200..0x202].copy_from_slice(&[0x61, 0x03]);
+    program.invalidate_decoded();
+    program.tick().unwrap();
+    assert_eq!(program.variable_regsiters[1], 3);
+}
@@ -0,0 +1,210 @@
+//! Runs the test suite in `roms/tests` and compares the screen with the images in
+//! `tests/golden`, one character per pixel: `#` for on and `.` for off.
+//!
+//! After a change that is meant to alter a screen, run the tests with `UPDATE_GOLDEN=1`
+//! to write the new images, and check them before committing.
+
+use std::{fs, path::PathBuf};
+
+use chip_8::{emulator::Program, host::NativeHost, quirks::Preset};
+
+// the test roms are written for the VIP speed, but faster is fine as long as they finish
+const INSTRUCTIONS_PER_FRAME: usize = 30;
+// the suite reads this address to skip its menus
+const MENU_CHOICE_ADDRESS: usize = 0x1FF;
+
+struct TestRom {
+    program: Program,
+}
+
+impl TestRom {
+    fn new(name: &str, preset: Preset) -> Self {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("roms/tests")
+            .join(name);
+        let rom = fs::read(&path).unwrap_or_else(|e| panic!("Could not read {path:?}: {e}"));
+        let mut program = Program::new(Box::new(NativeHost::new()));
+        program.quirks = preset.quirks();
+        program.set_platform(preset.platform());
+        program.load_rom(&rom).expect("The test rom doesn't fit");
+        Self { program }
+    }
+
+    /// Answers the menu of the test before it's shown
+    fn with_menu_choice(mut self, choice: u8) -> Self {
+        self.program.memory[MENU_CHOICE_ADDRESS] = choice;
+        self
+    }
+
+    fn run(&mut self, frames: usize) -> &mut Self {
+        for _ in 0..frames {
+            for _ in 0..INSTRUCTIONS_PER_FRAME {
+                self.program.tick().expect("The test rom crashed");
+            }
+            self.program.timer_tick();
+        }
+        self
+    }
+
+    /// Holds the keys down for the frames, then releases them
+    fn press(&mut self, keys: &[u8], frames: usize) -> &mut Self {
+        for &key in keys {
+            self.program.set_key_down(key);
+        }
+        self.run(frames);
+        for &key in keys {
+            self.program.set_key_up(key);
+        }
+        self
+    }
+
+    fn screen(&self) -> String {
+        let width = self.program.width() as usize;
+        self.program
+            .get_display()
+            .chunks(width)
+            .map(|row| {
+                row.iter()
+                    .map(|&planes| if planes == 0 { '.' } else { '#' })
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect()
+    }
+
+    fn assert_screen(&self, golden: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{golden}.txt"));
+        let actual = self.screen();
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, &actual).expect("Could not write the golden image");
+            return;
+        }
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Could not read {path:?}, run with UPDATE_GOLDEN=1: {e}"));
+        if actual != expected {
+            panic!(
+                "The screen doesn't match {path:?}\n{}",
+                diff(&expected, &actual)
+            );
+        }
+    }
+}
+
+/// The expected and actual screens side by side, with the rows that differ marked
+fn diff(expected: &str, actual: &str) -> String {
+    let mut expected_rows = expected.lines();
+    let mut actual_rows = actual.lines();
+    let mut diff = String::from("  expected | actual\n");
+    loop {
+        let (expected_row, actual_row) = match (expected_rows.next(), actual_rows.next()) {
+            (None, None) => break,
+            (expected_row, actual_row) => (
+                expected_row.unwrap_or_default(),
+                actual_row.unwrap_or_default(),
+            ),
+        };
+        let marker = if expected_row == actual_row { ' ' } else { '!' };
+        diff.push_str(&format!("{marker} {expected_row} | {actual_row}\n"));
+    }
+    diff
+}
+
+#[test]
+fn chip8_logo() {
+    TestRom::new("1-chip8-logo.ch8", Preset::Vip)
+        .run(60)
+        .assert_screen("1-chip8-logo");
+}
+
+#[test]
+fn ibm_logo() {
+    TestRom::new("2-ibm-logo.ch8", Preset::Vip)
+        .run(60)
+        .assert_screen("2-ibm-logo");
+}
+
+#[test]
+fn corax_opcodes() {
+    TestRom::new("3-corax+.ch8", Preset::Vip)
+        .run(120)
+        .assert_screen("3-corax+");
+}
+
+#[test]
+fn flags() {
+    TestRom::new("4-flags.ch8", Preset::Vip)
+        .run(120)
+        .assert_screen("4-flags");
+}
+
+#[test]
+fn quirks_vip() {
+    TestRom::new("5-quirks.ch8", Preset::Vip)
+        .with_menu_choice(1)
+        .run(1200)
+        .assert_screen("5-quirks-vip");
+}
+
+#[test]
+fn quirks_super_chip() {
+    TestRom::new("5-quirks.ch8", Preset::SuperChip)
+        .with_menu_choice(2)
+        .run(600)
+        .assert_screen("5-quirks-super-chip");
+}
+
+#[test]
+fn quirks_xo_chip() {
+    TestRom::new("5-quirks.ch8", Preset::XoChip)
+        .with_menu_choice(3)
+        .run(600)
+        .assert_screen("5-quirks-xo-chip");
+}
+
+#[test]
+fn keypad_pressed() {
+    // EX9E shows the keys that are down
+    let mut test = TestRom::new("6-keypad.ch8", Preset::Vip).with_menu_choice(1);
+    test.run(60);
+    for &key in &[0x1, 0x5, 0xF] {
+        test.program.set_key_down(key);
+    }
+    test.run(60).assert_screen("6-keypad-pressed");
+}
+
+#[test]
+fn keypad_released() {
+    // EXA1 shows the keys that are up, so releasing a key must only release that key
+    let mut test = TestRom::new("6-keypad.ch8", Preset::Vip).with_menu_choice(2);
+    test.run(60);
+    for &key in &[0x1, 0x5, 0xF] {
+        test.program.set_key_down(key);
+    }
+    test.program.set_key_up(0x5);
+    test.run(60).assert_screen("6-keypad-released");
+}
+
+#[test]
+fn keypad_get_key() {
+    // FX0A waits for a key, including F
+    TestRom::new("6-keypad.ch8", Preset::Vip)
+        .with_menu_choice(3)
+        .run(60)
+        .press(&[0xF], 10)
+        .run(60)
+        .assert_screen("6-keypad-get-key");
+}
+
+#[test]
+fn beep() {
+    // the sound plays while B is held
+    let mut test = TestRom::new("7-beep.ch8", Preset::Vip);
+    test.run(60);
+    assert_eq!(test.program.sound_timer, 0, "The beep started on its own");
+    test.program.set_key_down(0xB);
+    test.run(10);
+    assert_ne!(test.program.sound_timer, 0, "Holding B didn't beep");
+    test.assert_screen("7-beep");
+}
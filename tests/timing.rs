@@ -0,0 +1,75 @@
+//! The COSMAC VIP timing runs as many instructions in a frame as their costs allow.
+
+use chip_8::{
+    assembler::assemble,
+    emulator::{Program, FETCH_CYCLES, VIP_CYCLES_PER_FRAME},
+    host::NativeHost,
+};
+
+fn load(source: &str) -> Program {
+    let rom = assemble(source).unwrap().rom;
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.load_rom(&rom).unwrap();
+    program
+}
+
+/// The instructions that ran in each frame
+fn frames(program: &mut Program, count: usize) -> Vec<u32> {
+    (0..count)
+        .map(|_| {
+            let instructions = program.run_vip_frame().unwrap();
+            program.timer_tick();
+            instructions
+        })
+        .collect()
+}
+
+#[test]
+fn the_interpreter_gets_what_the_display_leaves() {
+    assert_eq!(VIP_CYCLES_PER_FRAME, 1836);
+    assert_eq!(FETCH_CYCLES, 40);
+}
+
+#[test]
+fn cheap_instructions_fill_the_frame() {
+    // 6XNN costs 6 + 40 and 1NNN 12 + 40, the instruction that goes over the frame takes
+    // its cycles from the next one
+    let mut program = load(
+        "
+        : main
+          v0 := 1
+          jump main",
+    );
+    assert_eq!(frames(&mut program, 4), [38, 37, 38, 37]);
+}
+
+#[test]
+fn clearing_the_screen_takes_more_than_a_frame() {
+    // 00E0 costs 3078 + 40, so the frames after it only run the jump and the next clear,
+    // or nothing
+    let mut program = load(
+        "
+        : main
+          clear
+          jump main",
+    );
+    assert_eq!(frames(&mut program, 6), [1, 2, 0, 2, 0, 2]);
+}
+
+#[test]
+fn sprites_wait_for_the_display() {
+    let mut program = load(
+        "
+        : main
+          i := dot
+        : loop
+          sprite v0 v0 1
+          v0 += 1
+          jump loop
+        : dot
+          0x80",
+    );
+    // the first frame starts without a display interrupt, then every frame draws once
+    assert_eq!(frames(&mut program, 4), [1, 3, 3, 3]);
+    assert_eq!(program.variable_regsiters[0], 3);
+}
@@ -0,0 +1,177 @@
+//! The watchpoints stop the emulator after the instruction that reads or writes their
+//! memory, and before an instruction in their memory.
+
+use chip_8::{
+    assembler::assemble,
+    emulator::{Access, Program, Watchpoint, WatchpointHit},
+    error::EmulatorError,
+    host::NativeHost,
+};
+
+const DATA: u16 = 0x300;
+
+fn load(source: &str) -> Program {
+    let rom = assemble(source).unwrap().rom;
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program.load_rom(&rom).unwrap();
+    program
+}
+
+fn watch(start: u16, end: u16, read: bool, write: bool, execute: bool) -> Watchpoint {
+    Watchpoint {
+        start,
+        end,
+        read,
+        write,
+        execute,
+    }
+}
+
+/// Ticks until the program stops, at most `limit` times
+fn run_until_stop(program: &mut Program, limit: usize) -> Option<EmulatorError> {
+    (0..limit).find_map(|_| program.tick().err())
+}
+
+#[test]
+fn writes_stop_after_the_instruction_with_the_old_and_new_values() {
+    let mut program = load(
+        "
+        : main
+          v0 := 1
+          v1 := 2
+          i := 0x300
+          save v1
+          v2 := 3
+        : end
+          jump end",
+    );
+    program.memory[DATA as usize..DATA as usize + 2].copy_from_slice(&[7, 8]);
+    program
+        .watchpoints
+        .push(watch(DATA + 1, DATA + 1, false, true, false));
+    assert_eq!(
+        run_until_stop(&mut program, 10),
+        Some(EmulatorError::Watchpoint {
+            program_counter: 0x206,
+            address: DATA,
+            access: Access::Write,
+        })
+    );
+    // stopped before the next instruction
+    assert_eq!(program.program_counter, 0x208);
+    assert_eq!(program.variable_regsiters[2], 0);
+    assert_eq!(
+        program.watchpoint_hit,
+        Some(WatchpointHit {
+            access: Access::Write,
+            instruction_address: 0x206,
+            opcode: 0xF155,
+            address: DATA,
+            old: vec![7, 8],
+            new: vec![1, 2],
+        })
+    );
+    // it goes on from there
+    program.tick().unwrap();
+    assert_eq!(program.variable_regsiters[2], 3);
+}
+
+#[test]
+fn reads_of_the_instructions_stop() {
+    let source = "
+        : main
+          i := 0x300
+          load v1
+          i := 0x300
+          bcd v0
+          i := 0x300
+          sprite v0 v0 4
+        : end
+          jump end";
+    let mut program = load(source);
+    program
+        .watchpoints
+        .push(watch(DATA, DATA + 3, true, false, false));
+    // load, and the sprite but not bcd, which only writes
+    let stops: Vec<u16> = (0..10)
+        .filter_map(|_| match program.tick() {
+            Err(EmulatorError::Watchpoint {
+                program_counter, ..
+            }) => Some(program_counter),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(stops, [0x202, 0x20A]);
+    let hit = program.watchpoint_hit.unwrap();
+    assert_eq!(hit.access, Access::Read);
+    assert_eq!(hit.new.len(), 4);
+
+    let mut program = load(source);
+    program
+        .watchpoints
+        .push(watch(DATA + 2, DATA + 2, false, true, false));
+    assert_eq!(
+        run_until_stop(&mut program, 10),
+        Some(EmulatorError::Watchpoint {
+            program_counter: 0x206,
+            address: DATA,
+            access: Access::Write,
+        })
+    );
+}
+
+#[test]
+fn execution_stops_before_the_instruction() {
+    let mut program = load(
+        "
+        : main
+          v0 := 1
+          v1 := 2
+        : end
+          jump end",
+    );
+    program
+        .watchpoints
+        .push(watch(0x201, 0x201, false, false, true));
+    assert_eq!(
+        program.tick(),
+        Err(EmulatorError::Watchpoint {
+            program_counter: 0x200,
+            address: 0x200,
+            access: Access::Execute,
+        })
+    );
+    assert_eq!(program.variable_regsiters[0], 0);
+    program.tick().unwrap();
+    assert_eq!(program.variable_regsiters[0], 1);
+    program.tick().unwrap();
+    assert_eq!(program.variable_regsiters[1], 2);
+}
+
+#[test]
+fn other_memory_and_loading_dont_stop() {
+    let mut program = Program::new(Box::new(NativeHost::new()));
+    program
+        .watchpoints
+        .push(watch(0x200, 0xFFF, true, true, false));
+    let rom = assemble(
+        "
+        : main
+          v0 := 0x10
+          i := 0x300
+          i += v0
+          save v0
+          i := 0x310
+          i += v0
+          load v0
+        : end
+          jump end",
+    )
+    .unwrap()
+    .rom;
+    program.load_rom(&rom).unwrap();
+    program.watchpoints = vec![watch(DATA, DATA + 0xF, true, true, false)];
+    // I moves into the range and out again, but only the memory accesses count
+    assert_eq!(run_until_stop(&mut program, 20), None);
+    assert_eq!(program.watchpoint_hit, None);
+}